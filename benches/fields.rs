@@ -0,0 +1,100 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde_json::Value;
+use tracing::info_span;
+use tracing_splunk_layer::{FieldMap, KeyCase, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+// the names a typical request span ends up with, metadata included
+const NAMES: [&str; 8] = [
+    "name",
+    "level",
+    "target",
+    "method",
+    "path",
+    "status",
+    "user",
+    "elapsed_time",
+];
+
+fn fill<M: Default>(insert: impl Fn(&mut M, Cow<'static, str>, Value)) -> M {
+    let mut map = M::default();
+    for (i, name) in NAMES.iter().enumerate() {
+        insert(&mut map, Cow::Borrowed(name), Value::from(i));
+    }
+    map
+}
+
+fn maps(c: &mut Criterion) {
+    let mut group = c.benchmark_group("8 fields");
+    group.bench_function("FieldMap", |b| {
+        b.iter(|| {
+            let map = fill::<FieldMap>(|m, k, v| {
+                m.insert(k, v);
+            });
+            let found = NAMES.iter().filter(|n| map.contains_key(n)).count();
+            black_box((serde_json::to_string(&map).unwrap(), found))
+        })
+    });
+    group.bench_function("HashMap", |b| {
+        b.iter(|| {
+            let map = fill::<HashMap<Cow<'static, str>, Value>>(|m, k, v| {
+                m.insert(k, v);
+            });
+            let found = NAMES.iter().filter(|n| map.contains_key(**n)).count();
+            black_box((serde_json::to_string(&map).unwrap(), found))
+        })
+    });
+    group.finish();
+}
+
+// the whole trip through the layer, up to the point the worker would send it
+fn spans(c: &mut Criterion) {
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .writer(std::io::sink)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    c.bench_function("span with 4 fields", |b| {
+        b.iter(|| {
+            info_span!(
+                "request",
+                method = "GET",
+                path = "/",
+                status = 200,
+                user = "ferris"
+            )
+            .in_scope(|| {})
+        })
+    });
+}
+
+// names that aren't the callsite's own, which are only built the first time they're needed
+fn renamed_spans(c: &mut Criterion) {
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .writer(std::io::sink)
+        .rename_field("user", "src_user")
+        .key_case(KeyCase::Camel)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    c.bench_function("span with 4 renamed fields", |b| {
+        b.iter(|| {
+            info_span!(
+                "request",
+                http_method = "GET",
+                url_path = "/",
+                status = 200,
+                user = "ferris"
+            )
+            .in_scope(|| {})
+        })
+    });
+}
+
+criterion_group!(benches, maps, spans, renamed_spans);
+criterion_main!(benches);
@@ -0,0 +1,151 @@
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use tracing::{debug, info, info_span, Dispatch};
+use tracing_splunk_layer::{SplunkHecLayer, WorkerGuard};
+use tracing_subscriber::prelude::*;
+
+// a layer that does everything but send, so what's measured is the cost to the application
+fn dispatch() -> (Dispatch, WorkerGuard) {
+    let (layer, guard) = SplunkHecLayer::builder()
+        .writer(std::io::sink)
+        .build()
+        .unwrap();
+    (tracing_subscriber::registry().with(layer).into(), guard)
+}
+
+fn single_threaded(c: &mut Criterion) {
+    let (dispatch, _guard) = dispatch();
+    let _default = tracing::dispatcher::set_default(&dispatch);
+    let mut group = c.benchmark_group("single threaded");
+
+    // just on_new_span, the span is closed outside the measurement
+    group.bench_function("new span", |b| {
+        b.iter_batched(
+            || (),
+            |()| info_span!("request", method = "GET", path = "/", status = 200),
+            BatchSize::SmallInput,
+        )
+    });
+    // on_event merging into the span it's in
+    let span = info_span!("request", method = "GET");
+    group.bench_function("event in a span", |b| {
+        b.iter(|| span.in_scope(|| info!(rows = 5, "query done")))
+    });
+    drop(span);
+    // on_close, where inheritance, the exporter's pipeline and handing it to the worker happen
+    group.bench_function("close and export", |b| {
+        b.iter_batched(
+            || {
+                let root = info_span!("root", tenant = "acme");
+                let child = root.in_scope(|| info_span!("request", method = "GET"));
+                (root, child)
+            },
+            |(root, child)| {
+                drop(child);
+                drop(root);
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("top level event", |b| {
+        b.iter(|| info!(rows = 5, "query done"))
+    });
+    group.finish();
+}
+
+// run `work` on `threads` threads at once, `iters` times each, and report how long the slowest
+// took. they all start together so they're actually fighting over whatever they share.
+fn concurrently(
+    dispatch: &Dispatch,
+    threads: usize,
+    iters: u64,
+    work: impl Fn() + Send + Sync + 'static,
+) -> Duration {
+    let work = Arc::new(work);
+    let start = Arc::new(Barrier::new(threads));
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let (dispatch, work, start) = (dispatch.clone(), work.clone(), start.clone());
+            thread::spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    start.wait();
+                    let began = Instant::now();
+                    for _ in 0..iters {
+                        work();
+                    }
+                    began.elapsed()
+                })
+            })
+        })
+        .collect();
+    handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .max()
+        .unwrap_or_default()
+}
+
+fn contended(c: &mut Criterion) {
+    let threads = thread::available_parallelism()
+        .map_or(4, |n| n.get())
+        .max(2);
+    let (dispatch, _guard) = dispatch();
+    let mut group = c.benchmark_group(format!("{} threads", threads));
+
+    // every thread with spans of its own, which only share the registry and the worker's queue
+    group.bench_function("own spans", |b| {
+        b.iter_custom(|iters| {
+            concurrently(&dispatch, threads, iters, || {
+                info_span!("request", method = "GET").in_scope(|| info!(rows = 5, "query done"))
+            })
+        })
+    });
+    // every thread recording into the same span, where they all want its extensions_mut()
+    group.bench_function("one shared span", |b| {
+        let shared = tracing::dispatcher::with_default(&dispatch, || info_span!("batch_job"));
+        b.iter_custom(|iters| {
+            let shared = shared.clone();
+            concurrently(&dispatch, threads, iters, move || {
+                shared.in_scope(|| info!(rows = 5, "query done"))
+            })
+        })
+    });
+    group.bench_function("top level events", |b| {
+        b.iter_custom(|iters| {
+            concurrently(&dispatch, threads, iters, || info!(rows = 5, "query done"))
+        })
+    });
+    group.finish();
+}
+
+// a debug event the layer doesn't export, checked in its callbacks or cached per callsite
+fn filtered_out(c: &mut Criterion) {
+    let mut group = c.benchmark_group("filtered out");
+    for filtered in [false, true] {
+        let (layer, _guard) = SplunkHecLayer::builder()
+            .writer(std::io::sink)
+            .max_level(tracing::Level::INFO)
+            .build()
+            .unwrap();
+        let dispatch: Dispatch = if filtered {
+            tracing_subscriber::registry().with(layer.filtered()).into()
+        } else {
+            tracing_subscriber::registry().with(layer).into()
+        };
+        let name = if filtered {
+            "per-layer filter"
+        } else {
+            "layer"
+        };
+        tracing::dispatcher::with_default(&dispatch, || {
+            group.bench_function(name, |b| b.iter(|| debug!(rows = 5, "query done")));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, single_threaded, contended, filtered_out);
+criterion_main!(benches);
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::batch::Batch;
+use crate::hec::{HecError, HecResponse};
+
+pub const DEFAULT_ACK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+// splunk's own default for how long an indexer may sit on an ack before giving up on it
+pub const DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_ACK_MAX_RESENDS: u32 = 3;
+
+// indexer acknowledgment, for HEC tokens that have useAck turned on. every request is sent on a
+// channel and HEC answers with an ack id instead of a promise that the events were indexed. the
+// worker keeps each batch around until polling /services/collector/ack says it made it, and sends
+// it again if that doesn't happen within `timeout`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AckConfig {
+    // the X-Splunk-Request-Channel every request is sent with, any GUID will do
+    pub channel: String,
+    // how often to ask HEC about outstanding acks
+    pub poll_interval: Duration,
+    // how long a batch can go unacknowledged before it's sent again
+    pub timeout: Duration,
+    // how many times a batch is sent again before it's given up on
+    pub max_resends: u32,
+}
+
+impl Default for AckConfig {
+    fn default() -> Self {
+        AckConfig::with_channel(random_channel())
+    }
+}
+
+impl AckConfig {
+    // acknowledgment on a freshly made up channel
+    pub fn new() -> Self {
+        AckConfig::default()
+    }
+
+    pub fn with_channel(channel: impl Into<String>) -> Self {
+        AckConfig {
+            channel: channel.into(),
+            poll_interval: DEFAULT_ACK_POLL_INTERVAL,
+            timeout: DEFAULT_ACK_TIMEOUT,
+            max_resends: DEFAULT_ACK_MAX_RESENDS,
+        }
+    }
+}
+
+// a random (v4) uuid, HEC doesn't care where the channel came from as long as it's shaped like one
+pub(crate) fn random_channel() -> String {
+    let bits = fastrand::u128(..);
+    let bits = (bits & !(0xf << 76)) | (0x4 << 76);
+    let bits = (bits & !(0x3 << 62)) | (0x2 << 62);
+    let hex = format!("{:032x}", bits);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+// HEC's answer to an ack query, which ack ids have been indexed so far
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+pub struct AckStatus {
+    pub acks: HashMap<u64, bool>,
+}
+
+impl AckStatus {
+    // the ack endpoint reports failures the same way the event endpoint does
+    pub fn parse(status: u16, body: &str) -> Result<Self, HecError> {
+        HecResponse::parse(status, None, body)?;
+        serde_json::from_str(body).map_err(HecError::transport)
+    }
+
+    pub fn is_acked(&self, ack_id: u64) -> bool {
+        self.acks.get(&ack_id).copied().unwrap_or(false)
+    }
+}
+
+// a batch HEC has taken but not yet told us was indexed
+pub(crate) struct Pending {
+    pub(crate) ack_id: u64,
+    pub(crate) batch: Batch,
+    pub(crate) sent_at: Instant,
+    // how many times this batch has already been sent again
+    pub(crate) resends: u32,
+}
+
+// the worker's bookkeeping for outstanding acks
+pub(crate) struct AckTracker {
+    pub(crate) config: AckConfig,
+    pending: Vec<Pending>,
+    last_poll: Instant,
+}
+
+impl AckTracker {
+    pub(crate) fn new(config: AckConfig) -> Self {
+        AckTracker {
+            config,
+            pending: Vec::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub(crate) fn track(&mut self, ack_id: u64, batch: Batch, resends: u32) {
+        if self.pending.is_empty() {
+            // no point polling straight away for something that was only just sent
+            self.last_poll = Instant::now();
+        }
+        self.pending.push(Pending {
+            ack_id,
+            batch,
+            sent_at: Instant::now(),
+            resends,
+        });
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // how long until the next poll, or None if nothing is waiting on an ack
+    pub(crate) fn time_until_poll(&self) -> Option<Duration> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(
+            self.config
+                .poll_interval
+                .saturating_sub(self.last_poll.elapsed()),
+        )
+    }
+
+    pub(crate) fn ack_ids(&self) -> Vec<u64> {
+        self.pending.iter().map(|p| p.ack_id).collect()
+    }
+
+    // forget about everything HEC says was indexed
+    pub(crate) fn acknowledge(&mut self, status: &AckStatus) {
+        self.last_poll = Instant::now();
+        self.pending.retain(|p| !status.is_acked(p.ack_id));
+    }
+
+    pub(crate) fn polled(&mut self) {
+        self.last_poll = Instant::now();
+    }
+
+    // take out every batch that's been waiting longer than the timeout
+    pub(crate) fn take_expired(&mut self) -> Vec<Pending> {
+        let timeout = self.config.timeout;
+        let (expired, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|p| p.sent_at.elapsed() >= timeout);
+        self.pending = pending;
+        expired
+    }
+
+    // everything that's still waiting on an ack
+    pub(crate) fn take_all(&mut self) -> Vec<Pending> {
+        std::mem::take(&mut self.pending)
+    }
+}
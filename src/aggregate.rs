@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::hec::HecMetadata;
+use crate::intern::intern_fmt;
+use crate::record::EventRecord;
+use crate::time::{ElapsedTime, HecTime, TimestampPrecision};
+use crate::EventHash;
+
+// for spans that close too often to ship every one, keep a duration histogram and count for each
+// span name instead, and send a summary of each of them every `interval`:
+//
+//   name=db.query span_summary=true count=1200 errors=4 interval=60
+//   elapsed_time.min=1 elapsed_time.max=830 elapsed_time.avg=12
+//   elapsed_time.buckets={"1": 80, "5": 700, ..., "+Inf": 2}
+//
+// in the elapsed time's unit (bucket bounds with a fraction if they need one, whether or not the
+// values have one), each bucket counting the spans that took up to its bound (and more than the
+// one before). whatever has built up is sent when the worker shuts down too.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanAggregation {
+    // the span names to aggregate, every span when it's empty
+    pub spans: Vec<String>,
+    pub interval: Duration,
+    // the upper bound of each histogram bucket, smallest first
+    pub buckets: Vec<Duration>,
+    // only send the summaries, not the aggregated spans themselves
+    pub exclude_from_export: bool,
+}
+
+impl Default for SpanAggregation {
+    fn default() -> Self {
+        let buckets = [1, 2, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+        SpanAggregation {
+            spans: Vec::new(),
+            interval: Duration::from_secs(60),
+            buckets: buckets.into_iter().map(Duration::from_millis).collect(),
+            exclude_from_export: false,
+        }
+    }
+}
+
+// the summaries being built up, shared by the layer recording into it and the worker sending it
+pub(crate) struct Aggregator {
+    config: SpanAggregation,
+    elapsed: ElapsedTime,
+    metadata: HecMetadata,
+    global_fields: EventHash,
+    precision: TimestampPrecision,
+    window: Mutex<Window>,
+}
+
+struct Window {
+    started: SystemTime,
+    due: Instant,
+    spans: HashMap<&'static str, Summary>,
+}
+
+struct Summary {
+    count: u64,
+    errors: u64,
+    min: Duration,
+    max: Duration,
+    total: Duration,
+    // one more than there are bounds, for everything over the last one
+    buckets: Vec<u64>,
+}
+
+impl Window {
+    fn new(interval: Duration) -> Self {
+        Window {
+            started: SystemTime::now(),
+            due: Instant::now() + interval,
+            spans: HashMap::new(),
+        }
+    }
+}
+
+impl Aggregator {
+    pub(crate) fn new(
+        config: SpanAggregation,
+        elapsed: ElapsedTime,
+        metadata: HecMetadata,
+        global_fields: EventHash,
+        precision: TimestampPrecision,
+    ) -> Self {
+        let window = Mutex::new(Window::new(config.interval));
+        Aggregator {
+            config,
+            elapsed,
+            metadata,
+            global_fields,
+            precision,
+            window,
+        }
+    }
+
+    pub(crate) fn aggregates(&self, name: &str) -> bool {
+        self.config.spans.is_empty() || self.config.spans.iter().any(|s| s == name)
+    }
+
+    pub(crate) fn exclude_from_export(&self) -> bool {
+        self.config.exclude_from_export
+    }
+
+    pub(crate) fn record(&self, name: &'static str, elapsed: Duration, saw_error: bool) {
+        let bucket = self
+            .config
+            .buckets
+            .iter()
+            .position(|bound| elapsed <= *bound)
+            .unwrap_or(self.config.buckets.len());
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        let summary = window.spans.entry(name).or_insert_with(|| Summary {
+            count: 0,
+            errors: 0,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+            total: Duration::ZERO,
+            buckets: vec![0; self.config.buckets.len() + 1],
+        });
+        summary.count += 1;
+        summary.errors += u64::from(saw_error);
+        summary.min = summary.min.min(elapsed);
+        summary.max = summary.max.max(elapsed);
+        summary.total += elapsed;
+        summary.buckets[bucket] += 1;
+    }
+
+    pub(crate) fn time_until_due(&self) -> Duration {
+        let window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        window.due.saturating_duration_since(Instant::now())
+    }
+
+    // the summaries for the window that's ended, if it has, and a fresh one started. `force` ends
+    // it early, for shutting down.
+    pub(crate) fn take_due(&self, force: bool) -> Vec<EventRecord> {
+        let window = {
+            let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+            if !force && window.due > Instant::now() {
+                return Vec::new();
+            }
+            std::mem::replace(&mut *window, Window::new(self.config.interval))
+        };
+        window
+            .spans
+            .into_iter()
+            .map(|(name, summary)| self.summary_record(window.started, name, summary))
+            .collect()
+    }
+
+    fn summary_record(&self, started: SystemTime, name: &str, summary: Summary) -> EventRecord {
+        let mut event = EventHash::new();
+        event.insert("name".into(), name.to_owned().into());
+        event.insert("span_summary".into(), true.into());
+        event.insert("count".into(), summary.count.into());
+        event.insert("errors".into(), summary.errors.into());
+        event.insert("interval".into(), self.config.interval.as_secs_f64().into());
+
+        let field = &self.elapsed.field;
+        let average = summary.total.div_f64(summary.count.max(1) as f64);
+        event.insert(
+            intern_fmt(format_args!("{}.min", field)),
+            self.elapsed.value(summary.min),
+        );
+        event.insert(
+            intern_fmt(format_args!("{}.max", field)),
+            self.elapsed.value(summary.max),
+        );
+        event.insert(
+            intern_fmt(format_args!("{}.avg", field)),
+            self.elapsed.value(average),
+        );
+        let mut buckets = serde_json::Map::new();
+        for (bound, count) in self.config.buckets.iter().zip(&summary.buckets) {
+            buckets.insert(self.elapsed.label(*bound), (*count).into());
+        }
+        let overflow = summary.buckets.last().copied().unwrap_or_default();
+        buckets.insert("+Inf".to_owned(), overflow.into());
+        event.insert(
+            intern_fmt(format_args!("{}.buckets", field)),
+            buckets.into(),
+        );
+
+        for (name, value) in &self.global_fields {
+            event.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        EventRecord {
+            time: HecTime::new(started, self.precision),
+            metadata: self.metadata.clone(),
+            event,
+            ..EventRecord::default()
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+pub const DEFAULT_MAX_BATCH_EVENTS: usize = 100;
+// HEC's default max_content_length is 800MB on newer releases but only 1MB on older ones, so stay
+// comfortably under the smaller of the two
+pub const DEFAULT_MAX_BATCH_BYTES: usize = 512 * 1024;
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// when the worker should stop accumulating and ship what it has. whichever limit is hit first wins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchConfig {
+    pub max_events: usize,
+    pub max_bytes: usize,
+    pub flush_interval: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            max_events: DEFAULT_MAX_BATCH_EVENTS,
+            max_bytes: DEFAULT_MAX_BATCH_BYTES,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+        }
+    }
+}
+
+// bounds for SplunkHecLayerBuilder::adaptive_batching. the worker starts from the batch limits it
+// was given and then, whenever a batch fills up (or more than a batch's worth is waiting in the
+// queue behind it), doubles max_events and flush_interval so a busy service sends fewer, bigger
+// requests. when the flush interval runs out on a batch that's not even a quarter full it halves
+// them again, so a quiet one doesn't sit on its few events for long. neither ever goes past these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdaptiveBatching {
+    pub min_events: usize,
+    pub max_events: usize,
+    pub min_flush_interval: Duration,
+    pub max_flush_interval: Duration,
+}
+
+impl Default for AdaptiveBatching {
+    fn default() -> Self {
+        AdaptiveBatching {
+            min_events: 10,
+            max_events: 1000,
+            min_flush_interval: Duration::from_millis(200),
+            max_flush_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl AdaptiveBatching {
+    // `config` pulled inside the bounds, which is where the worker starts
+    pub(crate) fn clamp(&self, config: BatchConfig) -> BatchConfig {
+        BatchConfig {
+            max_events: config
+                .max_events
+                .clamp(self.min_events.max(1), self.max_events.max(self.min_events)),
+            flush_interval: config.flush_interval.clamp(
+                self.min_flush_interval,
+                self.max_flush_interval.max(self.min_flush_interval),
+            ),
+            ..config
+        }
+    }
+
+    // bigger batches, less often
+    pub(crate) fn grow(&self, config: BatchConfig) -> BatchConfig {
+        self.clamp(BatchConfig {
+            max_events: config.max_events.saturating_mul(2),
+            flush_interval: config.flush_interval.saturating_mul(2),
+            ..config
+        })
+    }
+
+    // smaller batches, sooner
+    pub(crate) fn shrink(&self, config: BatchConfig) -> BatchConfig {
+        self.clamp(BatchConfig {
+            max_events: config.max_events / 2,
+            flush_interval: config.flush_interval / 2,
+            ..config
+        })
+    }
+}
+
+// HEC happily accepts several json events stacked one after another in a single POST, so a batch
+// is just the serialized events joined by newlines. this is what a Transport gets handed.
+#[derive(Clone, Debug, Default)]
+pub struct Batch {
+    buf: String,
+    len: usize,
+    // when the oldest event in the batch showed up, used for the flush interval
+    started: Option<Instant>,
+}
+
+impl Batch {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // how many events are in the batch
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.buf
+    }
+
+    // true if adding `payload` would push us past the byte limit. an empty batch always has room
+    // so a single oversized event still gets sent on its own instead of getting stuck.
+    pub(crate) fn would_overflow(&self, payload: &str, config: &BatchConfig) -> bool {
+        !self.is_empty() && self.buf.len() + 1 + payload.len() > config.max_bytes
+    }
+
+    pub(crate) fn push(&mut self, payload: &str) {
+        if self.is_empty() {
+            self.started = Some(Instant::now());
+        } else {
+            self.buf.push('\n');
+        }
+        self.buf.push_str(payload);
+        self.len += 1;
+    }
+
+    // serialize `value` straight onto the end of the batch, without a string of its own in
+    // between. if that takes a batch that already had events in it past the byte limit, the new
+    // event is cut back off and handed back to start the next batch with.
+    pub(crate) fn push_json(
+        &mut self,
+        value: &impl Serialize,
+        config: &BatchConfig,
+    ) -> Result<Option<String>, serde_json::Error> {
+        let had_events = !self.is_empty();
+        let mark = self.buf.len();
+        if had_events {
+            self.buf.push('\n');
+        }
+        if let Err(e) = serde_json::to_writer(StrWriter(&mut self.buf), value) {
+            self.buf.truncate(mark);
+            return Err(e);
+        }
+        if had_events && self.buf.len() > config.max_bytes {
+            let payload = self.buf.split_off(mark + 1);
+            self.buf.truncate(mark);
+            return Ok(Some(payload));
+        }
+        if !had_events {
+            self.started = Some(Instant::now());
+        }
+        self.len += 1;
+        Ok(None)
+    }
+
+    pub(crate) fn is_full(&self, config: &BatchConfig) -> bool {
+        self.len >= config.max_events || self.buf.len() >= config.max_bytes
+    }
+
+    // how long until the flush interval runs out, or None if there's nothing waiting
+    pub(crate) fn time_until_flush(&self, config: &BatchConfig) -> Option<Duration> {
+        self.started
+            .map(|started| config.flush_interval.saturating_sub(started.elapsed()))
+    }
+
+    // empty the batch out, keeping the buffer's allocation around for the next one
+    pub(crate) fn clear(&mut self) {
+        self.buf.clear();
+        self.len = 0;
+        self.started = None;
+    }
+}
+
+// lets serde_json write into a String. it only ever writes whole utf-8 sequences at a time (it
+// splits strings at escapes, which are all ascii), so checking each write on its own is enough.
+struct StrWriter<'a>(&'a mut String);
+
+impl io::Write for StrWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let s =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.0.push_str(s);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,1353 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::ack::AckConfig;
+use crate::aggregate::{Aggregator, SpanAggregation};
+use crate::batch::{AdaptiveBatching, BatchConfig};
+use crate::bytes::ByteEncoding;
+use crate::cim::CimModel;
+use crate::circuit::CircuitBreakerConfig;
+use crate::clock::{Clock, SystemClock};
+#[cfg(feature = "cloud-metadata")]
+use crate::cloud::CloudMetadata;
+use crate::dead_letter::DeadLetterSink;
+use crate::destination::Destination;
+use crate::error::{Error, ErrorPolicy};
+use crate::event_limit::SpanEventLimit;
+use crate::export::Exporter;
+use crate::fallback::FallbackSink;
+use crate::filter::{ExportFilter, FilterRules};
+use crate::hec::{self, HecMetadata};
+use crate::hooks::{BatchSummary, ExportHooks};
+use crate::metadata::MetadataFields;
+use crate::metric::SpanMetrics;
+use crate::metrics::Counters;
+use crate::oversize::{OversizedEvent, SizeLimit};
+use crate::parse::DebugParsing;
+use crate::probe::ProbeError;
+use crate::process::ProcessFields;
+use crate::processor::Processor;
+use crate::proxy::{Proxy, ProxyConfig};
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
+use crate::raw::LineFormatter;
+use crate::record::{EventRecord, MessageField};
+use crate::redact::Redactor;
+use crate::rename::{FieldRenames, KeyCase};
+use crate::retry::RetryPolicy;
+use crate::routing::{LevelRoutes, Route, RouteKey, Router, TenantRoutes};
+use crate::sampling::{sample_ratio, HeadSampleRatio, TailSample, TailSampler};
+use crate::spool::{Spool, SpoolConfig};
+use crate::time::{ElapsedTime, TimestampPrecision};
+use crate::tls::{TlsConfig, TlsError};
+use crate::transport::{
+    block_on, FileConfig, FileTransport, InFlightLimit, SocketInput, Timeouts, Transport,
+    WriterTransport,
+};
+use crate::truncate::FieldLengths;
+use crate::worker::{
+    LoadShedding, QueueFullPolicy, WorkerConfig, WorkerGuard, WorkerHandle, WorkerRuntime,
+    DEFAULT_CHANNEL_CAPACITY,
+};
+use crate::{
+    EventHash, FieldCollision, FieldInheritance, RecordOptions, SpanEventMode, SpanExportMode,
+    SplunkHecLayer,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    MissingEndpoint,
+    MissingToken,
+    // a required environment variable wasn't set, see SplunkHecLayerBuilder::from_env
+    MissingEnv(&'static str),
+    // an environment variable was set to something that doesn't make sense for it
+    InvalidEnv {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+    // the startup probe found HEC down or the token no good
+    Probe(ProbeError),
+    // a config file couldn't be read or didn't make sense
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+    // no transport was given and the crate was built without a default one
+    MissingTransport,
+    // the spool directory couldn't be created or read
+    Spool {
+        dir: PathBuf,
+        kind: io::ErrorKind,
+    },
+    // the TLS config couldn't be used by the default transport
+    Tls(TlsError),
+    // the proxy url couldn't be made sense of
+    Proxy(String),
+    // a header (or the user agent) can't be sent as it is
+    InvalidHeader(String),
+    // the worker thread couldn't be started, with what the OS said about it
+    Worker(String),
+    // tokio_runtime was asked for, but build wasn't called from inside one
+    #[cfg(feature = "tokio")]
+    NoTokioRuntime,
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::MissingEndpoint => write!(f, "no HEC endpoint was configured"),
+            BuildError::MissingToken => write!(f, "no HEC token was configured"),
+            BuildError::MissingEnv(name) => write!(f, "{} isn't set", name),
+            BuildError::InvalidEnv {
+                name,
+                value,
+                reason,
+            } => write!(f, "{} is set to `{}`, {}", name, value, reason),
+            BuildError::Probe(e) => write!(f, "{}", e),
+            BuildError::Config { path, message } => {
+                write!(f, "invalid config file {}: {}", path.display(), message)
+            }
+            BuildError::MissingTransport => write!(
+                f,
+                "no transport was configured and no default transport feature is enabled"
+            ),
+            BuildError::Spool { dir, kind } => write!(
+                f,
+                "failed to open the spool directory {}: {}",
+                dir.display(),
+                kind
+            ),
+            BuildError::Tls(e) => write!(f, "{}", e),
+            BuildError::Proxy(e) => write!(f, "invalid proxy: {}", e),
+            BuildError::InvalidHeader(name) => write!(f, "invalid http header `{}`", name),
+            BuildError::Worker(e) => {
+                write!(f, "failed to start the splunk hec worker thread: {}", e)
+            }
+            #[cfg(feature = "tokio")]
+            BuildError::NoTokioRuntime => write!(f, "not called from inside a tokio runtime"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+// collects the deployment specific settings for a SplunkHecLayer. only the endpoint and token
+// are required (or a transport that knows them), everything else defaults to whatever the HEC
+// input is configured with.
+pub struct SplunkHecLayerBuilder {
+    endpoint: Option<String>,
+    token: Option<String>,
+    transport: Option<Box<dyn Transport>>,
+    // set by tcp, udp and the unix socket methods instead of `transport`, see SocketInput
+    socket: Option<SocketInput>,
+    // set when sending to the raw endpoint
+    raw: Option<Arc<dyn LineFormatter>>,
+    metadata: HecMetadata,
+    level_routes: LevelRoutes,
+    channel_capacity: usize,
+    queue_full_policy: QueueFullPolicy,
+    load_shedding: Option<LoadShedding>,
+    batch: BatchConfig,
+    adaptive_batching: Option<AdaptiveBatching>,
+    retry: RetryPolicy,
+    indexed_fields: Vec<String>,
+    global_fields: EventHash,
+    process_fields: ProcessFields,
+    #[cfg(feature = "cloud-metadata")]
+    cloud_metadata: Option<CloudMetadata>,
+    renames: FieldRenames,
+    message_field: MessageField,
+    // a CIM model wants the span's elapsed time as `duration`, under whatever name it ends up with
+    cim_duration: bool,
+    processors: Vec<Arc<dyn Processor>>,
+    timestamp_precision: TimestampPrecision,
+    clock: Arc<dyn Clock>,
+    elapsed_time: ElapsedTime,
+    metadata_fields: MetadataFields,
+    span_event_mode: SpanEventMode,
+    field_inheritance: FieldInheritance,
+    field_collision: FieldCollision,
+    span_hierarchy: bool,
+    span_export_mode: SpanExportMode,
+    checkpoint_interval: Option<Duration>,
+    span_event_limit: Option<SpanEventLimit>,
+    error_debug: bool,
+    max_lengths: FieldLengths,
+    byte_encoding: ByteEncoding,
+    debug_parsing: DebugParsing,
+    span_metrics: Option<SpanMetrics>,
+    aggregation: Option<SpanAggregation>,
+    rate_limit: Option<RateLimitConfig>,
+    max_event_bytes: Option<usize>,
+    oversized_events: OversizedEvent,
+    redactor: Redactor,
+    tail_sampler: TailSampler,
+    head_sample_ratio: f64,
+    filter: FilterRules,
+    error_policy: ErrorPolicy,
+    hooks: ExportHooks,
+    acks: Option<AckConfig>,
+    spool: Option<SpoolConfig>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+    dead_letters: Option<DeadLetterSink>,
+    fallback: Option<FallbackSink>,
+    destinations: Vec<Destination>,
+    tenant_router: Option<Arc<Router>>,
+    tenant_routes: Vec<(RouteKey, Route)>,
+    tls: Option<TlsConfig>,
+    proxy: Proxy,
+    headers: Vec<(String, String)>,
+    user_agent: Option<String>,
+    timeouts: Timeouts,
+    in_flight: Option<InFlightLimit>,
+    startup_probe: bool,
+    runtime: WorkerRuntime,
+    // use whatever tokio runtime build() is called from
+    #[cfg(feature = "tokio")]
+    ambient_tokio: bool,
+}
+
+impl Default for SplunkHecLayerBuilder {
+    fn default() -> Self {
+        SplunkHecLayerBuilder {
+            endpoint: None,
+            token: None,
+            transport: None,
+            socket: None,
+            raw: None,
+            metadata: HecMetadata::default(),
+            level_routes: LevelRoutes::default(),
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            queue_full_policy: QueueFullPolicy::default(),
+            load_shedding: None,
+            batch: BatchConfig::default(),
+            adaptive_batching: None,
+            retry: RetryPolicy::default(),
+            indexed_fields: Vec::new(),
+            global_fields: EventHash::new(),
+            process_fields: ProcessFields::default(),
+            #[cfg(feature = "cloud-metadata")]
+            cloud_metadata: None,
+            renames: FieldRenames::default(),
+            message_field: MessageField::default(),
+            cim_duration: false,
+            processors: Vec::new(),
+            timestamp_precision: TimestampPrecision::default(),
+            clock: Arc::new(SystemClock),
+            elapsed_time: ElapsedTime::default(),
+            metadata_fields: MetadataFields::default(),
+            span_event_mode: SpanEventMode::default(),
+            field_inheritance: FieldInheritance::default(),
+            field_collision: FieldCollision::default(),
+            span_hierarchy: false,
+            span_export_mode: SpanExportMode::default(),
+            checkpoint_interval: None,
+            span_event_limit: None,
+            error_debug: false,
+            max_lengths: FieldLengths::default(),
+            byte_encoding: ByteEncoding::default(),
+            debug_parsing: DebugParsing::default(),
+            span_metrics: None,
+            aggregation: None,
+            rate_limit: None,
+            max_event_bytes: None,
+            oversized_events: OversizedEvent::default(),
+            redactor: Redactor::default(),
+            tail_sampler: TailSampler::default(),
+            head_sample_ratio: 1.0,
+            filter: FilterRules::default(),
+            error_policy: ErrorPolicy::default(),
+            hooks: ExportHooks::default(),
+            acks: None,
+            spool: None,
+            circuit_breaker: None,
+            dead_letters: None,
+            fallback: None,
+            destinations: Vec::new(),
+            tenant_router: None,
+            tenant_routes: Vec::new(),
+            tls: None,
+            proxy: Proxy::default(),
+            headers: Vec::new(),
+            user_agent: None,
+            timeouts: Timeouts::default(),
+            in_flight: None,
+            startup_probe: false,
+            runtime: WorkerRuntime::default(),
+            #[cfg(feature = "tokio")]
+            ambient_tokio: false,
+        }
+    }
+}
+
+impl SplunkHecLayerBuilder {
+    pub fn new() -> Self {
+        SplunkHecLayerBuilder::default()
+    }
+
+    // the base url of the HEC input, e.g. https://splunk.example.com:8088
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    // ship batches with this instead of the default transport. the endpoint and token are up to
+    // the transport at that point, so they don't need to be set on the builder.
+    pub fn transport(mut self, transport: impl Transport) -> Self {
+        self.transport = Some(Box::new(transport));
+        self.socket = None;
+        self
+    }
+
+    // write newline delimited json with `make_writer` instead of sending it anywhere, see
+    // WriterTransport
+    pub fn writer<W>(self, make_writer: W) -> Self
+    where
+        W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    {
+        self.transport(WriterTransport::new(make_writer))
+    }
+
+    // write newline delimited json to a file that's rotated as it grows, for a universal
+    // forwarder to pick up, see FileTransport
+    pub fn file(self, config: FileConfig) -> Self {
+        self.transport(FileTransport::new(config))
+    }
+
+    // write newline delimited json to a splunk TCP input at `addr` (a host:port) instead of
+    // HEC, see TcpTransport. connect_timeout and request_timeout apply to it too.
+    pub fn tcp(self, addr: impl Into<String>) -> Self {
+        self.socket(SocketInput::Tcp(addr.into()))
+    }
+
+    // send each event as a UDP datagram to a splunk UDP input at `addr`, with no way of knowing
+    // whether it got there, see UdpTransport
+    pub fn udp(self, addr: impl Into<String>) -> Self {
+        self.socket(SocketInput::Udp(addr.into()))
+    }
+
+    // the same as tcp, to a forwarder listening on a unix socket at `path`, see
+    // UnixStreamTransport
+    #[cfg(unix)]
+    pub fn unix_stream(self, path: impl Into<PathBuf>) -> Self {
+        self.socket(SocketInput::UnixStream(path.into()))
+    }
+
+    // the same as udp, to a unix datagram socket at `path`, see UnixDatagramTransport
+    #[cfg(unix)]
+    pub fn unix_datagram(self, path: impl Into<PathBuf>) -> Self {
+        self.socket(SocketInput::UnixDatagram(path.into()))
+    }
+
+    // the socket transports are made in build, once the timeouts are known
+    fn socket(mut self, input: SocketInput) -> Self {
+        self.socket = Some(input);
+        self.transport = None;
+        self
+    }
+
+    // send each event to /services/collector/raw as a line of text written by `formatter`, e.g.
+    // Logfmt, instead of as json to the event endpoint. the raw endpoint has nowhere to put
+    // metadata for each event, so everything goes where the builder's index, source etc. say and
+    // routing fields are just more text. a transport given to the builder has to be pointed at the
+    // raw endpoint itself, see UreqTransport::raw.
+    pub fn raw_endpoint(mut self, formatter: impl LineFormatter) -> Self {
+        self.raw = Some(Arc::new(formatter));
+        self
+    }
+
+    pub fn index(mut self, index: impl Into<String>) -> Self {
+        self.metadata.index = Some(index.into());
+        self
+    }
+
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.metadata.source = Some(source.into());
+        self
+    }
+
+    pub fn sourcetype(mut self, sourcetype: impl Into<String>) -> Self {
+        self.metadata.sourcetype = Some(sourcetype.into());
+        self
+    }
+
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.metadata.host = Some(host.into());
+        self
+    }
+
+    // send spans and events at any of these levels to `index` instead, e.g.
+    // `.route_levels([Level::ERROR, Level::WARN], "alerts")`. a span goes by its own level, not
+    // that of the events inside it. a splunk.index field still wins over this.
+    pub fn route_levels<I>(mut self, levels: I, index: impl Into<String>) -> Self
+    where
+        I: IntoIterator<Item = tracing::Level>,
+    {
+        let index = index.into();
+        for level in levels {
+            self.level_routes.set(level, index.clone());
+        }
+        self
+    }
+
+    // send each tenant's spans and events to a HEC input of its own, for a multi-tenant service
+    // with an index and token per tenant. `router` picks the tenant from the finished record (e.g.
+    // a `tenant` field its spans all have), and every route gets a worker of its own so batches
+    // never mix tenants. anything `router` says None to, or whose tenant isn't in `routes`, goes
+    // to the main endpoint as usual.
+    //
+    // route workers batch, retry and break the circuit like the main one, but acks, the spool,
+    // the export hooks and the fallback and dead letter sinks are only for the main endpoint, and
+    // they always send json to the event endpoint.
+    pub fn tenant_routes<F, I>(mut self, router: F, routes: I) -> Self
+    where
+        F: Fn(&EventRecord) -> Option<RouteKey> + Send + Sync + 'static,
+        I: IntoIterator<Item = (RouteKey, Route)>,
+    {
+        self.tenant_router = Some(Arc::new(router));
+        self.tenant_routes.extend(routes);
+        self
+    }
+
+    // how many events can be queued up for the background worker
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    // what to do with new events once the queue is full
+    pub fn queue_full_policy(mut self, policy: QueueFullPolicy) -> Self {
+        self.queue_full_policy = policy;
+        self
+    }
+
+    // start dropping DEBUG and TRACE (or whatever LoadShedding says) while the queue is filling
+    // up, before it gets to the queue_full_policy
+    pub fn load_shedding(mut self, shedding: LoadShedding) -> Self {
+        self.load_shedding = Some(shedding);
+        self
+    }
+
+    // ship a batch once it holds this many events
+    pub fn max_batch_events(mut self, max_events: usize) -> Self {
+        self.batch.max_events = max_events;
+        self
+    }
+
+    // ship a batch once its serialized size reaches this many bytes
+    pub fn max_batch_bytes(mut self, max_bytes: usize) -> Self {
+        self.batch.max_bytes = max_bytes;
+        self
+    }
+
+    // ship whatever has been batched up once the oldest event has waited this long
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.batch.flush_interval = interval;
+        self
+    }
+
+    // let the worker grow and shrink max_batch_events and flush_interval with the load, within
+    // `bounds`, see AdaptiveBatching. the batch limits set on the builder are where it starts.
+    pub fn adaptive_batching(mut self, bounds: AdaptiveBatching) -> Self {
+        self.adaptive_batching = Some(bounds);
+        self
+    }
+
+    // how failed batches are retried, see RetryPolicy::none() to turn retries off
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    // fields with these names are moved out of the event body and into HEC's `fields`, which
+    // splunk indexes at ingest time instead of extracting at search time
+    pub fn indexed_fields<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: Into<String>,
+    {
+        self.indexed_fields
+            .extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    // a field every exported span and event gets, like the service's name, version or region.
+    // one the span or event sets itself wins over it.
+    pub fn global_field(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.global_fields.insert(name.into().into(), value.into());
+        self
+    }
+
+    // the same as global_field, for several at once
+    pub fn global_fields<I, N, V>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = (N, V)>,
+        N: Into<String>,
+        V: Into<serde_json::Value>,
+    {
+        for (name, value) in fields {
+            self.global_fields.insert(name.into().into(), value.into());
+        }
+        self
+    }
+
+    // export the field recorded as `from` as `to` instead, e.g. rename_field("msg", "message").
+    // indexed_fields goes by the new name.
+    pub fn rename_field(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.renames.rename(from.into(), to.into());
+        self
+    }
+
+    // rename fields to what a splunk CIM data model calls them, e.g. `http.status_code` to `status`
+    // for CimModel::Web, along with the span's elapsed time to `duration`. where two models
+    // disagree on a name the last one wins, and rename_field always wins over both.
+    pub fn cim_model(mut self, model: CimModel) -> Self {
+        for (from, to) in model.renames() {
+            self.renames.preset(from.to_string(), to.to_string());
+        }
+        self.cim_duration = true;
+        self
+    }
+
+    // run every event through `processor` before it's shipped, after any added before it. a
+    // closure taking &mut EventRecord and returning a ControlFlow works too.
+    pub fn processor(mut self, processor: impl Processor) -> Self {
+        self.processors.push(Arc::new(processor));
+        self
+    }
+
+    // put every exported field name that isn't renamed through a naming convention
+    pub fn key_case(mut self, case: KeyCase) -> Self {
+        self.renames.set_case(case);
+        self
+    }
+
+    // how precise the HEC `time` field is, milliseconds unless told otherwise
+    pub fn timestamp_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    // where spans and events get their timestamps and durations from, the real time unless told
+    // otherwise. see ManualClock for tests that need to know exactly how long a span was open.
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // what a span's elapsed time is called and what it's measured in, see ElapsedTime
+    pub fn elapsed_time(mut self, elapsed_time: ElapsedTime) -> Self {
+        self.elapsed_time = elapsed_time;
+        self
+    }
+
+    // record the span or event's level as `level`, on by default
+    pub fn with_level(mut self, enabled: bool) -> Self {
+        self.metadata_fields.level = enabled;
+        self
+    }
+
+    // record the span or event's target as `target`, on by default
+    pub fn with_target(mut self, enabled: bool) -> Self {
+        self.metadata_fields.target = enabled;
+        self
+    }
+
+    // record the span or event's name as `name`, on by default
+    pub fn with_span_name(mut self, enabled: bool) -> Self {
+        self.metadata_fields.name = enabled;
+        self
+    }
+
+    // record the module the span or event came from as `module_path`, off by default
+    pub fn with_module_path(mut self, enabled: bool) -> Self {
+        self.metadata_fields.module_path = enabled;
+        self
+    }
+
+    // record the source file the span or event came from as `file`, off by default
+    pub fn with_file(mut self, enabled: bool) -> Self {
+        self.metadata_fields.file = enabled;
+        self
+    }
+
+    // record the source line the span or event came from as `line`, off by default
+    pub fn with_line_number(mut self, enabled: bool) -> Self {
+        self.metadata_fields.line = enabled;
+        self
+    }
+
+    // send the machine's hostname as the HEC `host`, unless host() says otherwise. on by default.
+    pub fn with_hostname(mut self, enabled: bool) -> Self {
+        self.process_fields.hostname = enabled;
+        self
+    }
+
+    // record the process id as `pid`, on by default
+    pub fn with_pid(mut self, enabled: bool) -> Self {
+        self.process_fields.pid = enabled;
+        self
+    }
+
+    // record the name of the running executable as `executable`, on by default
+    pub fn with_executable(mut self, enabled: bool) -> Self {
+        self.process_fields.executable = enabled;
+        self
+    }
+
+    // look up which cloud instance we're running on when the layer is built, and record it on
+    // every event as `cloud.provider`, `cloud.region` and `instance.id`. off by default, since it
+    // can hold up build() for CloudMetadata::timeout per provider when we're not on one.
+    #[cfg(feature = "cloud-metadata")]
+    pub fn cloud_metadata(mut self, config: CloudMetadata) -> Self {
+        self.cloud_metadata = Some(config);
+        self
+    }
+
+    // record the id of the thread the span closed or event happened on as `thread.id`, off by
+    // default
+    pub fn with_thread_ids(mut self, enabled: bool) -> Self {
+        self.process_fields.thread_id = enabled;
+        self
+    }
+
+    // record that thread's name as `thread.name`, for threads that have one. off by default.
+    pub fn with_thread_names(mut self, enabled: bool) -> Self {
+        self.process_fields.thread_name = enabled;
+        self
+    }
+
+    // whether events inside a span are merged into the span's fields, kept as a list on it, or
+    // exported on their own
+    pub fn span_event_mode(mut self, mode: SpanEventMode) -> Self {
+        self.span_event_mode = mode;
+        self
+    }
+
+    // whether an event's message is sent as a field, as the HEC event text with everything else
+    // indexed alongside it, or both. a field until told otherwise.
+    pub fn message_field(mut self, mode: MessageField) -> Self {
+        self.message_field = mode;
+        self
+    }
+
+    // how much of its parents' fields a span is exported with, everything up to the root unless
+    // told otherwise
+    pub fn field_inheritance(mut self, inheritance: FieldInheritance) -> Self {
+        self.field_inheritance = inheritance;
+        self
+    }
+
+    // what to do when a span sets a field its parents already have, or an event sets one its span
+    // already has. the closest one wins unless told otherwise.
+    pub fn field_collision(mut self, collision: FieldCollision) -> Self {
+        self.field_collision = collision;
+        self
+    }
+
+    // record which level of the call tree each field came from. this adds a `span` entry with the
+    // span's name and its own fields, the same for its `parent_span`, and a `spans` array of
+    // every span from the root down to this one. off by default.
+    pub fn with_span_hierarchy(mut self, enabled: bool) -> Self {
+        self.span_hierarchy = enabled;
+        self
+    }
+
+    // whether every span is exported with what it inherits, as one tree per root span, or with
+    // references to its parent instead, see SpanExportMode
+    pub fn span_export_mode(mut self, mode: SpanExportMode) -> Self {
+        self.span_export_mode = mode;
+        self
+    }
+
+    // have spans that stay open for a long time, like a connection handler or a daemon's main
+    // loop, send a checkpoint with what they've gathered so far as they're exited, at most once
+    // every `interval`. a span that's never exited isn't checkpointed. the span is still exported
+    // as usual when it closes, its `events` list (see SpanEventMode::List) only has what came
+    // after the last checkpoint.
+    pub fn checkpoint_interval(mut self, interval: Duration) -> Self {
+        self.checkpoint_interval = Some(interval);
+        self
+    }
+
+    // cap how many events, and how many bytes of them, a span keeps in its `events` list with
+    // SpanEventMode::List, see SpanEventLimit. there's no cap unless this is set.
+    pub fn span_event_limit(mut self, limit: SpanEventLimit) -> Self {
+        self.span_event_limit = Some(limit);
+        self
+    }
+
+    // send every span's duration, a count and its numeric fields as HEC metrics too, see
+    // SpanMetrics
+    pub fn span_metrics(mut self, metrics: SpanMetrics) -> Self {
+        self.span_metrics = Some(metrics);
+        self
+    }
+
+    // send a summary of the spans' durations every so often instead of (or as well as) each one,
+    // see SpanAggregation
+    pub fn aggregate_spans(mut self, aggregation: SpanAggregation) -> Self {
+        self.aggregation = Some(aggregation);
+        self
+    }
+
+    // cap how many spans and events are exported, overall and from each target, see
+    // RateLimitConfig. a per_second that's NaN or negative is taken as zero, so only the burst
+    // ever gets through.
+    pub fn rate_limit(mut self, mut config: RateLimitConfig) -> Self {
+        for limit in [&mut config.global, &mut config.per_target]
+            .into_iter()
+            .flatten()
+        {
+            limit.per_second = limit.per_second.max(0.0);
+        }
+        self.rate_limit = Some(config);
+        self
+    }
+
+    // the most a single event can come to once it's serialized, HEC's own limit is the input's
+    // max_content_length. there's no limit unless one is set.
+    pub fn max_event_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_event_bytes = Some(max_bytes);
+        self
+    }
+
+    // what's done with events over max_event_bytes, they're truncated unless told otherwise
+    pub fn oversized_events(mut self, policy: OversizedEvent) -> Self {
+        self.oversized_events = policy;
+        self
+    }
+
+    // cut string values (Debug formatted ones included) over `max_bytes` short, with an ellipsis
+    // after what's left and the full length as `<field>.original_length`. off by default.
+    pub fn max_field_length(mut self, max_bytes: usize) -> Self {
+        self.max_lengths.all = Some(max_bytes);
+        self
+    }
+
+    // the same as max_field_length, for just the field recorded as `field`. it wins over the
+    // limit for every field.
+    pub fn max_field_length_for(mut self, field: impl Into<String>, max_bytes: usize) -> Self {
+        self.max_lengths.fields.insert(field.into(), max_bytes);
+        self
+    }
+
+    // how byte slice fields are written out, base64 unless told otherwise
+    pub fn byte_encoding(mut self, encoding: ByteEncoding) -> Self {
+        self.byte_encoding = encoding;
+        self
+    }
+
+    // turn Debug and Display formatted values (`?count`, `%status`) that are really integers,
+    // floats or bools into json numbers and bools, so splunk can do math on them. off by default,
+    // a string that happens to look like a number might be better left as one. the message is
+    // never parsed this way unless asked for with parse_debug_field.
+    pub fn with_debug_value_parsing(mut self, enabled: bool) -> Self {
+        self.debug_parsing.all = enabled;
+        self
+    }
+
+    // the same as with_debug_value_parsing, for just the field recorded as `field`
+    pub fn parse_debug_field(mut self, field: impl Into<String>) -> Self {
+        self.debug_parsing.fields.insert(field.into());
+        self
+    }
+
+    // keep the Debug output of error fields as `<field>.debug`, alongside the message and source
+    // chain they always get. off by default, it's often just the message again.
+    pub fn with_error_debug(mut self, enabled: bool) -> Self {
+        self.error_debug = enabled;
+        self
+    }
+
+    // replace the value of any field with one of these names (ignoring case) with the redaction
+    // mask, wherever it shows up
+    pub fn redact_fields<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: AsRef<str>,
+    {
+        for name in names {
+            self.redactor.deny_name(name.as_ref());
+        }
+        self
+    }
+
+    // replace anything in a string value that matches `pattern` with the redaction mask
+    #[cfg(feature = "regex")]
+    pub fn redact_pattern(mut self, pattern: regex::Regex) -> Self {
+        self.redactor.deny_pattern(pattern);
+        self
+    }
+
+    // run every field through `redact`, which gets the field's name and can change its value
+    // however it likes. runs after the name and pattern redaction.
+    pub fn redact_with<F>(mut self, redact: F) -> Self
+    where
+        F: Fn(&str, &mut serde_json::Value) + Send + Sync + 'static,
+    {
+        self.redactor.add_callback(Arc::new(redact));
+        self
+    }
+
+    // what redacted values are replaced with, DEFAULT_REDACTION_MASK unless told otherwise
+    pub fn redaction_mask(mut self, mask: impl Into<String>) -> Self {
+        self.redactor.set_mask(mask.into());
+        self
+    }
+
+    // only export spans that had an ERROR event in them or in one of their children. spans that
+    // get dropped are counted, see WorkerGuard::suppressed_spans.
+    pub fn tail_sample_errors(mut self) -> Self {
+        self.tail_sampler = TailSampler::Errors;
+        self
+    }
+
+    // only export spans that `keep` returns true for when they close
+    pub fn tail_sample_with<F>(mut self, keep: F) -> Self
+    where
+        F: Fn(&TailSample<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.tail_sampler = TailSampler::Predicate(Arc::new(keep));
+        self
+    }
+
+    // export only this fraction of traces, e.g. 0.1 for 10%. the decision is made when a root
+    // span is created and every span under it follows along, so a trace is never half exported.
+    // events outside of any span aren't affected. a NaN or infinite ratio exports everything.
+    pub fn head_sample_ratio(mut self, ratio: f64) -> Self {
+        self.head_sample_ratio = sample_ratio(ratio);
+        self
+    }
+
+    // only export spans and events at or above this level. this only affects what gets shipped to
+    // splunk, the rest of the subscriber still sees everything.
+    pub fn max_level(mut self, level: impl Into<LevelFilter>) -> Self {
+        self.filter.set_max_level(level.into());
+        self
+    }
+
+    // only export spans and events whose target falls under one of these, e.g. `my_app` covers
+    // `my_app` and `my_app::db`
+    pub fn allow_targets<I, T>(mut self, targets: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        for target in targets {
+            self.filter.allow(target.into());
+        }
+        self
+    }
+
+    // never export spans and events whose target falls under one of these, even if they're allowed
+    pub fn deny_targets<I, T>(mut self, targets: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        for target in targets {
+            self.filter.deny(target.into());
+        }
+        self
+    }
+
+    // what to do when something goes wrong inside the layer, e.g. a batch that couldn't be
+    // delivered. errors are printed to stderr unless told otherwise.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    // called on the worker thread for every batch HEC took, e.g. to feed a health check or your
+    // own metrics. only for the main endpoint, not other destinations or tenant routes.
+    pub fn on_export_success<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&BatchSummary) + Send + Sync + 'static,
+    {
+        self.hooks.on_success = Some(Arc::new(callback));
+        self
+    }
+
+    // called on the worker thread for every batch that couldn't be sent, once any retries have
+    // run out, so export failures can go to your own alerting. the error is always an
+    // Error::Layer(LayerError::Export { .. }), with how many events and attempts there were. the
+    // error policy still gets it too, ErrorPolicy::Ignore stops that going to stderr.
+    pub fn on_export_error<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Error) + Send + Sync + 'static,
+    {
+        self.hooks.on_error = Some(Arc::new(callback));
+        self
+    }
+
+    // turn on indexer acknowledgment, for HEC tokens with useAck enabled. the default transport is
+    // set up with the config's channel, a custom transport has to send the
+    // X-Splunk-Request-Channel header and implement Transport::query_acks itself.
+    pub fn indexer_ack(mut self, config: AckConfig) -> Self {
+        self.acks = Some(config);
+        self
+    }
+
+    // keep batches that couldn't be delivered on disk instead of dropping them, and replay them
+    // once splunk is back. see SpoolConfig.
+    pub fn spool(mut self, config: SpoolConfig) -> Self {
+        self.spool = Some(config);
+        self
+    }
+
+    // stop sending to HEC once it's been failing for a while, and check on it every so often
+    // until it's back. what would have been sent is handled as if HEC couldn't be reached in the
+    // meantime, see CircuitBreakerConfig.
+    pub fn circuit_breaker(mut self, config: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = Some(config);
+        self
+    }
+
+    // where batches go once they've failed for good, be it retries running out, HEC rejecting
+    // them outright or never acknowledging them. they're still reported to the error policy too.
+    pub fn dead_letter_sink(mut self, sink: DeadLetterSink) -> Self {
+        self.dead_letters = Some(sink);
+        self
+    }
+
+    // where events go instead when splunk can't be reached and there's no spool, e.g.
+    // FallbackSink::Stderr so an outage still leaves them in the container's logs. see
+    // FallbackSink.
+    pub fn fallback_sink(mut self, sink: FallbackSink) -> Self {
+        self.fallback = Some(sink);
+        self
+    }
+
+    // send spans and events somewhere else too, can be called more than once. see Destination.
+    pub fn destination(mut self, destination: Destination) -> Self {
+        self.destinations.push(destination);
+        self
+    }
+
+    // how the default transport sets up TLS, e.g. to trust an internal CA or present a client
+    // certificate. see TlsConfig.
+    pub fn tls(mut self, config: TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    // send the default transport's requests through this proxy, instead of whichever HTTPS_PROXY
+    // points at
+    pub fn proxy(mut self, config: ProxyConfig) -> Self {
+        self.proxy = Proxy::Custom(config);
+        self
+    }
+
+    // connect straight to splunk, even if HTTPS_PROXY is set
+    pub fn no_proxy(mut self) -> Self {
+        self.proxy = Proxy::Direct;
+        self
+    }
+
+    // send `name: value` with every request the default transport makes, e.g. the auth header a
+    // gateway in front of HEC wants. can be called more than once.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    // what the default transport says it is, instead of tracing-splunk-layer/<version>
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    // how long the default transport waits to connect to HEC, DEFAULT_CONNECT_TIMEOUT unless
+    // told otherwise. tcp connects within it too.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.connect = timeout;
+        self
+    }
+
+    // how long a whole request can take, connecting included, DEFAULT_REQUEST_TIMEOUT unless told
+    // otherwise. a request that runs out of time is retried like any other that failed, so a HEC
+    // endpoint that's stopped answering can't hold the worker up forever. for tcp, udp and the
+    // unix sockets it's how long writing a batch can block.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.request = timeout;
+        self
+    }
+
+    // how long the default transport keeps an unused connection around for the next batch,
+    // DEFAULT_POOL_IDLE_TIMEOUT unless told otherwise. connections are reused for as long as
+    // they're not idle any longer than this, so most batches don't need a new TLS handshake.
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.timeouts.pool_idle = timeout;
+        self
+    }
+
+    // have at most `max` requests to HEC going at once, between the default transport and every
+    // tenant's route. each worker only sends one batch at a time, but with a lot of routes
+    // catching up after an outage that's still a lot of connections. with ureq the workers wait
+    // their turn and drain what's backed up one request at a time, with reqwest the requests wait
+    // on the runtime. there's no limit unless one is set.
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.in_flight = Some(InFlightLimit::new(max));
+        self
+    }
+
+    // make sure HEC is healthy and takes the token before build returns, instead of finding out
+    // from the first batch. build blocks for as long as that takes, see Transport::probe.
+    pub fn startup_probe(mut self) -> Self {
+        self.startup_probe = true;
+        self
+    }
+
+    // run the worker as a task on this tokio runtime instead of giving it a thread of its own. with
+    // the `reqwest` feature the default transport sends on this runtime too, otherwise make sure
+    // the transport doesn't block (ureq does). the runtime has to be multi threaded for dropping
+    // the guard from inside it to work, a current thread runtime can't run the worker while it's
+    // waiting on it.
+    #[cfg(feature = "tokio")]
+    pub fn tokio_handle(mut self, handle: tokio::runtime::Handle) -> Self {
+        self.runtime = WorkerRuntime::Tokio(handle);
+        self
+    }
+
+    // the same as tokio_handle, with whichever runtime build() gets called from
+    #[cfg(feature = "tokio")]
+    pub fn tokio_runtime(mut self) -> Self {
+        self.ambient_tokio = true;
+        self
+    }
+
+    // the guard keeps the background worker alive, see WorkerGuard
+    pub fn build(mut self) -> Result<(SplunkHecLayer, WorkerGuard), Error> {
+        let runtime = self.runtime()?;
+        // before the transport, which needs the host for the raw endpoint
+        self.process_fields
+            .apply(&mut self.metadata, &mut self.global_fields);
+        #[cfg(feature = "cloud-metadata")]
+        if let Some(cloud) = &self.cloud_metadata {
+            cloud.apply(&mut self.global_fields);
+        }
+        let headers = self.user_agent.iter().map(|ua| ("User-Agent", ua.as_str()));
+        let headers = headers.chain(self.headers.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        for (name, value) in headers {
+            if !hec::is_valid_header(name, value) {
+                return Err(BuildError::InvalidHeader(name.to_owned()).into());
+            }
+        }
+        let transport = match (self.transport.take(), self.socket.take()) {
+            (Some(transport), _) => transport,
+            (None, Some(socket)) => socket.transport(self.timeouts),
+            (None, None) => self.default_transport(&runtime)?,
+        };
+        if self.startup_probe {
+            block_on(transport.probe()).map_err(BuildError::Probe)?;
+        }
+        let route_transports = std::mem::take(&mut self.tenant_routes)
+            .into_iter()
+            .map(|(key, route)| Ok((key, self.route_transport(&runtime, &route)?, route.index)))
+            .collect::<Result<Vec<_>, BuildError>>()?;
+
+        let spool = match self.spool {
+            Some(config) => {
+                let dir = config.dir.clone();
+                Some(Spool::open(config).map_err(|e| BuildError::Spool {
+                    dir,
+                    kind: e.kind(),
+                })?)
+            }
+            None => None,
+        };
+
+        let aggregator = self.aggregation.map(|aggregation| {
+            Arc::new(Aggregator::new(
+                aggregation,
+                self.elapsed_time.clone(),
+                self.metadata.clone(),
+                self.global_fields.clone(),
+                self.timestamp_precision,
+            ))
+        });
+        let rate_limiter = self.rate_limit.map(|config| {
+            Arc::new(RateLimiter::new(
+                config,
+                self.metadata.clone(),
+                self.global_fields.clone(),
+                self.timestamp_precision,
+            ))
+        });
+
+        let size_limit = self.max_event_bytes.map(|max_bytes| SizeLimit {
+            max_bytes,
+            policy: self.oversized_events,
+        });
+        let counters = Arc::new(Counters::default());
+        let config = WorkerConfig {
+            runtime,
+            capacity: self.channel_capacity,
+            queue_full_policy: self.queue_full_policy,
+            load_shedding: self
+                .load_shedding
+                .map(|shedding| shedding.for_capacity(self.channel_capacity)),
+            batch: self.batch,
+            adaptive: self.adaptive_batching,
+            retry: self.retry,
+            acks: self.acks,
+            spool,
+            dead_letters: self.dead_letters,
+            fallback: self.fallback,
+            formatter: self.raw,
+            aggregator: aggregator.clone(),
+            rate_limiter: rate_limiter.clone(),
+            size_limit,
+            circuit_breaker: self.circuit_breaker,
+            hooks: self.hooks,
+        };
+        let destinations_config = config.for_destination();
+        let (worker, mut guard) = WorkerHandle::spawn(
+            transport,
+            config,
+            counters.clone(),
+            self.error_policy.clone(),
+        )?;
+        let destinations = self
+            .destinations
+            .into_iter()
+            .map(|destination| {
+                destination.attach(|transport| {
+                    let (worker, secondary) = WorkerHandle::spawn(
+                        transport,
+                        destinations_config.for_destination(),
+                        Arc::new(Counters::default()),
+                        self.error_policy.clone(),
+                    )?;
+                    guard.add_destination(secondary);
+                    Ok(worker)
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let tenants = match self.tenant_router {
+            Some(router) => {
+                let mut tenants = TenantRoutes::new(router);
+                for (key, transport, index) in route_transports {
+                    let (worker, route) = WorkerHandle::spawn(
+                        transport,
+                        destinations_config.for_destination(),
+                        Arc::new(Counters::default()),
+                        self.error_policy.clone(),
+                    )?;
+                    guard.add_route(key.clone(), route);
+                    tenants.add(key, worker, index);
+                }
+                Some(Arc::new(tenants))
+            }
+            None => None,
+        };
+        if self.cim_duration {
+            self.renames
+                .preset(self.elapsed_time.field.clone(), "duration".to_string());
+        }
+        let exporter = Exporter {
+            worker,
+            destinations,
+            tenants,
+            metadata: self.metadata,
+            level_routes: self.level_routes,
+            indexed_fields: self.indexed_fields,
+            global_fields: self.global_fields,
+            process_fields: self.process_fields,
+            timestamp_precision: self.timestamp_precision,
+            clock: self.clock,
+            redactor: self.redactor,
+            renames: self.renames,
+            message_field: self.message_field,
+            processors: self.processors,
+        };
+        let layer = SplunkHecLayer {
+            exporter,
+            elapsed_time: self.elapsed_time,
+            metadata_fields: self.metadata_fields,
+            span_event_mode: self.span_event_mode,
+            field_inheritance: self.field_inheritance,
+            field_collision: self.field_collision,
+            span_hierarchy: self.span_hierarchy,
+            span_export_mode: self.span_export_mode,
+            checkpoint_interval: self.checkpoint_interval,
+            span_event_limit: self.span_event_limit,
+            record_options: RecordOptions {
+                error_debug: self.error_debug,
+                max_lengths: Arc::new(self.max_lengths),
+                byte_encoding: self.byte_encoding,
+                debug_parsing: Arc::new(self.debug_parsing),
+            },
+            span_metrics: self.span_metrics,
+            aggregator,
+            rate_limiter,
+            tail_sampler: self.tail_sampler,
+            head_sample_ratio: Arc::new(HeadSampleRatio::new(self.head_sample_ratio)),
+            filter: ExportFilter::new(self.filter),
+            errors: self.error_policy,
+            counters,
+            #[cfg(feature = "opentelemetry")]
+            otel: Default::default(),
+        };
+        Ok((layer, guard))
+    }
+
+    #[cfg(feature = "tokio")]
+    fn runtime(&self) -> Result<WorkerRuntime, BuildError> {
+        if !self.ambient_tokio {
+            return Ok(self.runtime.clone());
+        }
+        tokio::runtime::Handle::try_current()
+            .map(WorkerRuntime::Tokio)
+            .map_err(|_| BuildError::NoTokioRuntime)
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn runtime(&self) -> Result<WorkerRuntime, BuildError> {
+        Ok(self.runtime.clone())
+    }
+
+    // with the worker on a tokio runtime there's no point blocking it on ureq if reqwest is around
+    fn default_transport(&self, runtime: &WorkerRuntime) -> Result<Box<dyn Transport>, BuildError> {
+        #[cfg(feature = "reqwest")]
+        if let WorkerRuntime::Tokio(handle) = runtime {
+            let (endpoint, token) = self.credentials()?;
+            let transport = self.reqwest_transport(endpoint, token, handle)?;
+            let transport = match &self.raw {
+                Some(_) => transport.raw(&self.metadata),
+                None => transport,
+            };
+            return Ok(match &self.acks {
+                Some(acks) => Box::new(transport.with_channel(&acks.channel)),
+                None => Box::new(transport),
+            });
+        }
+        let _ = runtime;
+
+        #[cfg(feature = "ureq")]
+        {
+            let (endpoint, token) = self.credentials()?;
+            let transport = self.ureq_transport(endpoint, token)?;
+            let transport = match &self.raw {
+                Some(_) => transport.raw(&self.metadata),
+                None => transport,
+            };
+            Ok(match &self.acks {
+                Some(acks) => Box::new(transport.with_channel(&acks.channel)),
+                None => Box::new(transport),
+            })
+        }
+        #[cfg(not(feature = "ureq"))]
+        Err(BuildError::MissingTransport)
+    }
+
+    // the same as default_transport for a tenant's route, minus acks and the raw endpoint
+    fn route_transport(
+        &self,
+        runtime: &WorkerRuntime,
+        route: &Route,
+    ) -> Result<Box<dyn Transport>, BuildError> {
+        #[cfg(feature = "reqwest")]
+        if let WorkerRuntime::Tokio(handle) = runtime {
+            let transport = self.reqwest_transport(&route.endpoint, &route.token, handle)?;
+            return Ok(Box::new(transport));
+        }
+        let _ = runtime;
+
+        #[cfg(feature = "ureq")]
+        {
+            Ok(Box::new(
+                self.ureq_transport(&route.endpoint, &route.token)?,
+            ))
+        }
+        #[cfg(not(feature = "ureq"))]
+        {
+            let _ = route;
+            Err(BuildError::MissingTransport)
+        }
+    }
+
+    // an http transport with everything the builder was told about the connection
+    #[cfg(feature = "ureq")]
+    fn ureq_transport(
+        &self,
+        endpoint: &str,
+        token: &str,
+    ) -> Result<crate::transport::UreqTransport, BuildError> {
+        let transport = crate::transport::UreqTransport::configured(
+            endpoint,
+            token,
+            self.tls.as_ref(),
+            &self.proxy,
+            self.timeouts,
+        )?;
+        let transport = match &self.user_agent {
+            Some(user_agent) => transport.with_user_agent(user_agent),
+            None => transport,
+        };
+        let transport = match &self.in_flight {
+            Some(limit) => transport.with_limit(limit.clone()),
+            None => transport,
+        };
+        Ok(self
+            .headers
+            .iter()
+            .fold(transport, |transport, (name, value)| {
+                transport.with_header(name, value)
+            }))
+    }
+
+    #[cfg(feature = "reqwest")]
+    fn reqwest_transport(
+        &self,
+        endpoint: &str,
+        token: &str,
+        handle: &tokio::runtime::Handle,
+    ) -> Result<crate::transport::ReqwestTransport, BuildError> {
+        let client =
+            crate::transport::reqwest_client(self.tls.as_ref(), &self.proxy, self.timeouts)?;
+        let transport = crate::transport::ReqwestTransport::with_runtime(
+            client,
+            endpoint,
+            token,
+            handle.clone(),
+        );
+        let transport = match &self.user_agent {
+            Some(user_agent) => transport.with_user_agent(user_agent),
+            None => transport,
+        };
+        let transport = match &self.in_flight {
+            Some(limit) => transport.with_limit(limit.clone()),
+            None => transport,
+        };
+        Ok(self
+            .headers
+            .iter()
+            .fold(transport, |transport, (name, value)| {
+                transport.with_header(name, value)
+            }))
+    }
+
+    #[cfg(any(feature = "ureq", feature = "reqwest"))]
+    fn credentials(&self) -> Result<(&str, &str), BuildError> {
+        let endpoint = self.endpoint.as_ref().ok_or(BuildError::MissingEndpoint)?;
+        let token = self.token.as_ref().ok_or(BuildError::MissingToken)?;
+        Ok((endpoint, token))
+    }
+}
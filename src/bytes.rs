@@ -0,0 +1,67 @@
+use std::fmt::{self, Write};
+
+// how byte slice fields (e.g. `info!(payload = &body[..])`) are turned into strings, since json has
+// no way to hold raw bytes and a Debug dump of a [u8] array is no use to anyone reading it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ByteEncoding {
+    // standard base64 with padding, what most tools expect
+    #[default]
+    Base64,
+    // lowercase hex, twice the size but easy to eyeball
+    Hex,
+}
+
+// bytes that display in the given encoding. the layer records byte slices this way already, this
+// is for handing bytes that aren't a slice (or that should always be one encoding) to `%`, e.g.
+// `info!(digest = %Encoded::hex(&digest))`
+#[derive(Clone, Copy, Debug)]
+pub struct Encoded<'a> {
+    pub bytes: &'a [u8],
+    pub encoding: ByteEncoding,
+}
+
+impl<'a> Encoded<'a> {
+    pub fn new(bytes: &'a [u8], encoding: ByteEncoding) -> Self {
+        Encoded { bytes, encoding }
+    }
+
+    pub fn base64(bytes: &'a [u8]) -> Self {
+        Encoded::new(bytes, ByteEncoding::Base64)
+    }
+
+    pub fn hex(bytes: &'a [u8]) -> Self {
+        Encoded::new(bytes, ByteEncoding::Hex)
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl fmt::Display for Encoded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.encoding {
+            ByteEncoding::Hex => {
+                for byte in self.bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+            }
+            ByteEncoding::Base64 => {
+                for chunk in self.bytes.chunks(3) {
+                    let n = chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+                    // a chunk of 1 byte is 2 characters, 2 bytes are 3, and 3 are all 4
+                    for i in 0..4 {
+                        if i <= chunk.len() {
+                            let index = (n >> (18 - 6 * i)) & 0x3f;
+                            f.write_char(char::from(BASE64[index as usize]))?;
+                        } else {
+                            f.write_str("=")?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
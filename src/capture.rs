@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::batch::Batch;
+use crate::hec::HecResponse;
+use crate::transport::{Transport, TransportFuture};
+
+// a transport that keeps everything it's sent in memory, for testing your own instrumentation
+// without a splunk server:
+//
+//   let sink = TestSink::new();
+//   let (layer, guard) = SplunkHecLayer::builder().transport(sink.clone()).build()?;
+//   ...
+//   guard.flush(Duration::from_secs(1))?;
+//   sink.find_span("outer").unwrap().assert_field("answer", 42);
+//
+// what it keeps is exactly what would have gone to HEC, envelope and all. records only show up
+// once the worker has sent them, so flush the guard before looking.
+#[derive(Clone, Debug, Default)]
+pub struct TestSink {
+    records: Arc<Mutex<Vec<Captured>>>,
+}
+
+impl TestSink {
+    pub fn new() -> Self {
+        TestSink::default()
+    }
+
+    // everything sent so far, oldest first
+    pub fn records(&self) -> Vec<Captured> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    // the first span called `name`, going by its `name` field
+    pub fn find_span(&self, name: &str) -> Option<Captured> {
+        self.find(|record| record.field("name").and_then(Value::as_str) == Some(name))
+    }
+
+    // the first event with this message
+    pub fn find_event(&self, message: &str) -> Option<Captured> {
+        self.find(|record| record.field("message").and_then(Value::as_str) == Some(message))
+    }
+
+    pub fn find(&self, matches: impl Fn(&Captured) -> bool) -> Option<Captured> {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|record| matches(record))
+            .cloned()
+    }
+
+    // panics unless some record has `name` set to `expected`
+    #[track_caller]
+    pub fn assert_field(&self, name: &str, expected: impl Into<Value>) {
+        let expected = expected.into();
+        let records = self.records();
+        assert!(
+            records
+                .iter()
+                .any(|record| record.field(name) == Some(&expected)),
+            "no record has {} = {}, got {:?}",
+            name,
+            expected,
+            records.iter().map(Captured::event).collect::<Vec<_>>()
+        );
+    }
+
+    pub fn clear(&self) {
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+impl Transport for TestSink {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        // a line that isn't json (the raw endpoint's, with a formatter) is kept as a string
+        let captured = batch.as_str().lines().map(|line| {
+            Captured(serde_json::from_str(line).unwrap_or_else(|_| Value::String(line.into())))
+        });
+        self.records
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(captured);
+        Box::pin(std::future::ready(Ok(HecResponse::success())))
+    }
+}
+
+// one span or event as TestSink got it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Captured(pub Value);
+
+impl Captured {
+    // the HEC envelope, with time, host, index and so on alongside the event
+    pub fn envelope(&self) -> &Value {
+        &self.0
+    }
+
+    // the span or event itself
+    pub fn event(&self) -> &Value {
+        self.0.get("event").unwrap_or(&self.0)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.event().get(name)
+    }
+
+    // the indexed fields, see SplunkHecLayerBuilder::indexed_fields
+    pub fn indexed_field(&self, name: &str) -> Option<&Value> {
+        self.0.get("fields")?.get(name)
+    }
+
+    // panics unless `name` is set to `expected`
+    #[track_caller]
+    pub fn assert_field(&self, name: &str, expected: impl Into<Value>) -> &Self {
+        let expected = expected.into();
+        assert_eq!(
+            self.field(name),
+            Some(&expected),
+            "{} in {}",
+            name,
+            self.event()
+        );
+        self
+    }
+}
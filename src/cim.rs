@@ -0,0 +1,65 @@
+// the splunk Common Information Model data models there are presets for, see
+// SplunkHecLayerBuilder::cim_model. each one renames the usual tracing and opentelemetry field
+// names to the ones the data model (and so every CIM dashboard) expects
+// (https://docs.splunk.com/Documentation/CIM/latest/User/Overview).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CimModel {
+    Web,
+    NetworkTraffic,
+}
+
+// what the old and new opentelemetry semantic conventions call things, and the CIM name for each
+const WEB: &[(&str, &str)] = &[
+    ("http.method", "http_method"),
+    ("http.request.method", "http_method"),
+    ("http.status_code", "status"),
+    ("http.response.status_code", "status"),
+    ("http.url", "url"),
+    ("url.full", "url"),
+    ("http.target", "uri_path"),
+    ("url.path", "uri_path"),
+    ("url.query", "uri_query"),
+    ("http.user_agent", "http_user_agent"),
+    ("user_agent.original", "http_user_agent"),
+    ("http.referer", "http_referrer"),
+    ("http.request.header.referer", "http_referrer"),
+    ("http.request_content_length", "bytes_in"),
+    ("http.request.body.size", "bytes_in"),
+    ("http.response_content_length", "bytes_out"),
+    ("http.response.body.size", "bytes_out"),
+    ("net.peer.ip", "src"),
+    ("client.address", "src"),
+    ("net.host.name", "dest"),
+    ("server.address", "dest"),
+    ("latency", "duration"),
+    ("latency_ms", "duration"),
+];
+
+const NETWORK_TRAFFIC: &[(&str, &str)] = &[
+    ("net.peer.ip", "src_ip"),
+    ("client.address", "src_ip"),
+    ("source.address", "src_ip"),
+    ("net.peer.port", "src_port"),
+    ("client.port", "src_port"),
+    ("source.port", "src_port"),
+    ("net.host.ip", "dest_ip"),
+    ("server.address", "dest_ip"),
+    ("destination.address", "dest_ip"),
+    ("net.host.port", "dest_port"),
+    ("server.port", "dest_port"),
+    ("destination.port", "dest_port"),
+    ("net.transport", "transport"),
+    ("network.transport", "transport"),
+    ("network.protocol.name", "protocol"),
+    ("latency", "duration"),
+    ("latency_ms", "duration"),
+];
+
+impl CimModel {
+    pub(crate) fn renames(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            CimModel::Web => WEB,
+            CimModel::NetworkTraffic => NETWORK_TRAFFIC,
+        }
+    }
+}
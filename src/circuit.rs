@@ -0,0 +1,127 @@
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_CIRCUIT_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+// stop sending to HEC once it's been failing for a while. after `failure_threshold` failed tries
+// in a row (counting retries, and only failures that are worth retrying, so a rejected batch
+// doesn't count) the circuit opens: batches go straight to the spool, the fallback sink or the
+// dead letter sink, whichever there is first, without being sent. every `probe_interval` the
+// worker checks whether HEC is back with Transport::probe, and if it is the next batch is sent
+// once, without retries, to find out for sure. that one going through closes the circuit again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub probe_interval: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+            probe_interval: DEFAULT_CIRCUIT_PROBE_INTERVAL,
+        }
+    }
+}
+
+// where the circuit breaker is at, see MetricsSnapshot::circuit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CircuitState {
+    // batches are sent as usual (also what it says without a circuit breaker)
+    #[default]
+    Closed,
+    // HEC is considered down and nothing is sent to it
+    Open,
+    // HEC looked like it was back, the next batch decides
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub(crate) fn to_u8(self) -> u8 {
+        match self {
+            CircuitState::Closed => 0,
+            CircuitState::Open => 1,
+            CircuitState::HalfOpen => 2,
+        }
+    }
+
+    pub(crate) fn from_u8(state: u8) -> Self {
+        match state {
+            1 => CircuitState::Open,
+            2 => CircuitState::HalfOpen,
+            _ => CircuitState::Closed,
+        }
+    }
+}
+
+// the worker's side of it
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: CircuitState,
+    failures: u32,
+    // when the circuit was last opened, or last failed a probe
+    opened_at: Instant,
+}
+
+impl CircuitBreaker {
+    pub(crate) fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            state: CircuitState::Closed,
+            failures: 0,
+            opened_at: Instant::now(),
+        }
+    }
+
+    pub(crate) fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.state == CircuitState::Open
+    }
+
+    // a try that went through
+    pub(crate) fn succeeded(&mut self) {
+        self.failures = 0;
+        self.state = CircuitState::Closed;
+    }
+
+    // a try that failed in a way worth retrying. the trial a half open circuit lets through is
+    // all it gets.
+    pub(crate) fn failed(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+        let opens = match self.state {
+            CircuitState::Closed => self.failures >= self.config.failure_threshold.max(1),
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if opens {
+            self.open();
+        }
+    }
+
+    // what a probe found
+    pub(crate) fn probed(&mut self, healthy: bool) {
+        if healthy {
+            self.state = CircuitState::HalfOpen;
+        } else {
+            self.open();
+        }
+    }
+
+    // how long until the worker should probe, only while the circuit is open
+    pub(crate) fn time_until_probe(&self) -> Option<Duration> {
+        self.is_open().then(|| {
+            self.config
+                .probe_interval
+                .saturating_sub(self.opened_at.elapsed())
+        })
+    }
+
+    fn open(&mut self) {
+        self.state = CircuitState::Open;
+        self.opened_at = Instant::now();
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+// where the layer gets the time from, for span timestamps and for how long spans were open. it's
+// SystemClock unless the builder is given another, ManualClock lets tests say exactly how much
+// time passes.
+pub trait Clock: Send + Sync + 'static {
+    // what span durations are measured with, the way Instant::now is
+    fn now(&self) -> Instant;
+    // what spans and events are timestamped with, the way SystemTime::now is
+    fn system_time(&self) -> SystemTime;
+}
+
+// the real time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+// a clock that only moves when it's told to. clones share the same time, so keep one to advance
+// after handing another to the builder:
+//
+//   let clock = ManualClock::new();
+//   let (layer, guard) = SplunkHecLayer::builder().clock(clock.clone()).build()?;
+//   let span = info_span!("request");
+//   clock.advance(Duration::from_millis(250));
+//   drop(span); // exported with elapsed_time = 250
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    started: Instant,
+    started_at: SystemTime,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    // stopped at whatever the time is now
+    pub fn new() -> Self {
+        ManualClock::starting_at(SystemTime::now())
+    }
+
+    // stopped at `time`, for timestamps that come out the same every run
+    pub fn starting_at(time: SystemTime) -> Self {
+        ManualClock {
+            started: Instant::now(),
+            started_at: time,
+            elapsed: Arc::default(),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+
+    // how far it's been advanced altogether
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.started + self.elapsed()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.started_at + self.elapsed()
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::EventHash;
+
+// the metadata services there's a lookup for, see CloudMetadata
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloudProvider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+impl CloudProvider {
+    fn name(self) -> &'static str {
+        match self {
+            CloudProvider::Aws => "aws",
+            CloudProvider::Gcp => "gcp",
+            CloudProvider::Azure => "azure",
+        }
+    }
+}
+
+// where the machine is running, looked up once when the layer is built and added to every event
+// as `cloud.provider`, `cloud.region` and `instance.id`. the providers are tried in order and the
+// first one to answer wins. when none of them do (we're not on a cloud vm, or the metadata
+// service is blocked) nothing is added, it's not an error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloudMetadata {
+    pub providers: Vec<CloudProvider>,
+    // for each request, these answer in milliseconds when they're there at all
+    pub timeout: Duration,
+    // where each metadata service is, only worth changing for tests
+    pub aws_endpoint: String,
+    pub gcp_endpoint: String,
+    pub azure_endpoint: String,
+}
+
+impl Default for CloudMetadata {
+    fn default() -> Self {
+        CloudMetadata {
+            providers: vec![CloudProvider::Aws, CloudProvider::Gcp, CloudProvider::Azure],
+            timeout: Duration::from_millis(500),
+            aws_endpoint: "http://169.254.169.254".to_string(),
+            gcp_endpoint: "http://metadata.google.internal".to_string(),
+            azure_endpoint: "http://169.254.169.254".to_string(),
+        }
+    }
+}
+
+// what we want out of any of them
+struct Instance {
+    provider: CloudProvider,
+    region: String,
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AwsIdentity {
+    region: String,
+    instance_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AzureCompute {
+    location: String,
+    vm_id: String,
+}
+
+impl CloudMetadata {
+    // look up where we are and add it to the global fields every event gets. anything already
+    // set on the builder is left alone.
+    pub(crate) fn apply(&self, global_fields: &mut EventHash) {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(self.timeout))
+            // metadata services are link local, a proxy can't reach them
+            .proxy(None)
+            .build()
+            .new_agent();
+        let Some(instance) = self
+            .providers
+            .iter()
+            .find_map(|&provider| self.instance(&agent, provider))
+        else {
+            return;
+        };
+
+        let fields = [
+            ("cloud.provider", instance.provider.name().to_string()),
+            ("cloud.region", instance.region),
+            ("instance.id", instance.id),
+        ];
+        for (name, value) in fields {
+            global_fields.entry(name.into()).or_insert(value.into());
+        }
+    }
+
+    fn instance(&self, agent: &ureq::Agent, provider: CloudProvider) -> Option<Instance> {
+        match provider {
+            CloudProvider::Aws => self.aws(agent),
+            CloudProvider::Gcp => self.gcp(agent),
+            CloudProvider::Azure => self.azure(agent),
+        }
+    }
+
+    fn aws(&self, agent: &ureq::Agent) -> Option<Instance> {
+        let base = self.aws_endpoint.trim_end_matches('/');
+        // IMDSv2 wants a session token first, v1 (where it's still turned on) doesn't
+        let token = agent
+            .put(format!("{}/latest/api/token", base))
+            .header("X-aws-ec2-metadata-token-ttl-seconds", "60")
+            .send_empty()
+            .ok()
+            .and_then(|mut r| r.body_mut().read_to_string().ok());
+        let mut request = agent.get(format!(
+            "{}/latest/dynamic/instance-identity/document",
+            base
+        ));
+        if let Some(token) = &token {
+            request = request.header("X-aws-ec2-metadata-token", token);
+        }
+        let body = request.call().ok()?.body_mut().read_to_string().ok()?;
+        let identity: AwsIdentity = serde_json::from_str(&body).ok()?;
+        Some(Instance {
+            provider: CloudProvider::Aws,
+            region: identity.region,
+            id: identity.instance_id,
+        })
+    }
+
+    fn gcp(&self, agent: &ureq::Agent) -> Option<Instance> {
+        let base = self.gcp_endpoint.trim_end_matches('/');
+        let get = |path: &str| {
+            agent
+                .get(format!("{}/computeMetadata/v1/instance/{}", base, path))
+                .header("Metadata-Flavor", "Google")
+                .call()
+                .ok()?
+                .body_mut()
+                .read_to_string()
+                .ok()
+        };
+        let id = get("id")?;
+        // projects/123456/zones/us-central1-a, and the region is the zone without its last part
+        let zone = get("zone")?;
+        let zone = zone.rsplit('/').next().unwrap_or(&zone);
+        let region = zone.rsplit_once('-').map_or(zone, |(region, _)| region);
+        Some(Instance {
+            provider: CloudProvider::Gcp,
+            region: region.to_string(),
+            id,
+        })
+    }
+
+    fn azure(&self, agent: &ureq::Agent) -> Option<Instance> {
+        let base = self.azure_endpoint.trim_end_matches('/');
+        let body = agent
+            .get(format!(
+                "{}/metadata/instance/compute?api-version=2021-02-01",
+                base
+            ))
+            .header("Metadata", "true")
+            .call()
+            .ok()?
+            .body_mut()
+            .read_to_string()
+            .ok()?;
+        let compute: AzureCompute = serde_json::from_str(&body).ok()?;
+        Some(Instance {
+            provider: CloudProvider::Azure,
+            region: compute.location,
+            id: compute.vm_id,
+        })
+    }
+}
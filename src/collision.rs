@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+
+use serde_json::Value;
+
+use crate::intern::intern_fmt;
+use crate::EventHash;
+
+// what happens when a span sets a field one of its parents already has, or an event merged into a
+// span (see SpanEventMode::Merge) sets one the span already has. a value that's the same on both
+// sides isn't a collision at all.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FieldCollision {
+    // whatever was set closest to where it's exported wins, the span over its parents and the
+    // latest event over everything before it
+    #[default]
+    Overwrite,
+    // whatever was set first wins, the root over its children and the span over its events
+    KeepFirst,
+    // the closer one keeps the field's name, the other one gets the name of the span it was set
+    // on in front of it, e.g. `request.user`
+    Namespace,
+    // all of them, in an array from the root down. values that are already arrays are added to.
+    Collect,
+}
+
+impl FieldCollision {
+    // `value` was set on `span`, further up the tree than whatever `fields` already has
+    pub(crate) fn inherit(
+        self,
+        fields: &mut EventHash,
+        span: &str,
+        name: Cow<'static, str>,
+        value: &Value,
+    ) {
+        let Some(existing) = fields.get_mut(&name) else {
+            fields.insert(name, value.clone());
+            return;
+        };
+        if existing == value {
+            return;
+        }
+        match self {
+            FieldCollision::Overwrite => {}
+            FieldCollision::KeepFirst => *existing = value.clone(),
+            FieldCollision::Namespace => {
+                fields
+                    .entry(intern_fmt(format_args!("{}.{}", span, name)))
+                    .or_insert_with(|| value.clone());
+            }
+            FieldCollision::Collect => match existing {
+                Value::Array(values) => values.insert(0, value.clone()),
+                other => *other = Value::Array(vec![value.clone(), other.take()]),
+            },
+        }
+    }
+
+    // `value` came from an event inside `span`, after whatever `fields` already has
+    pub(crate) fn record(
+        self,
+        fields: &mut EventHash,
+        span: &str,
+        name: Cow<'static, str>,
+        value: Value,
+    ) {
+        let Some(existing) = fields.get_mut(&name) else {
+            fields.insert(name, value);
+            return;
+        };
+        if *existing == value {
+            return;
+        }
+        match self {
+            FieldCollision::Overwrite => *existing = value,
+            FieldCollision::KeepFirst => {}
+            FieldCollision::Namespace => {
+                let previous = std::mem::replace(existing, value);
+                fields.insert(intern_fmt(format_args!("{}.{}", span, name)), previous);
+            }
+            FieldCollision::Collect => match existing {
+                Value::Array(values) => values.push(value),
+                other => *other = Value::Array(vec![other.take(), value]),
+            },
+        }
+    }
+}
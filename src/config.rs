@@ -0,0 +1,191 @@
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
+
+use crate::builder::{BuildError, SplunkHecLayerBuilder};
+use crate::error::Error;
+use crate::retry::RetryPolicy;
+
+// the layout of a config file, e.g.
+//
+//   [endpoint]
+//   url = "https://splunk.example.com:8088"
+//
+//   [auth]
+//   token_env = "SPLUNK_HEC_TOKEN"
+//
+//   [metadata]
+//   index = "app_logs"
+//
+//   [batching]
+//   max_events = 500
+//   flush_interval_ms = 2000
+//
+//   [retry]
+//   max_attempts = 3
+//
+//   [filter]
+//   max_level = "info"
+//   deny_targets = ["hyper"]
+//
+// every section is optional, and anything the file doesn't mention keeps its default
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    endpoint: EndpointSection,
+    auth: AuthSection,
+    metadata: MetadataSection,
+    batching: BatchingSection,
+    retry: RetrySection,
+    filter: FilterSection,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct EndpointSection {
+    url: Option<String>,
+}
+
+// the token can be in the file, but it's usually better kept out of it
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AuthSection {
+    token: Option<String>,
+    // the name of an environment variable holding the token
+    token_env: Option<String>,
+    // a file holding the token, e.g. a mounted kubernetes secret
+    token_file: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MetadataSection {
+    index: Option<String>,
+    source: Option<String>,
+    sourcetype: Option<String>,
+    host: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BatchingSection {
+    max_events: Option<usize>,
+    max_bytes: Option<usize>,
+    flush_interval_ms: Option<u64>,
+    channel_capacity: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RetrySection {
+    max_attempts: Option<u32>,
+    initial_backoff_ms: Option<u64>,
+    max_backoff_ms: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FilterSection {
+    max_level: Option<String>,
+    allow_targets: Vec<String>,
+    deny_targets: Vec<String>,
+}
+
+impl SplunkHecLayerBuilder {
+    // a builder set up from a TOML file, see ConfigFile above for what goes in it. keys the layer
+    // doesn't know about are an error rather than silently ignored, so a typo doesn't go unnoticed.
+    pub fn from_toml(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let invalid = |message: String| BuildError::Config {
+            path: path.to_owned(),
+            message,
+        };
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let file: ConfigFile = toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+        Ok(file.into_builder().map_err(invalid)?)
+    }
+}
+
+impl ConfigFile {
+    fn into_builder(self) -> Result<SplunkHecLayerBuilder, String> {
+        let mut builder = SplunkHecLayerBuilder::new();
+
+        if let Some(url) = self.endpoint.url {
+            builder = builder.endpoint(url);
+        }
+        if let Some(token) = self.auth.token()? {
+            builder = builder.token(token);
+        }
+
+        let metadata = self.metadata;
+        if let Some(index) = metadata.index {
+            builder = builder.index(index);
+        }
+        if let Some(source) = metadata.source {
+            builder = builder.source(source);
+        }
+        if let Some(sourcetype) = metadata.sourcetype {
+            builder = builder.sourcetype(sourcetype);
+        }
+        if let Some(host) = metadata.host {
+            builder = builder.host(host);
+        }
+
+        let batching = self.batching;
+        if let Some(max_events) = batching.max_events {
+            builder = builder.max_batch_events(max_events);
+        }
+        if let Some(max_bytes) = batching.max_bytes {
+            builder = builder.max_batch_bytes(max_bytes);
+        }
+        if let Some(ms) = batching.flush_interval_ms {
+            builder = builder.flush_interval(Duration::from_millis(ms));
+        }
+        if let Some(capacity) = batching.channel_capacity {
+            builder = builder.channel_capacity(capacity);
+        }
+
+        let retry = self.retry;
+        let defaults = RetryPolicy::default();
+        builder = builder.retry_policy(RetryPolicy {
+            max_attempts: retry.max_attempts.unwrap_or(defaults.max_attempts),
+            initial_backoff: retry
+                .initial_backoff_ms
+                .map_or(defaults.initial_backoff, Duration::from_millis),
+            max_backoff: retry
+                .max_backoff_ms
+                .map_or(defaults.max_backoff, Duration::from_millis),
+        });
+
+        let filter = self.filter;
+        if let Some(level) = filter.max_level {
+            let level: LevelFilter = level
+                .parse()
+                .map_err(|e| format!("filter.max_level `{}`: {}", level, e))?;
+            builder = builder.max_level(level);
+        }
+        Ok(builder
+            .allow_targets(filter.allow_targets)
+            .deny_targets(filter.deny_targets))
+    }
+}
+
+impl AuthSection {
+    fn token(self) -> Result<Option<String>, String> {
+        match (self.token, self.token_env, self.token_file) {
+            (None, None, None) => Ok(None),
+            (Some(token), None, None) => Ok(Some(token)),
+            (None, Some(name), None) => std::env::var(&name)
+                .map(Some)
+                .map_err(|e| format!("auth.token_env `{}`: {}", name, e)),
+            (None, None, Some(path)) => std::fs::read_to_string(&path)
+                .map(|token| Some(token.trim().to_owned()))
+                .map_err(|e| format!("auth.token_file `{}`: {}", path, e)),
+            _ => {
+                Err("only one of auth.token, auth.token_env and auth.token_file can be set".into())
+            }
+        }
+    }
+}
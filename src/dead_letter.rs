@@ -0,0 +1,65 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::error::LayerError;
+
+// a batch that's never going to make it to splunk, exactly as it would have been sent, along with
+// why. the payload is HEC's json event format so it can be re-ingested as is later.
+#[derive(Debug)]
+pub struct DeadLetter<'a> {
+    pub payload: &'a str,
+    pub reason: &'a LayerError,
+}
+
+// where dead letters go. without one they're only reported to the ErrorPolicy and then dropped.
+#[derive(Clone)]
+pub enum DeadLetterSink {
+    // append each one as a line of json, with `reason` and `payload` keys
+    File(PathBuf),
+    // print that same line of json to stderr
+    Stderr,
+    Callback(Arc<dyn Fn(&DeadLetter<'_>) + Send + Sync>),
+}
+
+impl DeadLetterSink {
+    pub fn callback<F>(callback: F) -> Self
+    where
+        F: Fn(&DeadLetter<'_>) + Send + Sync + 'static,
+    {
+        DeadLetterSink::Callback(Arc::new(callback))
+    }
+
+    pub(crate) fn send(&self, letter: &DeadLetter<'_>) -> io::Result<()> {
+        match self {
+            DeadLetterSink::File(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                writeln!(file, "{}", to_line(letter))
+            }
+            DeadLetterSink::Stderr => writeln!(io::stderr(), "{}", to_line(letter)),
+            DeadLetterSink::Callback(callback) => {
+                callback(letter);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for DeadLetterSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeadLetterSink::File(path) => f.debug_tuple("File").field(path).finish(),
+            DeadLetterSink::Stderr => write!(f, "Stderr"),
+            DeadLetterSink::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+fn to_line(letter: &DeadLetter<'_>) -> String {
+    serde_json::json!({
+        "reason": letter.reason.to_string(),
+        "payload": letter.payload,
+    })
+    .to_string()
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+
+use crate::builder::BuildError;
+use crate::record::EventRecord;
+use crate::transport::Transport;
+use crate::worker::WorkerHandle;
+
+type RecordFilter = dyn Fn(&EventRecord) -> bool + Send + Sync;
+
+// somewhere else for spans and events to go as well as the layer's own HEC input, e.g. a local
+// ndjson file with WriterTransport, or a second splunk while moving from one to the other. each one
+// gets a worker of its own with the same batching, retries, queue and circuit breaker settings
+// as the main one, so a slow destination can't hold the others up. acks, the spool, the fallback
+// and dead letter sinks and summaries (aggregation, rate limiting) are only for the main one.
+//
+// what's sent is the finished record, after redaction, renames and processors. metrics events
+// (see SpanMetrics) only go to the main destination.
+pub struct Destination {
+    transport: Box<dyn Transport>,
+    max_level: LevelFilter,
+    filter: Option<Arc<RecordFilter>>,
+}
+
+impl Destination {
+    pub fn new(transport: impl Transport) -> Self {
+        Destination {
+            transport: Box::new(transport),
+            max_level: LevelFilter::TRACE,
+            filter: None,
+        }
+    }
+
+    // only spans and events at least this severe, e.g. Level::ERROR for just the errors
+    pub fn max_level(mut self, level: impl Into<LevelFilter>) -> Self {
+        self.max_level = level.into();
+        self
+    }
+
+    // only records `filter` returns true for, checked after max_level
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&EventRecord) -> bool + Send + Sync + 'static,
+    {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    // start its worker with `spawn`
+    pub(crate) fn attach(
+        self,
+        spawn: impl FnOnce(Box<dyn Transport>) -> Result<WorkerHandle, BuildError>,
+    ) -> Result<DestinationHandle, BuildError> {
+        Ok(DestinationHandle {
+            worker: spawn(self.transport)?,
+            max_level: self.max_level,
+            filter: self.filter,
+        })
+    }
+}
+
+impl std::fmt::Debug for Destination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Destination")
+            .field("max_level", &self.max_level)
+            .field("filter", &self.filter.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+// the exporter's side of a destination once its worker is running
+#[derive(Clone)]
+pub(crate) struct DestinationHandle {
+    worker: WorkerHandle,
+    max_level: LevelFilter,
+    filter: Option<Arc<RecordFilter>>,
+}
+
+impl DestinationHandle {
+    pub(crate) fn send(&self, record: &EventRecord, level: &Level) {
+        if *level > self.max_level {
+            return;
+        }
+        if self.filter.as_ref().is_some_and(|filter| !filter(record)) {
+            return;
+        }
+        self.worker.send(record.clone(), level);
+    }
+}
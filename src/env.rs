@@ -0,0 +1,159 @@
+use std::env::{self, VarError};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+use tracing::level_filters::LevelFilter;
+
+use crate::ack::AckConfig;
+use crate::builder::{BuildError, SplunkHecLayerBuilder};
+use crate::error::Error;
+use crate::proxy::ProxyConfig;
+use crate::retry::RetryPolicy;
+use crate::tls::TlsConfig;
+
+const ENV_HEC_URL: &str = "SPLUNK_HEC_URL";
+const ENV_HEC_TOKEN: &str = "SPLUNK_HEC_TOKEN";
+const ENV_INDEX: &str = "SPLUNK_INDEX";
+const ENV_SOURCE: &str = "SPLUNK_SOURCE";
+const ENV_SOURCETYPE: &str = "SPLUNK_SOURCETYPE";
+const ENV_HOST: &str = "SPLUNK_HOST";
+// a level like `info`, see SplunkHecLayerBuilder::max_level
+const ENV_MAX_LEVEL: &str = "SPLUNK_HEC_MAX_LEVEL";
+const ENV_MAX_BATCH_EVENTS: &str = "SPLUNK_HEC_MAX_BATCH_EVENTS";
+const ENV_MAX_BATCH_BYTES: &str = "SPLUNK_HEC_MAX_BATCH_BYTES";
+const ENV_FLUSH_INTERVAL_MS: &str = "SPLUNK_HEC_FLUSH_INTERVAL_MS";
+const ENV_CHANNEL_CAPACITY: &str = "SPLUNK_HEC_CHANNEL_CAPACITY";
+const ENV_MAX_ATTEMPTS: &str = "SPLUNK_HEC_MAX_ATTEMPTS";
+// turns indexer acknowledgment on, on this channel
+const ENV_ACK_CHANNEL: &str = "SPLUNK_HEC_ACK_CHANNEL";
+// a PEM file of CA certificates to trust instead of the built in roots
+const ENV_CA_FILE: &str = "SPLUNK_HEC_CA_FILE";
+const ENV_INSECURE_SKIP_VERIFY: &str = "SPLUNK_HEC_INSECURE_SKIP_VERIFY";
+// a proxy to use instead of HTTPS_PROXY, which is still read when this isn't set
+const ENV_PROXY: &str = "SPLUNK_HEC_PROXY";
+
+impl SplunkHecLayerBuilder {
+    // a builder set up from the environment, for deployments that configure everything that way.
+    // SPLUNK_HEC_URL and SPLUNK_HEC_TOKEN have to be there, anything else that's missing (or
+    // empty) keeps its default, and the builder can still be changed afterwards:
+    //
+    //   SPLUNK_INDEX, SPLUNK_SOURCE, SPLUNK_SOURCETYPE, SPLUNK_HOST
+    //   SPLUNK_HEC_MAX_LEVEL              e.g. `info`
+    //   SPLUNK_HEC_MAX_BATCH_EVENTS, SPLUNK_HEC_MAX_BATCH_BYTES, SPLUNK_HEC_FLUSH_INTERVAL_MS
+    //   SPLUNK_HEC_CHANNEL_CAPACITY, SPLUNK_HEC_MAX_ATTEMPTS
+    //   SPLUNK_HEC_ACK_CHANNEL            turns on indexer acknowledgment
+    //   SPLUNK_HEC_CA_FILE                a PEM file of CA certificates to trust
+    //   SPLUNK_HEC_INSECURE_SKIP_VERIFY   `true` to not check certificates at all
+    //   SPLUNK_HEC_PROXY                  used instead of HTTPS_PROXY
+    pub fn from_env() -> Result<Self, Error> {
+        let mut builder = SplunkHecLayerBuilder::new()
+            .endpoint(required(ENV_HEC_URL)?)
+            .token(required(ENV_HEC_TOKEN)?);
+
+        if let Some(index) = var(ENV_INDEX)? {
+            builder = builder.index(index);
+        }
+        if let Some(source) = var(ENV_SOURCE)? {
+            builder = builder.source(source);
+        }
+        if let Some(sourcetype) = var(ENV_SOURCETYPE)? {
+            builder = builder.sourcetype(sourcetype);
+        }
+        if let Some(host) = var(ENV_HOST)? {
+            builder = builder.host(host);
+        }
+        if let Some(level) = parsed::<LevelFilter>(ENV_MAX_LEVEL)? {
+            builder = builder.max_level(level);
+        }
+        if let Some(max_events) = parsed(ENV_MAX_BATCH_EVENTS)? {
+            builder = builder.max_batch_events(max_events);
+        }
+        if let Some(max_bytes) = parsed(ENV_MAX_BATCH_BYTES)? {
+            builder = builder.max_batch_bytes(max_bytes);
+        }
+        if let Some(ms) = parsed(ENV_FLUSH_INTERVAL_MS)? {
+            builder = builder.flush_interval(Duration::from_millis(ms));
+        }
+        if let Some(capacity) = parsed(ENV_CHANNEL_CAPACITY)? {
+            builder = builder.channel_capacity(capacity);
+        }
+        if let Some(max_attempts) = parsed(ENV_MAX_ATTEMPTS)? {
+            builder = builder.retry_policy(RetryPolicy {
+                max_attempts,
+                ..RetryPolicy::default()
+            });
+        }
+        if let Some(channel) = var(ENV_ACK_CHANNEL)? {
+            builder = builder.indexer_ack(AckConfig::with_channel(channel));
+        }
+        if let Some(proxy) = var(ENV_PROXY)? {
+            builder = builder.proxy(ProxyConfig::new(proxy));
+        }
+
+        let ca_file = var(ENV_CA_FILE)?;
+        let insecure = flag(ENV_INSECURE_SKIP_VERIFY)?;
+        if ca_file.is_some() || insecure {
+            let mut tls = TlsConfig::new();
+            if let Some(path) = ca_file {
+                let pem = std::fs::read(&path).map_err(|e| invalid(ENV_CA_FILE, &path, e))?;
+                tls = tls.root_certificate(pem);
+            }
+            tls.danger_accept_invalid_certs = insecure;
+            builder = builder.tls(tls);
+        }
+        Ok(builder)
+    }
+}
+
+// set and not empty, which is how an unset variable often looks in a kubernetes manifest
+fn var(name: &'static str) -> Result<Option<String>, BuildError> {
+    match env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(value)) => Err(invalid(
+            name,
+            &value.to_string_lossy(),
+            "it isn't valid unicode",
+        )),
+    }
+}
+
+fn required(name: &'static str) -> Result<String, BuildError> {
+    var(name)?.ok_or(BuildError::MissingEnv(name))
+}
+
+fn parsed<T>(name: &'static str) -> Result<Option<T>, BuildError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match var(name)? {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e| invalid(name, &value, e)),
+        None => Ok(None),
+    }
+}
+
+fn flag(name: &'static str) -> Result<bool, BuildError> {
+    let Some(value) = var(name)? else {
+        return Ok(false);
+    };
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(invalid(name, &value, "expected true or false")),
+    }
+}
+
+fn invalid(name: &'static str, value: &str, reason: impl Display) -> BuildError {
+    BuildError::InvalidEnv {
+        name,
+        value: value.to_owned(),
+        reason: reason.to_string(),
+    }
+}
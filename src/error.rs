@@ -0,0 +1,220 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::builder::BuildError;
+use crate::hec::HecError;
+use crate::worker::FlushError;
+
+// something that went wrong inside the layer or its worker. none of these are allowed to take the
+// application down with them, they're handed to the ErrorPolicy instead.
+#[derive(Debug)]
+pub enum LayerError {
+    // the subscriber didn't know about a span it gave us the id of
+    SpanNotFound,
+    // a span was missing the data this layer stores on it, usually because the layer wasn't
+    // around when the span was created
+    MissingSpanData {
+        span: &'static str,
+    },
+    // an event couldn't be turned into json
+    Serialize(serde_json::Error),
+    // a batch couldn't be delivered, even after retrying
+    Export {
+        events: usize,
+        attempts: u32,
+        source: HecError,
+    },
+    // asking HEC about outstanding indexer acknowledgments failed
+    AckQuery(HecError),
+    // HEC took a batch but never acknowledged indexing it, even after sending it again
+    Unacknowledged {
+        events: usize,
+    },
+    // reading or writing the spool directory failed
+    Spool(std::io::Error),
+    // the spool hit its size cap, so this many bytes of the oldest batches in it were thrown away
+    SpoolFull {
+        bytes: u64,
+    },
+    // the circuit breaker was open, so a batch of this many events wasn't sent at all
+    CircuitOpen {
+        events: usize,
+    },
+    // a dead letter couldn't be handed to its sink
+    DeadLetter(std::io::Error),
+    // writing to the fallback sink failed, so the batch was lost after all
+    Fallback(std::io::Error),
+    // dropping the WorkerGuard gave up waiting for the worker to ship what it had left
+    ShutdownTimeout,
+    // the queue to the worker was full, so records are being dropped (see QueueFullPolicy). it's
+    // only reported the first time, until a record gets through again.
+    QueueFull,
+}
+
+impl fmt::Display for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayerError::SpanNotFound => write!(f, "span not found in the subscriber"),
+            LayerError::MissingSpanData { span } => {
+                write!(f, "span `{}` is missing its splunk hec data", span)
+            }
+            LayerError::Serialize(e) => write!(f, "failed to serialize event for splunk: {}", e),
+            LayerError::Export {
+                events,
+                attempts,
+                source,
+            } => write!(
+                f,
+                "failed to ship {} events to splunk after {} attempts: {}",
+                events, attempts, source
+            ),
+            LayerError::AckQuery(e) => write!(f, "failed to check splunk acks: {}", e),
+            LayerError::Unacknowledged { events } => write!(
+                f,
+                "splunk never acknowledged indexing a batch of {} events",
+                events
+            ),
+            LayerError::Spool(e) => write!(f, "failed to use the splunk spool: {}", e),
+            LayerError::SpoolFull { bytes } => write!(
+                f,
+                "the splunk spool is full, discarded {} bytes of the oldest events",
+                bytes
+            ),
+            LayerError::CircuitOpen { events } => write!(
+                f,
+                "splunk has been failing, so a batch of {} events wasn't sent",
+                events
+            ),
+            LayerError::DeadLetter(e) => write!(f, "failed to write a dead letter: {}", e),
+            LayerError::Fallback(e) => write!(f, "failed to write to the fallback sink: {}", e),
+            LayerError::ShutdownTimeout => {
+                write!(f, "timed out flushing events to splunk on shutdown")
+            }
+            LayerError::QueueFull => write!(f, "the splunk hec queue is full, dropping events"),
+        }
+    }
+}
+
+impl std::error::Error for LayerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LayerError::Serialize(e) => Some(e),
+            LayerError::Spool(e) | LayerError::DeadLetter(e) | LayerError::Fallback(e) => Some(e),
+            LayerError::Export { source, .. } | LayerError::AckQuery(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// every error the crate hands back, for code that would rather `?` them all into one type:
+//
+//   fn setup() -> Result<WorkerGuard, tracing_splunk_layer::Error> {
+//       let (layer, guard) = SplunkHecLayer::builder().build()?;
+//       ...
+//       guard.flush(Duration::from_secs(5))?;
+//   }
+#[derive(Debug)]
+pub enum Error {
+    // the layer couldn't be built as configured
+    Config(BuildError),
+    // something went wrong exporting, the same as what the ErrorPolicy is given
+    Layer(LayerError),
+    // the worker couldn't be reached, or took too long
+    Flush(FlushError),
+    // talking to HEC failed
+    Transport(HecError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(e) => write!(f, "{}", e),
+            Error::Layer(e) => write!(f, "{}", e),
+            Error::Flush(e) => write!(f, "{}", e),
+            Error::Transport(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    // the error it wraps is what it displays as, so that's skipped over
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(e) => e.source(),
+            Error::Layer(e) => e.source(),
+            Error::Flush(e) => e.source(),
+            Error::Transport(e) => e.source(),
+        }
+    }
+}
+
+impl From<BuildError> for Error {
+    fn from(e: BuildError) -> Self {
+        Error::Config(e)
+    }
+}
+
+impl From<LayerError> for Error {
+    fn from(e: LayerError) -> Self {
+        Error::Layer(e)
+    }
+}
+
+impl From<FlushError> for Error {
+    fn from(e: FlushError) -> Self {
+        Error::Flush(e)
+    }
+}
+
+impl From<HecError> for Error {
+    fn from(e: HecError) -> Self {
+        Error::Transport(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Layer(LayerError::Serialize(e))
+    }
+}
+
+// what happens to a LayerError, which a callback gets as an Error::Layer. errors from the worker
+// are handled on the worker thread, so a callback has to be Send + Sync.
+#[derive(Clone, Default)]
+pub enum ErrorPolicy {
+    // pretend nothing happened
+    Ignore,
+    // print the error to stderr. this doesn't go through tracing since that could land us right
+    // back in this layer.
+    #[default]
+    Log,
+    // hand the error to your own code
+    Callback(Arc<dyn Fn(&Error) + Send + Sync>),
+}
+
+impl ErrorPolicy {
+    pub fn callback<F>(callback: F) -> Self
+    where
+        F: Fn(&Error) + Send + Sync + 'static,
+    {
+        ErrorPolicy::Callback(Arc::new(callback))
+    }
+
+    pub(crate) fn handle(&self, error: LayerError) {
+        match self {
+            ErrorPolicy::Ignore => {}
+            ErrorPolicy::Log => eprintln!("{}", error),
+            ErrorPolicy::Callback(callback) => callback(&Error::Layer(error)),
+        }
+    }
+}
+
+impl fmt::Debug for ErrorPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorPolicy::Ignore => write!(f, "Ignore"),
+            ErrorPolicy::Log => write!(f, "Log"),
+            ErrorPolicy::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
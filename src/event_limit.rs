@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::io;
+
+use serde_json::Value;
+
+use crate::EventHash;
+
+// how much of its `events` list (see SpanEventMode::List) a span holds on to while it's open, so
+// a chatty loop inside one span can't grow it without bound
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanEventLimit {
+    pub max_events: usize,
+    // going by the events' size as json
+    pub max_bytes: usize,
+    pub overflow: SpanEventOverflow,
+}
+
+impl Default for SpanEventLimit {
+    fn default() -> Self {
+        SpanEventLimit {
+            max_events: 1000,
+            max_bytes: 256 * 1024,
+            overflow: SpanEventOverflow::default(),
+        }
+    }
+}
+
+// what happens to an event that would take a span over its SpanEventLimit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpanEventOverflow {
+    // make room by dropping the span's oldest events
+    #[default]
+    DropOldest,
+    // keep the events the span already has and drop the new one
+    Summarize,
+    // send what the span has so far as a checkpoint (see
+    // SplunkHecLayerBuilder::checkpoint_interval) and start the list over
+    Flush,
+}
+
+// the events recorded inside a span when using SpanEventMode::List
+#[derive(Default)]
+pub(crate) struct SpanEvents {
+    events: VecDeque<Value>,
+    bytes: usize,
+    // what the limit didn't leave room for, which goes out as `events_dropped`
+    dropped: u64,
+}
+
+impl SpanEvents {
+    // add `event` unless it won't fit and the limit says to flush first, in which case it's
+    // handed back
+    pub(crate) fn push(&mut self, event: Value, limit: Option<&SpanEventLimit>) -> Option<Value> {
+        let Some(limit) = limit else {
+            self.events.push_back(event);
+            return None;
+        };
+        let size = json_len(&event);
+        let fits = |events: &Self| {
+            events.events.len() < limit.max_events && events.bytes + size <= limit.max_bytes
+        };
+        if !fits(self) {
+            match limit.overflow {
+                SpanEventOverflow::DropOldest => {
+                    while !fits(self) {
+                        let Some(oldest) = self.events.pop_front() else {
+                            break;
+                        };
+                        self.bytes -= json_len(&oldest);
+                        self.dropped += 1;
+                    }
+                }
+                SpanEventOverflow::Summarize => {
+                    self.dropped += 1;
+                    return None;
+                }
+                SpanEventOverflow::Flush if !self.events.is_empty() => return Some(event),
+                SpanEventOverflow::Flush => {}
+            }
+        }
+        // even on its own it's over the limit, there's nothing to be done but drop it
+        if size > limit.max_bytes || limit.max_events == 0 {
+            self.dropped += 1;
+            return None;
+        }
+        self.bytes += size;
+        self.events.push_back(event);
+        None
+    }
+
+    // count an event push handed back that there's still no room for, because flushing didn't
+    // happen (the checkpoint was rate limited, say)
+    pub(crate) fn drop_event(&mut self) {
+        self.dropped += 1;
+    }
+
+    // `events`, and `events_dropped` if anything was, for exporting the span
+    pub(crate) fn record(self, fields: &mut EventHash) {
+        fields.insert("events".into(), Value::Array(self.events.into()));
+        if self.dropped > 0 {
+            fields.insert("events_dropped".into(), self.dropped.into());
+        }
+    }
+}
+
+fn json_len(value: &Value) -> usize {
+    struct Count(usize);
+
+    impl io::Write for Count {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut count = Count(0);
+    // writing a Value out can't fail
+    let _ = serde_json::to_writer(&mut count, value);
+    count.0
+}
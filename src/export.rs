@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::clock::Clock;
+use crate::destination::DestinationHandle;
+use crate::hec::HecMetadata;
+use crate::process::ProcessFields;
+use crate::processor::Processor;
+use crate::record::{EventRecord, MessageField};
+use crate::redact::Redactor;
+use crate::rename::FieldRenames;
+use crate::routing::{LevelRoutes, TenantRoutes};
+use crate::time::{HecTime, TimestampPrecision};
+use crate::worker::WorkerHandle;
+use crate::EventHash;
+
+// everything it takes to wrap collected fields up in the HEC envelope and get them to the worker.
+// it's cloned out of the layer for anything that ships events from outside a layer callback, like
+// the panic hook.
+#[derive(Clone)]
+pub(crate) struct Exporter {
+    pub(crate) worker: WorkerHandle,
+    // anywhere else spans and events go, see Destination
+    pub(crate) destinations: Vec<DestinationHandle>,
+    // only there with SplunkHecLayerBuilder::tenant_routes
+    pub(crate) tenants: Option<Arc<TenantRoutes>>,
+    pub(crate) metadata: HecMetadata,
+    pub(crate) level_routes: LevelRoutes,
+    pub(crate) indexed_fields: Vec<String>,
+    // added to every event that doesn't have a field of the same name already
+    pub(crate) global_fields: EventHash,
+    pub(crate) process_fields: ProcessFields,
+    pub(crate) timestamp_precision: TimestampPrecision,
+    // what spans and events are timestamped by, see SplunkHecLayerBuilder::clock
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) redactor: Redactor,
+    pub(crate) renames: FieldRenames,
+    pub(crate) message_field: MessageField,
+    pub(crate) processors: Vec<Arc<dyn Processor>>,
+}
+
+impl Exporter {
+    // `level` is the span or event's own, for picking its index
+    pub(crate) fn export(&self, mut event: EventHash, time: SystemTime, level: &tracing::Level) {
+        let mut metadata = self.metadata.clone();
+        if let Some(index) = self.level_routes.index(level) {
+            metadata.index = Some(index.to_owned());
+        }
+        metadata.route(&mut event);
+        self.process_fields.record_thread(&mut event);
+        for (name, value) in &self.global_fields {
+            if !event.contains_key(name) {
+                event.insert(name.clone(), value.clone());
+            }
+        }
+        // redaction goes by the names fields were recorded under, indexing by the exported ones
+        self.redactor.redact(&mut event);
+        self.renames.apply(&mut event);
+
+        let mut fields = EventHash::new();
+        for name in &self.indexed_fields {
+            if let Some((key, value)) = event.remove_entry(name.as_str()) {
+                fields.insert(key, value);
+            }
+        }
+
+        let mut record = EventRecord {
+            time: HecTime::new(time, self.timestamp_precision),
+            metadata,
+            event,
+            fields,
+            message: None,
+        };
+        // by the exported name, so a field renamed to `message` counts
+        record.extract_message(self.message_field);
+        for processor in &self.processors {
+            if processor.process(&mut record).is_break() {
+                return;
+            }
+        }
+        for destination in &self.destinations {
+            destination.send(&record, level);
+        }
+        let record = match &self.tenants {
+            Some(tenants) => match tenants.send(record, level) {
+                Some(record) => record,
+                None => return,
+            },
+            None => record,
+        };
+        self.worker.send(record, level);
+    }
+
+    // a metric event, see SpanMetrics. none of the event handling applies, just the envelope and
+    // the global fields as dimensions.
+    pub(crate) fn export_metric(
+        &self,
+        mut fields: EventHash,
+        time: SystemTime,
+        index: Option<&String>,
+    ) {
+        let mut metadata = self.metadata.clone();
+        if let Some(index) = index {
+            metadata.index = Some(index.clone());
+        }
+        for (name, value) in &self.global_fields {
+            if value.is_string() && !fields.contains_key(name) {
+                fields.insert(name.clone(), value.clone());
+            }
+        }
+        // metrics are as important as INFO events when the queue is full
+        self.worker.send(
+            EventRecord {
+                time: HecTime::new(time, self.timestamp_precision),
+                metadata,
+                event: EventHash::new(),
+                fields,
+                // it's what HEC expects the event to be for metrics
+                message: Some("metric".to_string()),
+            },
+            &tracing::Level::INFO,
+        );
+    }
+}
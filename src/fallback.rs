@@ -0,0 +1,65 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::batch::Batch;
+
+type WriteBatch = dyn Fn(&str) -> io::Result<()> + Send + Sync;
+
+// where events go when splunk can't be reached, so an outage leaves them in local logs instead of
+// losing them. only batches that failed because HEC was unavailable (retries running out on a
+// connection error, a 5xx or a 429, or the circuit breaker being open) end up here, and only when
+// there's no spool to keep them in for later. rejected batches still go to the dead letter sink.
+//
+// events are written exactly as they would have been sent, one line of json (or text, for the raw
+// endpoint) each, the same as WriterTransport.
+#[derive(Clone)]
+pub enum FallbackSink {
+    Stderr,
+    // appended to, the file is created if it isn't there
+    File(PathBuf),
+    Writer(Arc<WriteBatch>),
+}
+
+impl FallbackSink {
+    // anything fmt::Layer could write to, e.g. a tracing_appender rolling file
+    pub fn writer<W>(make_writer: W) -> Self
+    where
+        W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+    {
+        FallbackSink::Writer(Arc::new(move |events| {
+            let mut writer = make_writer.make_writer();
+            write_events(&mut writer, events)
+        }))
+    }
+
+    pub(crate) fn write(&self, batch: &Batch) -> io::Result<()> {
+        match self {
+            FallbackSink::Stderr => write_events(&mut io::stderr().lock(), batch.as_str()),
+            FallbackSink::File(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                write_events(&mut file, batch.as_str())
+            }
+            FallbackSink::Writer(write) => write(batch.as_str()),
+        }
+    }
+}
+
+fn write_events(writer: &mut impl Write, events: &str) -> io::Result<()> {
+    writer.write_all(events.as_bytes())?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+impl std::fmt::Debug for FallbackSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FallbackSink::Stderr => write!(f, "Stderr"),
+            FallbackSink::File(path) => f.debug_tuple("File").field(path).finish(),
+            FallbackSink::Writer(_) => write!(f, "Writer(..)"),
+        }
+    }
+}
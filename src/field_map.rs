@@ -0,0 +1,325 @@
+use std::borrow::Cow;
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::Value;
+
+type Key = Cow<'static, str>;
+
+// past this many fields a linear scan stops being cheaper than hashing the name
+const SMALL: usize = 16;
+
+// the fields of a span or event. almost everything has a handful of fields, which are quicker to
+// find by looking through them one by one than by hashing, and a list doesn't allocate anything
+// until the first one goes in. once there are more than SMALL of them (e.g. a span a lot of
+// events were merged into) it switches to a HashMap for good.
+//
+// it's used like a HashMap, iteration order is whatever it happens to be.
+#[derive(Clone, Default)]
+pub struct FieldMap(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Small(Vec<(Key, Value)>),
+    Large(HashMap<Key, Value>),
+}
+
+impl Default for Repr {
+    fn default() -> Self {
+        Repr::Small(Vec::new())
+    }
+}
+
+impl FieldMap {
+    pub fn new() -> Self {
+        FieldMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Repr::Small(pairs) => pairs.len(),
+            Repr::Large(fields) => fields.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.get_key_value(name).map(|(_, value)| value)
+    }
+
+    pub fn get_key_value(&self, name: &str) -> Option<(&Key, &Value)> {
+        match &self.0 {
+            Repr::Small(pairs) => pairs
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(key, value)| (key, value)),
+            Repr::Large(fields) => fields.get_key_value(name),
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Value> {
+        match &mut self.0 {
+            Repr::Small(pairs) => pairs
+                .iter_mut()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value),
+            Repr::Large(fields) => fields.get_mut(name),
+        }
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // the value that was there before, if there was one
+    pub fn insert(&mut self, name: Key, value: Value) -> Option<Value> {
+        if let Some(existing) = self.get_mut(&name) {
+            return Some(std::mem::replace(existing, value));
+        }
+        self.make_room();
+        match &mut self.0 {
+            Repr::Small(pairs) => pairs.push((name, value)),
+            Repr::Large(fields) => {
+                fields.insert(name, value);
+            }
+        }
+        None
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Value> {
+        self.remove_entry(name).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry(&mut self, name: &str) -> Option<(Key, Value)> {
+        match &mut self.0 {
+            Repr::Small(pairs) => {
+                let i = pairs.iter().position(|(key, _)| key == name)?;
+                Some(pairs.remove(i))
+            }
+            Repr::Large(fields) => fields.remove_entry(name),
+        }
+    }
+
+    pub fn entry(&mut self, name: Key) -> Entry<'_> {
+        Entry { map: self, name }
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&Key, &mut Value) -> bool) {
+        match &mut self.0 {
+            Repr::Small(pairs) => pairs.retain_mut(|(key, value)| keep(key, value)),
+            Repr::Large(fields) => fields.retain(|key, value| keep(key, value)),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = FieldMap::new();
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        match &self.0 {
+            Repr::Small(pairs) => Iter(IterRepr::Small(pairs.iter())),
+            Repr::Large(fields) => Iter(IterRepr::Large(fields.iter())),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        match &mut self.0 {
+            Repr::Small(pairs) => IterMut(IterMutRepr::Small(pairs.iter_mut())),
+            Repr::Large(fields) => IterMut(IterMutRepr::Large(fields.iter_mut())),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.iter_mut().map(|(_, value)| value)
+    }
+
+    // called before a field that isn't there yet goes in
+    fn make_room(&mut self) {
+        if let Repr::Small(pairs) = &mut self.0 {
+            if pairs.len() >= SMALL {
+                let mut fields = HashMap::with_capacity(SMALL * 2);
+                fields.extend(pairs.drain(..));
+                self.0 = Repr::Large(fields);
+            }
+        }
+    }
+}
+
+// what FieldMap::entry hands back, only the parts of HashMap's Entry anything needs
+pub struct Entry<'a> {
+    map: &'a mut FieldMap,
+    name: Key,
+}
+
+impl<'a> Entry<'a> {
+    pub fn or_insert(self, value: Value) -> &'a mut Value {
+        self.or_insert_with(|| value)
+    }
+
+    pub fn or_insert_with(self, value: impl FnOnce() -> Value) -> &'a mut Value {
+        let Entry { map, name } = self;
+        if !map.contains_key(&name) {
+            map.make_room();
+        }
+        match &mut map.0 {
+            Repr::Large(fields) => fields.entry(name).or_insert_with(value),
+            Repr::Small(pairs) => {
+                let i = match pairs.iter().position(|(key, _)| *key == name) {
+                    Some(i) => i,
+                    None => {
+                        pairs.push((name, value()));
+                        pairs.len() - 1
+                    }
+                };
+                &mut pairs[i].1
+            }
+        }
+    }
+}
+
+pub struct Iter<'a>(IterRepr<'a>);
+
+enum IterRepr<'a> {
+    Small(std::slice::Iter<'a, (Key, Value)>),
+    Large(hash_map::Iter<'a, Key, Value>),
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Key, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IterRepr::Small(pairs) => pairs.next().map(|(key, value)| (key, value)),
+            IterRepr::Large(fields) => fields.next(),
+        }
+    }
+}
+
+pub struct IterMut<'a>(IterMutRepr<'a>);
+
+enum IterMutRepr<'a> {
+    Small(std::slice::IterMut<'a, (Key, Value)>),
+    Large(hash_map::IterMut<'a, Key, Value>),
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a Key, &'a mut Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IterMutRepr::Small(pairs) => pairs.next().map(|(key, value)| (&*key, value)),
+            IterMutRepr::Large(fields) => fields.next(),
+        }
+    }
+}
+
+pub struct IntoIter(IntoIterRepr);
+
+enum IntoIterRepr {
+    Small(std::vec::IntoIter<(Key, Value)>),
+    Large(hash_map::IntoIter<Key, Value>),
+}
+
+impl Iterator for IntoIter {
+    type Item = (Key, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IntoIterRepr::Small(pairs) => pairs.next(),
+            IntoIterRepr::Large(fields) => fields.next(),
+        }
+    }
+}
+
+impl IntoIterator for FieldMap {
+    type Item = (Key, Value);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        match self.0 {
+            Repr::Small(pairs) => IntoIter(IntoIterRepr::Small(pairs.into_iter())),
+            Repr::Large(fields) => IntoIter(IntoIterRepr::Large(fields.into_iter())),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a FieldMap {
+    type Item = (&'a Key, &'a Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut FieldMap {
+    type Item = (&'a Key, &'a mut Value);
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> IterMut<'a> {
+        self.iter_mut()
+    }
+}
+
+impl Extend<(Key, Value)> for FieldMap {
+    fn extend<I: IntoIterator<Item = (Key, Value)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl FromIterator<(Key, Value)> for FieldMap {
+    fn from_iter<I: IntoIterator<Item = (Key, Value)>>(iter: I) -> Self {
+        let mut map = FieldMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+// like a HashMap, this panics if the field isn't there
+impl std::ops::Index<&str> for FieldMap {
+    type Output = Value;
+
+    fn index(&self, name: &str) -> &Value {
+        self.get(name).expect("no such field")
+    }
+}
+
+// equal when they have the same fields, however each one happens to be keeping them
+impl PartialEq for FieldMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(name, value)| other.get(name) == Some(value))
+    }
+}
+
+impl fmt::Debug for FieldMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl Serialize for FieldMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (name, value) in self {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
@@ -0,0 +1,240 @@
+use std::fmt;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use tracing::level_filters::LevelFilter;
+use tracing::subscriber::Interest;
+#[cfg(feature = "log")]
+use tracing::Event;
+use tracing::Metadata;
+use tracing_subscriber::layer::{Context, Filter};
+
+#[cfg(feature = "log")]
+use crate::log_bridge;
+
+// decides which spans and events this layer exports, independently of what the rest of the
+// subscriber records.
+//
+// this is deliberately checked inside the layer's callbacks rather than through Layer::enabled,
+// since a layer saying no from enabled (or register_callsite) turns the callsite off for every
+// layer in the subscriber. that would make it impossible to keep fmt at DEBUG locally while only
+// shipping INFO and up to splunk.
+//
+// it's also a per-layer Filter though, see SplunkHecLayer::filtered. that way tracing caches
+// what it decided for each callsite and only asks this layer about the ones it exports, without
+// the rest of the subscriber losing any of them.
+#[derive(Clone, Debug)]
+pub struct ExportFilter {
+    // shared with any FilterHandle, so the rules can change while the layer is running
+    rules: Arc<RwLock<FilterRules>>,
+}
+
+// what ExportFilter decides with, which is what the builder puts together
+#[derive(Clone, Debug)]
+pub(crate) struct FilterRules {
+    max_level: LevelFilter,
+    // levels for particular targets that win over max_level, the longest matching prefix counts
+    levels: Vec<(String, LevelFilter)>,
+    // target prefixes, if there are any then a target has to match one to be exported
+    allow: Vec<String>,
+    // target prefixes that are never exported, these win over the allow list
+    deny: Vec<String>,
+}
+
+impl Default for FilterRules {
+    fn default() -> Self {
+        FilterRules {
+            max_level: LevelFilter::TRACE,
+            levels: Vec::new(),
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+}
+
+impl FilterRules {
+    pub(crate) fn set_max_level(&mut self, level: LevelFilter) {
+        self.max_level = level;
+    }
+
+    pub(crate) fn allow(&mut self, target: String) {
+        self.allow.push(target);
+    }
+
+    pub(crate) fn deny(&mut self, target: String) {
+        self.deny.push(target);
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        if *metadata.level() > self.level_for(target) {
+            return false;
+        }
+
+        if self
+            .deny
+            .iter()
+            .any(|prefix| matches_target(prefix, target))
+        {
+            return false;
+        }
+        self.allow.is_empty()
+            || self
+                .allow
+                .iter()
+                .any(|prefix| matches_target(prefix, target))
+    }
+
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.levels
+            .iter()
+            .filter(|(prefix, _)| matches_target(prefix, target))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.max_level, |(_, level)| *level)
+    }
+
+    // the most verbose level anything could be exported at
+    fn max_level_hint(&self) -> LevelFilter {
+        self.levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.max_level, LevelFilter::max)
+    }
+}
+
+impl ExportFilter {
+    pub(crate) fn new(rules: FilterRules) -> Self {
+        ExportFilter {
+            rules: Arc::new(RwLock::new(rules)),
+        }
+    }
+
+    pub(crate) fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.rules().enabled(metadata)
+    }
+
+    pub(crate) fn handle(&self) -> FilterHandle {
+        FilterHandle(self.clone())
+    }
+
+    fn rules(&self) -> RwLockReadGuard<'_, FilterRules> {
+        self.rules.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// changes what the layer exports while it's running, e.g. turning on DEBUG for one target during
+// an incident. see SplunkHecLayer::filter_handle.
+#[derive(Clone, Debug)]
+pub struct FilterHandle(ExportFilter);
+
+impl FilterHandle {
+    // replace the level for everything and the levels for particular targets with `directives`,
+    // which are comma separated levels and `target=level` pairs like `info,my_app::db=debug`. a
+    // bare level is the level for everything, and stays TRACE if there isn't one. the allowed and
+    // denied targets are left as they are.
+    pub fn set_filter(&self, directives: &str) -> Result<(), InvalidFilter> {
+        let mut max_level = LevelFilter::TRACE;
+        let mut levels = Vec::new();
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            let invalid = |reason: String| InvalidFilter {
+                directive: directive.to_owned(),
+                reason,
+            };
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let target = target.trim();
+                    if target.is_empty() {
+                        return Err(invalid("the target is empty".into()));
+                    }
+                    let level = level
+                        .trim()
+                        .parse()
+                        .map_err(|e| invalid(format!("{}", e)))?;
+                    levels.push((target.to_owned(), level));
+                }
+                None => max_level = directive.parse().map_err(|e| invalid(format!("{}", e)))?,
+            }
+        }
+        self.update(|rules| {
+            rules.max_level = max_level;
+            rules.levels = levels;
+        });
+        Ok(())
+    }
+
+    // the level for every target that doesn't have one of its own
+    pub fn set_max_level(&self, level: impl Into<LevelFilter>) {
+        let level = level.into();
+        self.update(|rules| rules.max_level = level);
+    }
+
+    fn update(&self, change: impl FnOnce(&mut FilterRules)) {
+        change(&mut self.0.rules.write().unwrap_or_else(|e| e.into_inner()));
+        // callsites remember what the filter said about them, see the Filter impl below
+        tracing::callsite::rebuild_interest_cache();
+    }
+}
+
+// a directive FilterHandle::set_filter couldn't make sense of
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidFilter {
+    pub directive: String,
+    pub reason: String,
+}
+
+impl fmt::Display for InvalidFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid filter `{}`: {}", self.directive, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidFilter {}
+
+// everything the filter looks at is the same every time a callsite is hit, so the answer for a
+// callsite is good until FilterHandle changes the rules. the exception is the `log` bridge's
+// callsites, which carry records from every target, so those are decided one event at a time
+impl<S> Filter<S> for ExportFilter {
+    fn enabled(&self, metadata: &Metadata<'_>, _: &Context<'_, S>) -> bool {
+        #[cfg(feature = "log")]
+        if log_bridge::is_log_callsite(metadata) {
+            return true;
+        }
+        ExportFilter::enabled(self, metadata)
+    }
+
+    #[cfg(feature = "log")]
+    fn event_enabled(&self, event: &Event<'_>, _: &Context<'_, S>) -> bool {
+        log_bridge::log_metadata(event)
+            .is_none_or(|metadata| ExportFilter::enabled(self, &metadata))
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        #[cfg(feature = "log")]
+        if log_bridge::is_log_callsite(metadata) {
+            return Interest::sometimes();
+        }
+        if ExportFilter::enabled(self, metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.rules().max_level_hint())
+    }
+}
+
+// `my_crate` covers `my_crate` and `my_crate::db`, but not `my_crate_extras`
+fn matches_target(prefix: &str, target: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+// marks a span the filter turned away. spans like this are see-through: their children and events
+// are recorded against the closest ancestor that wasn't filtered out.
+pub(crate) struct FilteredOut;
@@ -0,0 +1,256 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::EventHash;
+
+// HEC's endpoint for json formatted events
+// (https://docs.splunk.com/Documentation/Splunk/latest/Data/HECRESTendpoints)
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+const EVENT_PATH: &str = "/services/collector/event";
+// the endpoint for plain text, which splunk breaks into events itself
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+const RAW_PATH: &str = "/services/collector/raw";
+// where ack ids are checked on when indexer acknowledgment is turned on
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+const ACK_PATH: &str = "/services/collector/ack";
+// answers 200 as long as HEC is taking events, no token needed
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+const HEALTH_PATH: &str = "/services/collector/health";
+
+// HEC answers every request with a small json body explaining what happened
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct HecResponse {
+    pub text: String,
+    pub code: i64,
+    // only there when the HEC token has indexer acknowledgment turned on, see AckConfig
+    #[serde(rename = "ackId", default)]
+    pub ack_id: Option<u64>,
+}
+
+impl HecResponse {
+    // what transports that never talk to HEC can hand back, e.g. test doubles
+    pub fn success() -> Self {
+        HecResponse {
+            text: "Success".to_string(),
+            code: 0,
+            ack_id: None,
+        }
+    }
+
+    // HEC explains what went wrong in the response body, so every transport turns its http
+    // client's response into a HecResponse or a HecError this same way. `retry_after` is the raw
+    // Retry-After header, if there was one.
+    pub fn parse(status: u16, retry_after: Option<&str>, body: &str) -> Result<Self, HecError> {
+        // only the delay-seconds form, HEC doesn't send http-dates
+        let retry_after = retry_after
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let parsed = serde_json::from_str::<HecResponse>(body).ok();
+        let success = (200..300).contains(&status);
+
+        match parsed {
+            Some(parsed) if success => Ok(parsed),
+            // some proxies in front of HEC reply 200 with an empty body, that's still a success
+            None if success => Ok(HecResponse::success()),
+            Some(parsed) => Err(HecError::Status {
+                status,
+                code: Some(parsed.code),
+                text: parsed.text,
+                retry_after,
+            }),
+            None => Err(HecError::Status {
+                status,
+                code: None,
+                text: body.to_string(),
+                retry_after,
+            }),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HecError {
+    // HEC got our request but didn't like it (bad token, disabled input, malformed data...)
+    Status {
+        status: u16,
+        code: Option<i64>,
+        text: String,
+        // HEC sends a Retry-After along with 429s and 503s when it's overloaded
+        retry_after: Option<Duration>,
+    },
+    // we never got an answer out of HEC at all, whatever the transport's own error was
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for HecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HecError::Status {
+                status,
+                code: Some(code),
+                text,
+                ..
+            } => write!(f, "HEC returned {} (code {}): {}", status, code, text),
+            HecError::Status { status, text, .. } => write!(f, "HEC returned {}: {}", status, text),
+            HecError::Transport(e) => write!(f, "failed to reach HEC: {}", e),
+        }
+    }
+}
+
+impl HecError {
+    pub fn transport(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        HecError::Transport(error.into())
+    }
+
+    // whether sending the exact same payload again could possibly work
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // a bad token or a malformed batch will fail no matter how many times we try
+            HecError::Status { status, .. } => *status == 429 || *status >= 500,
+            HecError::Transport(_) => true,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            HecError::Status { retry_after, .. } => *retry_after,
+            HecError::Transport(_) => None,
+        }
+    }
+}
+
+impl std::error::Error for HecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HecError::Transport(e) => Some(e.as_ref()),
+            HecError::Status { .. } => None,
+        }
+    }
+}
+
+// the url events get POSTed to for a HEC input at `endpoint`,
+// e.g. https://splunk.example.com:8088
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn event_url(endpoint: &str) -> String {
+    format!("{}{}", endpoint.trim_end_matches('/'), EVENT_PATH)
+}
+
+// the raw endpoint doesn't have an envelope, so the metadata goes in the query string and is the
+// same for everything in a request
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn raw_url(endpoint: &str, metadata: &HecMetadata) -> String {
+    let mut url = format!("{}{}", endpoint.trim_end_matches('/'), RAW_PATH);
+    let params = [
+        ("host", &metadata.host),
+        ("source", &metadata.source),
+        ("sourcetype", &metadata.sourcetype),
+        ("index", &metadata.index),
+    ];
+    let mut separator = '?';
+    for (name, value) in params {
+        if let Some(value) = value {
+            url.push(separator);
+            url.push_str(name);
+            url.push('=');
+            url.push_str(&percent_encode(value));
+            separator = '&';
+        }
+    }
+    url
+}
+
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn ack_url(endpoint: &str) -> String {
+    format!("{}{}", endpoint.trim_end_matches('/'), ACK_PATH)
+}
+
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn health_url(endpoint: &str) -> String {
+    format!("{}{}", endpoint.trim_end_matches('/'), HEALTH_PATH)
+}
+
+// the body of an ack query for `ack_ids`
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn ack_query(ack_ids: &[u64]) -> String {
+    serde_json::json!({ "acks": ack_ids }).to_string()
+}
+
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn authorization(token: &str) -> String {
+    format!("Splunk {}", token)
+}
+
+// what the built in transports say they are unless told otherwise
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) const USER_AGENT: &str = concat!("tracing-splunk-layer/", env!("CARGO_PKG_VERSION"));
+
+// whether `name: value` can go in an http request as it is, so a typo is caught by the builder
+// rather than failing every request
+pub(crate) fn is_valid_header(name: &str, value: &str) -> bool {
+    let token = |b: u8| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b);
+    !name.is_empty()
+        && name.bytes().all(token)
+        && value
+            .bytes()
+            .all(|b| b == b'\t' || (b >= 0x20 && b != 0x7f))
+}
+
+// the per-event metadata HEC lets us set alongside the event itself. anything left unset falls
+// back to whatever defaults the HEC input was configured with.
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct HecMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sourcetype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+}
+
+// fields that set the envelope instead of being exported, e.g.
+// info_span!("login", splunk.index = "audit") sends that span (and everything in it) to the audit
+// index
+pub(crate) fn is_routing_field(name: &str) -> bool {
+    matches!(
+        name,
+        "splunk.index" | "splunk.source" | "splunk.sourcetype" | "splunk.host"
+    )
+}
+
+impl HecMetadata {
+    // move any routing fields out of `event` and onto the envelope. only strings count, a
+    // `splunk.index = 5` is left where it is.
+    pub(crate) fn route(&mut self, event: &mut EventHash) {
+        let routed = [
+            ("splunk.index", &mut self.index),
+            ("splunk.source", &mut self.source),
+            ("splunk.sourcetype", &mut self.sourcetype),
+            ("splunk.host", &mut self.host),
+        ];
+        for (name, slot) in routed {
+            match event.remove(name) {
+                Some(serde_json::Value::String(value)) => *slot = Some(value),
+                Some(other) => {
+                    event.insert(name.into(), other);
+                }
+                None => {}
+            }
+        }
+    }
+}
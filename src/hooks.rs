@@ -0,0 +1,60 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Error, LayerError};
+
+// a batch that made it to splunk, for SplunkHecLayerBuilder::on_export_success
+#[derive(Clone, Debug)]
+pub struct BatchSummary {
+    pub events: usize,
+    pub bytes: usize,
+    // 1 unless it had to be retried
+    pub attempts: u32,
+    // from the first attempt to HEC taking it, retries and backoff included
+    pub elapsed: Duration,
+    // only with indexer acknowledgment, see AckConfig
+    pub ack_id: Option<u64>,
+}
+
+type OnSuccess = dyn Fn(&BatchSummary) + Send + Sync;
+type OnError = dyn Fn(&Error) + Send + Sync;
+
+// the callbacks the worker makes as batches go out. they're called on the worker thread, so
+// anything slow in one holds up every batch behind it.
+#[derive(Clone, Default)]
+pub(crate) struct ExportHooks {
+    pub(crate) on_success: Option<Arc<OnSuccess>>,
+    pub(crate) on_error: Option<Arc<OnError>>,
+}
+
+impl ExportHooks {
+    pub(crate) fn succeeded(&self, summary: impl FnOnce() -> BatchSummary) {
+        if let Some(on_success) = &self.on_success {
+            on_success(&summary());
+        }
+    }
+
+    // `reason` is always a LayerError::Export, handed back for the ErrorPolicy once the callback
+    // has seen it
+    pub(crate) fn failed(&self, reason: LayerError) -> LayerError {
+        let Some(on_error) = &self.on_error else {
+            return reason;
+        };
+        let error = Error::Layer(reason);
+        on_error(&error);
+        match error {
+            Error::Layer(reason) => reason,
+            _ => unreachable!("the export error was made a LayerError just above"),
+        }
+    }
+}
+
+impl fmt::Debug for ExportHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExportHooks")
+            .field("on_success", &self.on_success.is_some())
+            .field("on_error", &self.on_error.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,95 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use http::{Request, Response};
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+use crate::TraceParent;
+
+// a tower layer that puts every request a service handles in a span, so an axum or hyper
+// service gets a splunk event per request with one line of setup:
+//
+//   let app = Router::new().route("/", get(handler)).layer(HttpSpanLayer::new());
+//
+// the span is `http_request`, with `http.method`, `http.route` (the path the request was for)
+// and `http.status_code` once the response is ready, and how long it took as `elapsed_time` like
+// any other span. a request with a `traceparent` header joins the caller's trace, see
+// TraceParent. a service that fails instead of answering gets `error = true`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HttpSpanLayer;
+
+impl HttpSpanLayer {
+    pub fn new() -> Self {
+        HttpSpanLayer
+    }
+}
+
+impl<S> tower_layer::Layer<S> for HttpSpanLayer {
+    type Service = HttpSpan<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpSpan { inner }
+    }
+}
+
+// the service HttpSpanLayer wraps another in
+#[derive(Clone, Debug)]
+pub struct HttpSpan<S> {
+    inner: S,
+}
+
+// what an HttpSpan answers with, the inner service's response once its span has been filled in
+pub type HttpSpanFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
+
+impl<S, B, R> tower_service::Service<Request<B>> for HttpSpan<S>
+where
+    S: tower_service::Service<Request<B>, Response = Response<R>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<R>;
+    type Error = S::Error;
+    type Future = HttpSpanFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let span = request_span(&request);
+        let response = span.in_scope(|| self.inner.call(request));
+        let recorded = span.clone();
+        Box::pin(
+            async move {
+                let response = response.await;
+                match &response {
+                    Ok(response) => recorded.record("http.status_code", response.status().as_u16()),
+                    Err(_) => recorded.record("error", true),
+                };
+                response
+            }
+            .instrument(span),
+        )
+    }
+}
+
+fn request_span<B>(request: &Request<B>) -> Span {
+    let headers = request
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)));
+    let span = tracing::info_span!(
+        "http_request",
+        http.method = %request.method(),
+        http.route = request.uri().path(),
+        http.status_code = Empty,
+        error = Empty,
+        traceparent = Empty,
+    );
+    // recorded before anything can be made inside it, so the whole request joins the trace
+    if let Some(traceparent) = TraceParent::from_headers(headers) {
+        span.record("traceparent", traceparent.to_string());
+    }
+    span
+}
@@ -0,0 +1,57 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::{self, Write};
+use std::sync::{OnceLock, RwLock};
+
+// the field names that didn't come straight from a callsite (renames, `<field>.original_length`,
+// `metric_name:span.<field>` and so on) are made the same way for every span and event, so rather
+// than allocating the same string over and over they're built once and kept for the rest of the
+// process, where they're as good as a callsite's own &'static str.
+//
+// there's a limit on how many, in case a name has something like an id in it. past that new
+// names are owned strings again, same as before.
+const MAX_NAMES: usize = 4096;
+
+fn names() -> &'static RwLock<HashSet<&'static str>> {
+    static NAMES: OnceLock<RwLock<HashSet<&'static str>>> = OnceLock::new();
+    NAMES.get_or_init(Default::default)
+}
+
+thread_local! {
+    // where intern_fmt writes a name out to look it up, so a name that's already known doesn't
+    // allocate at all
+    static SCRATCH: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+pub(crate) fn intern(name: &str) -> Cow<'static, str> {
+    let names = names();
+    if let Some(known) = names.read().unwrap_or_else(|e| e.into_inner()).get(name) {
+        return Cow::Borrowed(known);
+    }
+    let mut names = names.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(known) = names.get(name) {
+        return Cow::Borrowed(known);
+    }
+    if names.len() >= MAX_NAMES {
+        return Cow::Owned(name.to_owned());
+    }
+    let leaked: &'static str = Box::leak(name.into());
+    names.insert(leaked);
+    Cow::Borrowed(leaked)
+}
+
+// the same as intern(&format!(...)), without the format! when the name is already known
+pub(crate) fn intern_fmt(args: fmt::Arguments<'_>) -> Cow<'static, str> {
+    // a Display impl somewhere in `args` could log, and end up back here
+    SCRATCH
+        .try_with(|scratch| {
+            let mut scratch = scratch.try_borrow_mut().ok()?;
+            scratch.clear();
+            scratch.write_fmt(args).ok()?;
+            Some(intern(&scratch))
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| intern(&args.to_string()))
+}
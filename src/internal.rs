@@ -0,0 +1,55 @@
+use std::cell::Cell;
+
+// whether this thread is already busy inside the layer, or belongs to the exporter. anything
+// traced from there (the layer's own callbacks, user callbacks run by them, the http client on the
+// worker thread) is ignored, otherwise it would feed straight back into the layer and either loop
+// forever or deadlock on a span's extensions.
+thread_local! {
+    static INTERNAL: Cell<bool> = const { Cell::new(false) };
+}
+
+// clears the flag again once the layer's callback is done
+pub(crate) struct InternalGuard(());
+
+impl Drop for InternalGuard {
+    fn drop(&mut self) {
+        INTERNAL.with(|internal| internal.set(false));
+    }
+}
+
+// None if we're already inside the layer, in which case the caller should stay out of it
+pub(crate) fn enter() -> Option<InternalGuard> {
+    INTERNAL.with(|internal| {
+        if internal.replace(true) {
+            None
+        } else {
+            Some(InternalGuard(()))
+        }
+    })
+}
+
+// for threads that only ever do the exporter's work
+#[cfg(feature = "reqwest")]
+pub(crate) fn mark_thread() {
+    INTERNAL.with(|internal| internal.set(true));
+}
+
+// a future that counts as internal whenever it's polled, for exporter work that runs on someone
+// else's threads, like a borrowed tokio runtime
+#[cfg(feature = "tokio")]
+pub(crate) struct Internal<F>(pub(crate) F);
+
+#[cfg(feature = "tokio")]
+impl<F: std::future::Future + Unpin> std::future::Future for Internal<F> {
+    type Output = F::Output;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<F::Output> {
+        let previous = INTERNAL.with(|internal| internal.replace(true));
+        let output = std::pin::Pin::new(&mut self.0).poll(cx);
+        INTERNAL.with(|internal| internal.set(previous));
+        output
+    }
+}
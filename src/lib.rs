@@ -1,166 +1,1225 @@
-use std::collections::HashMap;
-use std::time::{Instant, SystemTime};
-use tracing::field::{Field, Value, Visit};
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::field::{Field, Visit};
 use tracing::span;
 use tracing::Subscriber;
 use tracing_subscriber::{
+    filter::Filtered,
     layer::{Context, Layer},
-    registry::LookupSpan,
+    registry::{LookupSpan, SpanRef},
 };
 
+mod ack;
+mod aggregate;
+mod batch;
+mod builder;
+mod bytes;
+mod capture;
+mod cim;
+mod circuit;
+mod clock;
+#[cfg(feature = "cloud-metadata")]
+mod cloud;
+mod collision;
+#[cfg(feature = "toml")]
+mod config;
+mod dead_letter;
+mod destination;
+mod env;
+mod error;
+mod event_limit;
+mod export;
+mod fallback;
+mod field_map;
+mod filter;
+mod hec;
+mod hooks;
+#[cfg(feature = "http")]
+mod http;
+mod intern;
+mod internal;
+#[cfg(feature = "log")]
+mod log_bridge;
+mod metadata;
+mod metric;
+mod metrics;
+mod number;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod oversize;
+mod panic;
+mod parse;
+mod probe;
+mod process;
+mod processor;
+mod proxy;
+mod queue;
+mod rate_limit;
+mod raw;
+mod record;
+mod redact;
+mod reload;
+mod rename;
+mod retry;
+mod routing;
+mod sampling;
+mod serialized;
+#[cfg(feature = "signal")]
+mod signal;
+mod spool;
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+mod structured;
+#[cfg(feature = "testing")]
+mod testing;
+mod time;
+mod tls;
+mod trace;
+mod transport;
+mod truncate;
+mod worker;
+pub use ack::{
+    AckConfig, AckStatus, DEFAULT_ACK_MAX_RESENDS, DEFAULT_ACK_POLL_INTERVAL, DEFAULT_ACK_TIMEOUT,
+};
+pub use aggregate::SpanAggregation;
+pub use batch::{
+    AdaptiveBatching, Batch, BatchConfig, DEFAULT_FLUSH_INTERVAL, DEFAULT_MAX_BATCH_BYTES,
+    DEFAULT_MAX_BATCH_EVENTS,
+};
+pub use builder::{BuildError, SplunkHecLayerBuilder};
+pub use bytes::{ByteEncoding, Encoded};
+pub use capture::{Captured, TestSink};
+pub use cim::CimModel;
+pub use circuit::{
+    CircuitBreakerConfig, CircuitState, DEFAULT_CIRCUIT_FAILURE_THRESHOLD,
+    DEFAULT_CIRCUIT_PROBE_INTERVAL,
+};
+pub use clock::{Clock, ManualClock, SystemClock};
+#[cfg(feature = "cloud-metadata")]
+pub use cloud::{CloudMetadata, CloudProvider};
+pub use collision::FieldCollision;
+pub use dead_letter::{DeadLetter, DeadLetterSink};
+pub use destination::Destination;
+pub use error::{Error, ErrorPolicy, LayerError};
+use event_limit::SpanEvents;
+pub use event_limit::{SpanEventLimit, SpanEventOverflow};
+pub use fallback::FallbackSink;
+pub use field_map::FieldMap;
+pub use filter::{ExportFilter, FilterHandle, InvalidFilter};
+pub use hec::{HecError, HecMetadata, HecResponse};
+pub use hooks::BatchSummary;
+#[cfg(feature = "http")]
+pub use http::{HttpSpan, HttpSpanFuture, HttpSpanLayer};
+#[cfg(feature = "log")]
+pub use log_bridge::{init_log_bridge, SetLoggerError};
+pub use metric::SpanMetrics;
+pub use metrics::{DropReason, LayerMetrics, MetricsSnapshot};
+pub use oversize::OversizedEvent;
+pub use probe::ProbeError;
+pub use processor::Processor;
+pub use proxy::{Proxy, ProxyConfig, ProxyCredentials};
+pub use rate_limit::{RateLimit, RateLimitConfig};
+pub use raw::{LineFormatter, Logfmt};
+pub use record::{EventRecord, MessageField};
+pub use redact::DEFAULT_REDACTION_MASK;
+pub use reload::{ConfigHandle, ReloadError};
+pub use rename::KeyCase;
+pub use retry::RetryPolicy;
+pub use routing::{Route, RouteKey};
+pub use sampling::TailSample;
+pub use serialized::Serialized;
+#[cfg(feature = "signal")]
+pub use signal::{flush_on_signal, SignalError};
+pub use spool::{
+    SpoolConfig, DEFAULT_SPOOL_MAX_BYTES, DEFAULT_SPOOL_REPLAY_INTERVAL,
+    DEFAULT_SPOOL_SEGMENT_BYTES,
+};
+#[cfg(feature = "testing")]
+pub use testing::{MockHec, MockResponse, ReceivedRequest};
+use time::SpanTimings;
+pub use time::{ElapsedTime, ElapsedUnit, HecTime, TimestampPrecision};
+pub use tls::{ClientIdentity, TlsBackend, TlsConfig, TlsError};
+pub use trace::TraceParent;
+#[cfg(feature = "reqwest")]
+pub use transport::ReqwestTransport;
+#[cfg(feature = "ureq")]
+pub use transport::UreqTransport;
+pub use transport::{
+    AckFuture, FileConfig, FileTransport, ProbeFuture, TcpTransport, Transport, TransportFuture,
+    UdpTransport, WriterTransport, DEFAULT_CONNECT_TIMEOUT, DEFAULT_FILE_MAX_BYTES,
+    DEFAULT_FILE_MAX_FILES, DEFAULT_POOL_IDLE_TIMEOUT, DEFAULT_REQUEST_TIMEOUT,
+};
+#[cfg(unix)]
+pub use transport::{UnixDatagramTransport, UnixStreamTransport};
+pub use worker::{
+    FlushError, LoadShedding, QueueFullPolicy, ShutdownReport, WorkerGuard,
+    DEFAULT_CHANNEL_CAPACITY, DEFAULT_SHUTDOWN_TIMEOUT,
+};
+// so splunk_event! works without the caller naming tracing themselves
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+use aggregate::Aggregator;
+use export::Exporter;
+use filter::FilteredOut;
+use intern::{intern, intern_fmt};
+use metadata::MetadataFields;
+use metrics::Counters;
+use parse::DebugParsing;
+use rate_limit::RateLimiter;
+use sampling::{head_sample, HeadSampleRatio, NotSampled, SawError, TailSampler};
+use trace::{FindTraceParent, SpanIds};
+use truncate::{Bounded, FieldLengths};
+
 // remove some boilerplate with this type alias for our events
 // serde_json provides a convenient enum for valid json body values
-pub type EventHash<'a> = HashMap<&'a str, serde_json::Value>;
+// keys are Cow so the usual callsite field names (which are all &'static str) are stored without
+// allocating, while renamed, prefixed or otherwise made up names can still be owned Strings. see
+// FieldMap for why it isn't just a HashMap.
+pub type EventHash = FieldMap;
 
 // this is essentially a custom json layer implimentation
-#[derive(Clone, Debug, serde::Serialize)]
-pub struct EventStorage<'a>(EventHash<'a>);
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct EventStorage(EventHash, #[serde(skip)] RecordOptions);
+
+// how values get recorded, the same for every span and event
+#[derive(Clone, Debug, Default)]
+struct RecordOptions {
+    // whether recorded errors get their Debug output kept too, see
+    // SplunkHecLayerBuilder::with_error_debug
+    error_debug: bool,
+    max_lengths: Arc<FieldLengths>,
+    byte_encoding: ByteEncoding,
+    debug_parsing: Arc<DebugParsing>,
+}
 
-impl<'a> EventStorage<'a> {
+impl EventStorage {
     pub fn new() -> Self {
         EventStorage::default()
     }
 
+    fn recording(options: &RecordOptions) -> Self {
+        EventStorage(EventHash::new(), options.clone())
+    }
+
+    // a string value, cut short if it's over the field's max length
+    fn insert_string(
+        &mut self,
+        name: Cow<'static, str>,
+        write: impl FnOnce(&mut dyn std::fmt::Write) -> std::fmt::Result,
+    ) {
+        let Some(limit) = self.1.max_lengths.limit(&name) else {
+            let mut value = String::new();
+            let _ = write(&mut value);
+            self.0.insert(name, value.into());
+            return;
+        };
+        let mut bounded = Bounded::new(limit);
+        let _ = write(&mut bounded);
+        let (value, original_length) = bounded.finish();
+        if let Some(length) = original_length {
+            self.0.insert(
+                intern_fmt(format_args!("{}.original_length", name)),
+                length.into(),
+            );
+        }
+        self.0.insert(name, value.into());
+    }
+
     pub fn events(&self) -> &EventHash {
         &self.0
     }
-}
 
-impl<'a> Default for EventStorage<'a> {
-    fn default() -> Self {
-        EventStorage(HashMap::new())
+    // record a field that didn't come from a callsite, e.g. one with a dynamically built name
+    pub fn insert(
+        &mut self,
+        name: impl Into<Cow<'static, str>>,
+        value: impl Into<serde_json::Value>,
+    ) {
+        self.0.insert(name.into(), value.into());
     }
 }
 
 // we need to impliment Visit to add the logic necessary to record a field of a specific
 // type. (https://docs.rs/tracing-subscriber/0.3.6/tracing_subscriber/field/trait.Visit.html)
 // we're basically just inserting field-value pairs into our EventStorage object
-impl<'a> Visit for EventStorage<'a> {
+impl Visit for EventStorage {
     fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
-        self.0.insert(
-            field.name(),
-            serde_json::Value::from(format!("{:?}", value)),
-        );
+        if field.name() == serialized::FIELD {
+            serialized::flatten(&format!("{:?}", value), &mut self.0);
+            return;
+        }
+        if self.1.debug_parsing.applies(field.name()) {
+            let formatted = format!("{:?}", value);
+            match parse::parse(&formatted) {
+                Some(parsed) => {
+                    self.0.insert(Cow::Borrowed(field.name()), parsed);
+                }
+                None => {
+                    self.insert_string(Cow::Borrowed(field.name()), |w| w.write_str(&formatted))
+                }
+            }
+            return;
+        }
+        self.insert_string(Cow::Borrowed(field.name()), |w| write!(w, "{:?}", value));
     }
 
     fn record_f64(&mut self, field: &Field, value: f64) {
-        self.0.insert(field.name(), serde_json::Value::from(value));
+        self.0
+            .insert(Cow::Borrowed(field.name()), serde_json::Value::from(value));
     }
 
     fn record_i64(&mut self, field: &Field, value: i64) {
-        self.0.insert(field.name(), serde_json::Value::from(value));
+        self.0
+            .insert(Cow::Borrowed(field.name()), serde_json::Value::from(value));
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
-        self.0.insert(field.name(), serde_json::Value::from(value));
+        self.0
+            .insert(Cow::Borrowed(field.name()), serde_json::Value::from(value));
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.0
+            .insert(Cow::Borrowed(field.name()), number::from_u128(value));
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.0
+            .insert(Cow::Borrowed(field.name()), number::from_i128(value));
     }
 
     fn record_bool(&mut self, field: &Field, value: bool) {
-        self.0.insert(field.name(), serde_json::Value::from(value));
+        self.0
+            .insert(Cow::Borrowed(field.name()), serde_json::Value::from(value));
+    }
+
+    // nested structs and maps keep their shape, see structured::to_json
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable::Value<'_>) {
+        self.0
+            .insert(Cow::Borrowed(field.name()), structured::to_json(value));
+    }
+
+    fn record_bytes(&mut self, field: &Field, value: &[u8]) {
+        let encoded = Encoded::new(value, self.1.byte_encoding);
+        self.insert_string(Cow::Borrowed(field.name()), |w| write!(w, "{}", encoded));
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
-        self.0.insert(field.name(), serde_json::Value::from(value));
+        self.insert_string(Cow::Borrowed(field.name()), |w| w.write_str(value));
+    }
+
+    // an `error = &e as &dyn Error` field becomes `error.message`, plus `error.chain` with every
+    // source() under it, outermost first
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        let name = field.name();
+        let mut chain = Vec::new();
+        let mut source = value.source();
+        while let Some(error) = source {
+            chain.push(serde_json::Value::from(error.to_string()));
+            source = error.source();
+        }
+        self.0.insert(
+            intern_fmt(format_args!("{}.message", name)),
+            value.to_string().into(),
+        );
+        self.0.insert(
+            intern_fmt(format_args!("{}.chain", name)),
+            serde_json::Value::Array(chain),
+        );
+        if self.1.error_debug {
+            self.insert_string(intern_fmt(format_args!("{}.debug", name)), |w| {
+                write!(w, "{:?}", value)
+            });
+        }
     }
 }
 
 // this is the actual layer which handles the tracing logic
-pub struct SplunkHecLayer;
+// all of the I/O happens on a background worker so closing a span only costs us a serialization
+// and a push onto a bounded queue.
+pub struct SplunkHecLayer {
+    exporter: Exporter,
+    elapsed_time: ElapsedTime,
+    metadata_fields: MetadataFields,
+    span_event_mode: SpanEventMode,
+    field_inheritance: FieldInheritance,
+    field_collision: FieldCollision,
+    span_hierarchy: bool,
+    span_export_mode: SpanExportMode,
+    checkpoint_interval: Option<Duration>,
+    span_event_limit: Option<SpanEventLimit>,
+    record_options: RecordOptions,
+    span_metrics: Option<SpanMetrics>,
+    aggregator: Option<Arc<Aggregator>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    tail_sampler: TailSampler,
+    head_sample_ratio: Arc<HeadSampleRatio>,
+    filter: ExportFilter,
+    errors: ErrorPolicy,
+    counters: Arc<Counters>,
+    #[cfg(feature = "opentelemetry")]
+    otel: otel::OtelDispatch,
+}
+
+// when a span was created, which is the time HEC will index it under
+struct SpanTimestamp(SystemTime);
+
+// how events that happen inside of a span end up in that span's export
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpanEventMode {
+    // event fields are recorded straight onto the span, so a later event's fields overwrite an
+    // earlier one's
+    #[default]
+    Merge,
+    // each event is kept whole, with its own time, level and message, in an `events` array on
+    // the span
+    List,
+    // each event is exported right away as an event of its own, with the fields of the spans
+    // it's in (going by FieldInheritance) and the `trace_id` and `span_id` of the one it's
+    // directly in. the spans are still exported when they close, without the events' fields.
+    Export,
+}
+
+// which spans go to HEC as events of their own, and what they carry
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpanExportMode {
+    // every span, with whatever it inherits from the spans above it, see FieldInheritance
+    #[default]
+    Inherited,
+    // one event per root span, with every span that closed inside it nested under `children`
+    // (and theirs under their own `children`) instead of exported on its own. each child keeps
+    // its own fields, durations and time, but doesn't inherit from the spans it's nested in, and
+    // it's the root that's routed, rate limited and sampled for the whole tree.
+    Tree,
+    // every span, with only its own fields. its parent is referred to by `parent_span_id`, which
+    // along with `span_id` and `trace_id` is enough to put the tree back together in splunk,
+    // e.g. `stats values(name) by trace_id`. routing fields are still inherited.
+    References,
+}
+
+// which of the fields set on the spans above a span end up in its export too. a span's own fields
+// always win over the ones it inherits. routing fields like `splunk.index` are inherited whatever
+// this says, since they pick where the whole tree goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FieldInheritance {
+    // only the span's own fields
+    None,
+    // its own, and the ones set on the span right above it
+    Parent,
+    // everything from every span up to the root
+    #[default]
+    Full,
+}
+
+// the closest span in `scope` this layer is recording, looking straight through any spans that
+// were filtered out
+fn nearest_recorded<'a, S>(
+    mut scope: impl Iterator<Item = SpanRef<'a, S>>,
+) -> Option<SpanRef<'a, S>>
+where
+    S: Subscriber + for<'l> LookupSpan<'l>,
+{
+    scope.find(|span| span.extensions().get::<FilteredOut>().is_none())
+}
+
+// a span's own fields, without anything inherited from its parents, kept around for the span
+// hierarchy
+#[derive(Default)]
+struct SpanFields(EventStorage);
+
+// when a long lived span last sent a checkpoint, see SplunkHecLayerBuilder::checkpoint_interval
+struct SpanCheckpoint {
+    last: Instant,
+    sent: u64,
+}
+
+// the spans a span follows from, e.g. the request that queued a job the span is working on
+#[derive(Default)]
+struct SpanLinks(Vec<SpanIds>);
+
+// the spans that closed inside a span with SpanExportMode::Tree, each with
+// their own children, waiting for it to close too
+#[derive(Default)]
+struct SpanChildren(Vec<serde_json::Value>);
+
+impl SplunkHecLayer {
+    // `endpoint` is the base url of your HEC input and `token` is the HEC token for it. hold on
+    // to the guard for as long as you want events shipped, see WorkerGuard. fails the same way
+    // SplunkHecLayerBuilder::build does.
+    #[cfg(feature = "ureq")]
+    pub fn new(endpoint: &str, token: &str) -> Result<(Self, WorkerGuard), Error> {
+        SplunkHecLayer::builder()
+            .endpoint(endpoint)
+            .token(token)
+            .build()
+    }
+
+    // configured entirely from SPLUNK_HEC_URL, SPLUNK_HEC_TOKEN and friends, see
+    // SplunkHecLayerBuilder::from_env
+    pub fn from_env() -> Result<(Self, WorkerGuard), Error> {
+        SplunkHecLayerBuilder::from_env()?.build()
+    }
+
+    // use the builder when you need to set the index, source, sourcetype or host
+    pub fn builder() -> SplunkHecLayerBuilder {
+        SplunkHecLayerBuilder::new()
+    }
+
+    // the same numbers as WorkerGuard::metrics, for when the layer is the easier thing to reach
+    pub fn metrics(&self) -> LayerMetrics {
+        LayerMetrics::new(self.counters.clone())
+    }
+
+    // for changing the max level and per-target levels while the layer is running, it works the
+    // same whether or not the layer is filtered()
+    pub fn filter_handle(&self) -> FilterHandle {
+        self.filter.handle()
+    }
+
+    // for changing batching, the head sampling ratio and the HEC token while the layer is
+    // running, see ConfigHandle
+    pub fn config_handle(&self) -> ConfigHandle {
+        ConfigHandle::new(self.exporter.worker.clone(), self.head_sample_ratio.clone())
+    }
+
+    // the layer with its max level and target lists as a per-layer filter, e.g.
+    //
+    //   tracing_subscriber::registry().with(layer.filtered()).with(fmt::layer())
+    //
+    // tracing remembers which callsites the filter turned away and stops asking, so filtered out
+    // spans and events cost next to nothing, instead of each one coming through the layer to be
+    // checked. the other layers still see everything.
+    pub fn filtered<S>(self) -> Filtered<Self, ExportFilter, S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let filter = self.filter.clone();
+        self.with_filter(filter)
+    }
+
+    // look a span up, reporting rather than panicking if the subscriber has lost track of it
+    fn span<'a, S>(&self, id: &span::Id, ctx: &'a Context<'_, S>) -> Option<SpanRef<'a, S>>
+    where
+        S: Subscriber + for<'l> LookupSpan<'l>,
+    {
+        let span = ctx.span(id);
+        if span.is_none() {
+            self.errors.handle(LayerError::SpanNotFound);
+        }
+        span
+    }
+
+    fn missing_span_data<S>(&self, span: &SpanRef<'_, S>)
+    where
+        S: Subscriber + for<'l> LookupSpan<'l>,
+    {
+        self.errors
+            .handle(LayerError::MissingSpanData { span: span.name() });
+    }
+
+    // whether the rate limit lets a span or event from here be exported, counting it if not
+    fn allowed(&self, metadata: &'static tracing::Metadata<'static>) -> bool {
+        let Some(limiter) = &self.rate_limiter else {
+            return true;
+        };
+        let allowed = limiter.allow(metadata.target());
+        if !allowed {
+            self.counters.dropped(DropReason::RateLimited, 1);
+        }
+        allowed
+    }
+
+    // record an event's metadata and fields into a fresh map of its own
+    fn record_event(&self, event: &tracing::Event<'_>) -> EventHash {
+        let mut event_visitor = EventStorage::recording(&self.record_options);
+        self.metadata_fields
+            .record(event.metadata(), &mut event_visitor.0);
+        event.record(&mut event_visitor);
+        event_visitor.0
+    }
+
+    // a record that came from the `log` crate through init_log_bridge, exported on its own with
+    // the level and target it was logged with. it's rate limited along with everything else
+    // from the bridge, since they all share its callsite.
+    #[cfg(feature = "log")]
+    fn export_log_record(&self, event: &tracing::Event<'_>, metadata: &tracing::Metadata<'_>) {
+        if !self.filter.enabled(metadata) || !self.allowed(event.metadata()) {
+            return;
+        }
+        let mut event_visitor = EventStorage::recording(&self.record_options);
+        self.metadata_fields.record(metadata, &mut event_visitor.0);
+        event.record(&mut event_visitor);
+        log_bridge::remove_log_fields(&mut event_visitor.0);
+        self.exporter.export(
+            event_visitor.0,
+            self.exporter.clock.system_time(),
+            metadata.level(),
+        );
+    }
+
+    // the `span`, `parent_span` and `spans` entries, where each span is just its name and the
+    // fields it recorded itself
+    fn record_hierarchy<S>(&self, span: &SpanRef<'_, S>, fields: &mut EventHash)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn summarize<S>(span: &SpanRef<'_, S>) -> serde_json::Value
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+        {
+            let mut summary = serde_json::Map::new();
+            summary.insert("name".to_string(), span.name().into());
+            if let Some(own) = span.extensions().get::<SpanFields>() {
+                for (k, v) in own.0.events() {
+                    if !hec::is_routing_field(k) {
+                        summary.insert(k.to_string(), v.clone());
+                    }
+                }
+            }
+            serde_json::Value::Object(summary)
+        }
+
+        let spans: Vec<serde_json::Value> = span
+            .scope()
+            .from_root()
+            .filter(|s| s.extensions().get::<FilteredOut>().is_none())
+            .map(|s| summarize(&s))
+            .collect();
+        fields.insert("span".into(), spans.last().cloned().unwrap_or_default());
+        if let Some(parent) = nearest_recorded(span.scope().skip(1)) {
+            fields.insert("parent_span".into(), summarize(&parent));
+        }
+        fields.insert("spans".into(), serde_json::Value::Array(spans));
+    }
+
+    // send what a span has gathered so far as an event of its own, if it's been `interval` since
+    // the last time (or whenever, without one), so something is seen of a connection handler or
+    // the like before it finally closes. the checkpoint has everything the span would be exported
+    // with, as it stands now, and a `checkpoint` count. its `events` list goes with it, and starts
+    // over, so that doesn't grow for as long as the span is open.
+    fn checkpoint<S>(&self, span: &SpanRef<'_, S>, interval: Option<Duration>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let now = self.exporter.clock.now();
+        let (mut fields, elapsed, events, ids, sent) = {
+            let mut extensions = span.extensions_mut();
+            if extensions.get_mut::<SpanCheckpoint>().is_none() {
+                if interval.is_some() {
+                    return;
+                }
+                extensions.insert(SpanCheckpoint { last: now, sent: 0 });
+            }
+            let Some(checkpoint) = extensions.get_mut::<SpanCheckpoint>() else {
+                return;
+            };
+            match interval {
+                Some(interval) if now.saturating_duration_since(checkpoint.last) < interval => {
+                    return
+                }
+                _ => {}
+            }
+            // before anything is taken off the span, so a checkpoint that's rate limited leaves
+            // its events for the next one
+            if !self.allowed(span.metadata()) {
+                return;
+            }
+            checkpoint.last = now;
+            checkpoint.sent += 1;
+            let sent = checkpoint.sent;
+            let Some(storage) = extensions.get_mut::<EventStorage>() else {
+                return;
+            };
+            let fields = storage.0.clone();
+            let elapsed = extensions
+                .get_mut::<SpanTimings>()
+                .map(|timings| timings.elapsed(now))
+                .unwrap_or_default();
+            let events = extensions.remove::<SpanEvents>();
+            let ids = extensions.get_mut::<SpanIds>().copied();
+            (fields, elapsed, events, ids, sent)
+        };
+        self.inherit(span.scope().skip(1), &mut fields, self.field_inheritance);
+        if let Some(ids) = ids {
+            fields.insert("trace_id".into(), ids.trace_id_hex().into());
+            fields.insert("span_id".into(), ids.span_id_hex().into());
+        }
+        fields.insert(
+            intern(&self.elapsed_time.field),
+            self.elapsed_time.value(elapsed),
+        );
+        fields.insert("checkpoint".into(), sent.into());
+        if let Some(events) = events {
+            events.record(&mut fields);
+        }
+        self.exporter.export(
+            fields,
+            self.exporter.clock.system_time(),
+            span.metadata().level(),
+        );
+    }
+
+    // add a closed span to its parent's `children` rather than exporting it, with its own time
+    // since it won't have an envelope of its own. where the tree is routed is up to the root, so
+    // any routing fields it set are dropped, and an error anywhere in the tree counts towards
+    // keeping the root when it's tail sampled.
+    fn roll_up<S>(
+        &self,
+        parent: &SpanRef<'_, S>,
+        mut fields: EventHash,
+        created_at: SystemTime,
+        saw_error: bool,
+    ) where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fields.retain(|name, _| !hec::is_routing_field(name));
+        if let Some(time) = HecTime::new(created_at, self.exporter.timestamp_precision) {
+            match serde_json::to_value(time) {
+                Ok(time) => fields.insert("time".into(), time),
+                Err(e) => return self.errors.handle(LayerError::Serialize(e)),
+            };
+        }
+        let child = match serde_json::to_value(fields) {
+            Ok(child) => child,
+            Err(e) => return self.errors.handle(LayerError::Serialize(e)),
+        };
+
+        let mut extensions = parent.extensions_mut();
+        if saw_error && extensions.get_mut::<SawError>().is_none() {
+            extensions.insert(SawError);
+        }
+        match extensions.get_mut::<SpanChildren>() {
+            Some(children) => children.0.push(child),
+            None => extensions.insert(SpanChildren(vec![child])),
+        }
+    }
+
+    // fill in what a closing span didn't set itself from the spans above it. this is done as the
+    // span closes, while its parents are all still open, rather than copying their fields into
+    // every child when it's made, so a deep tree doesn't keep a copy of its ancestors' fields at
+    // every level.
+    //
+    // `ancestors` is the span's scope above it, or for an event exported on its own, the whole
+    // scope it happened in.
+    fn inherit<'a, S>(
+        &self,
+        ancestors: impl Iterator<Item = SpanRef<'a, S>>,
+        fields: &mut EventHash,
+        inheritance: FieldInheritance,
+    ) where
+        S: Subscriber + for<'l> LookupSpan<'l>,
+    {
+        let ancestors = ancestors.filter(|s| s.extensions().get::<FilteredOut>().is_none());
+        for (depth, ancestor) in ancestors.enumerate() {
+            let everything = match inheritance {
+                FieldInheritance::None => false,
+                FieldInheritance::Parent => depth == 0,
+                FieldInheritance::Full => true,
+            };
+            let extensions = ancestor.extensions();
+            let Some(storage) = extensions.get::<EventStorage>() else {
+                continue;
+            };
+            for (name, value) in storage.events() {
+                // every span has its own level, target and so on, those never collide
+                if hec::is_routing_field(name) || self.metadata_fields.contains(name) {
+                    if !fields.contains_key(name) {
+                        fields.insert(name.clone(), value.clone());
+                    }
+                } else if everything {
+                    self.field_collision
+                        .inherit(fields, ancestor.name(), name.clone(), value);
+                }
+            }
+        }
+    }
+
+    // swap the ids we made up for a span for the ones tracing-opentelemetry gave it, so splunk
+    // events line up with the traces sent to an OTLP backend. tracing-opentelemetry only settles
+    // on a span's ids once it's started (a parent can still be set on it until then), so we ask
+    // the first time the span is entered, or when it closes if it never was.
+    #[cfg(feature = "opentelemetry")]
+    fn adopt_otel_ids<S>(&self, span: &SpanRef<'_, S>)
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        {
+            let extensions = span.extensions();
+            if extensions.get::<SpanIds>().is_none()
+                || extensions.get::<otel::OtelChecked>().is_some()
+            {
+                return;
+            }
+        }
+        let ids = self.otel.ids(&span.id());
+        let mut extensions = span.extensions_mut();
+        extensions.insert(otel::OtelChecked);
+        if let (Some(ids), Some(own)) = (ids, extensions.get_mut::<SpanIds>()) {
+            *own = SpanIds {
+                remote_parent: own.remote_parent,
+                ..ids
+            };
+        }
+    }
+}
 
-// TODO: track event and span metadata
-// TODO: handle events not associated with a span
-// TODO: ship a span to splunk once its been closed
 impl<S> Layer<S> for SplunkHecLayer
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
+    #[cfg(feature = "opentelemetry")]
+    fn on_register_dispatch(&self, subscriber: &tracing::Dispatch) {
+        self.otel.register(subscriber);
+    }
+
     // on entering a new span we need to
     fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-
-        // create a new visitor that inherits the parent's fields or gives us a fresh new visitor
-        let mut event_visitor = if let Some(parent) = span.parent() {
-            let mut extensions = parent.extensions_mut();
-            extensions
-                .get_mut::<EventStorage>()
-                .map(|c| c.to_owned())
-                .unwrap_or_default()
-        } else {
-            EventStorage::new()
+        let Some(span) = self.span(id, &ctx) else {
+            return;
+        };
+        // spans made by the layer's own machinery are as good as filtered out
+        let Some(_internal) = internal::enter() else {
+            span.extensions_mut().insert(FilteredOut);
+            return;
+        };
+        if !self.filter.enabled(attrs.metadata()) {
+            span.extensions_mut().insert(FilteredOut);
+            return;
+        }
+        let parent = nearest_recorded(span.scope().skip(1));
+
+        // the whole trace is either in or out, decided once at the root
+        let sampled = match &parent {
+            Some(parent) => parent.extensions().get::<NotSampled>().is_none(),
+            None => head_sample(self.head_sample_ratio.get()),
         };
+        if !sampled {
+            span.extensions_mut().insert(NotSampled);
+            return;
+        }
+        let mut ids = parent
+            .as_ref()
+            .and_then(|parent| parent.extensions().get::<SpanIds>().map(|p| p.child(id)))
+            .unwrap_or_else(|| SpanIds::root(id));
+        let mut traceparent = FindTraceParent::default();
+        attrs.record(&mut traceparent);
+        if let Some(traceparent) = traceparent.0 {
+            ids.continue_from(&traceparent);
+        }
+
+        // only the span's own fields are kept here, whatever it inherits is filled in when it
+        // closes, see inherit
+        let mut event_visitor = EventStorage::recording(&self.record_options);
 
         // visit and record fields
+        self.metadata_fields
+            .record(attrs.metadata(), &mut event_visitor.0);
         attrs.record(&mut event_visitor);
 
         // tracing_subscriber provides extensions on our spans so we can store span data
         // which the tracing library wont do.
         let mut extensions = span.extensions_mut();
+        if self.span_hierarchy {
+            let mut own_fields = SpanFields(EventStorage::recording(&self.record_options));
+            attrs.record(&mut own_fields.0);
+            extensions.insert(own_fields);
+        }
         // store the fields
         extensions.insert::<EventStorage>(event_visitor);
+        extensions.insert(SpanTimestamp(self.exporter.clock.system_time()));
+        extensions.insert(SpanTimings::start(self.exporter.clock.now()));
+        if self.checkpoint_interval.is_some() {
+            extensions.insert(SpanCheckpoint {
+                last: self.exporter.clock.now(),
+                sent: 0,
+            });
+        }
+        extensions.insert(ids);
     }
 
     fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
-        let span = ctx.lookup_current();
+        let Some(_internal) = internal::enter() else {
+            return;
+        };
+        // before the filter, which has to see the record's own target and level rather than the
+        // bridge's
+        #[cfg(feature = "log")]
+        if let Some(metadata) = log_bridge::log_metadata(event) {
+            return self.export_log_record(event, &metadata);
+        }
+        if !self.filter.enabled(event.metadata()) {
+            return;
+        }
+
+        let tracks_errors = self.tail_sampler.is_enabled() || self.aggregator.is_some();
+        if tracks_errors && *event.metadata().level() == tracing::Level::ERROR {
+            // an error anywhere down the tree is reason enough to keep every span above it, and
+            // counts against each of them in the summaries
+            for span in ctx.event_scope(event).into_iter().flatten() {
+                let mut extensions = span.extensions_mut();
+                if extensions.get_mut::<SawError>().is_none() {
+                    extensions.insert(SawError);
+                }
+            }
+        }
+
+        let span = ctx.event_scope(event).and_then(nearest_recorded);
         if let Some(span) = &span {
             let mut extensions = span.extensions_mut();
-            let event_visitor = extensions.get_mut::<EventStorage>().unwrap();
-            event.record(event_visitor);
+            if extensions.get_mut::<NotSampled>().is_some() {
+                return;
+            }
+            match self.span_event_mode {
+                SpanEventMode::Merge => match extensions.get_mut::<EventStorage>() {
+                    Some(event_visitor) if self.field_collision == FieldCollision::Overwrite => {
+                        event.record(event_visitor)
+                    }
+                    Some(event_visitor) => {
+                        let mut fields = EventStorage::recording(&self.record_options);
+                        event.record(&mut fields);
+                        for (name, value) in fields.0 {
+                            self.field_collision.record(
+                                &mut event_visitor.0,
+                                span.name(),
+                                name,
+                                value,
+                            );
+                        }
+                    }
+                    None => self.missing_span_data(span),
+                },
+                SpanEventMode::List => {
+                    let mut fields = self.record_event(event);
+                    let now = self.exporter.clock.system_time();
+                    if let Some(time) = HecTime::new(now, self.exporter.timestamp_precision) {
+                        match serde_json::to_value(time) {
+                            Ok(time) => fields.insert("time".into(), time),
+                            Err(e) => return self.errors.handle(LayerError::Serialize(e)),
+                        };
+                    }
+                    let fields = match serde_json::to_value(fields) {
+                        Ok(fields) => fields,
+                        Err(e) => return self.errors.handle(LayerError::Serialize(e)),
+                    };
+
+                    let limit = self.span_event_limit.as_ref();
+                    if extensions.get_mut::<SpanEvents>().is_none() {
+                        extensions.insert(SpanEvents::default());
+                    }
+                    let Some(events) = extensions.get_mut::<SpanEvents>() else {
+                        return;
+                    };
+                    // the span's full, and the limit says to send what it has to make room
+                    if let Some(fields) = events.push(fields, limit) {
+                        drop(extensions);
+                        // which takes the list along with it, this starts the next one
+                        self.checkpoint(span, None);
+                        let mut extensions = span.extensions_mut();
+                        if extensions.get_mut::<SpanEvents>().is_none() {
+                            extensions.insert(SpanEvents::default());
+                        }
+                        if let Some(events) = extensions.get_mut::<SpanEvents>() {
+                            if events.push(fields, limit).is_some() {
+                                events.drop_event();
+                            }
+                        }
+                    }
+                }
+                SpanEventMode::Export => {
+                    let ids = extensions.get_mut::<SpanIds>().copied();
+                    // inherit looks at the span's extensions too
+                    drop(extensions);
+                    if !self.allowed(event.metadata()) {
+                        return;
+                    }
+                    let mut fields = self.record_event(event);
+                    self.inherit(span.scope(), &mut fields, self.field_inheritance);
+                    if let Some(ids) = ids {
+                        fields.insert("trace_id".into(), ids.trace_id_hex().into());
+                        fields.insert("span_id".into(), ids.span_id_hex().into());
+                    }
+                    self.exporter.export(
+                        fields,
+                        self.exporter.clock.system_time(),
+                        event.metadata().level(),
+                    );
+                }
+            }
         } else {
-            tracing::debug!("uh oh, this event doesn't have an associated span!")
+            // there's no span to accumulate into, so top level events get shipped on their own
+            if !self.allowed(event.metadata()) {
+                return;
+            }
+            self.exporter.export(
+                self.record_event(event),
+                self.exporter.clock.system_time(),
+                event.metadata().level(),
+            );
         };
     }
 
     // allows us to update spans even after they are created
     fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
+        let Some(_internal) = internal::enter() else {
+            return;
+        };
+        let Some(span) = self.span(id, &ctx) else {
+            return;
+        };
         let mut extensions = span.extensions_mut();
-        let event_visitor = extensions.get_mut::<EventStorage>().unwrap();
+        if extensions.get_mut::<NotSampled>().is_some()
+            || extensions.get_mut::<FilteredOut>().is_some()
+        {
+            return;
+        }
+        let Some(event_visitor) = extensions.get_mut::<EventStorage>() else {
+            return self.missing_span_data(&span);
+        };
         values.record(event_visitor);
+        if let Some(own_fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut own_fields.0);
+        }
+        let mut traceparent = FindTraceParent::default();
+        values.record(&mut traceparent);
+        if let (Some(traceparent), Some(ids)) = (traceparent.0, extensions.get_mut::<SpanIds>()) {
+            ids.continue_from(&traceparent);
+        }
+    }
+
+    fn on_follows_from(&self, id: &span::Id, follows: &span::Id, ctx: Context<'_, S>) {
+        let Some(_internal) = internal::enter() else {
+            return;
+        };
+        let Some(span) = self.span(id, &ctx) else {
+            return;
+        };
+        // the span it follows from may well have closed already, there's nothing left to link to
+        let Some(follows) = ctx.span(follows) else {
+            return;
+        };
+        #[cfg(feature = "opentelemetry")]
+        self.adopt_otel_ids(&follows);
+        let Some(link) = follows.extensions().get::<SpanIds>().copied() else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        match extensions.get_mut::<SpanLinks>() {
+            Some(links) => links.0.push(link),
+            None => extensions.insert(SpanLinks(vec![link])),
+        }
     }
 
     fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
+        let Some(_internal) = internal::enter() else {
+            return;
+        };
+        let Some(span) = self.span(id, &ctx) else {
+            return;
+        };
+        #[cfg(feature = "opentelemetry")]
+        self.adopt_otel_ids(&span);
         let mut extensions = span.extensions_mut();
+        if let Some(timings) = extensions.get_mut::<SpanTimings>() {
+            timings.enter(self.exporter.clock.now());
+        }
+    }
 
-        // if you're entering a span for the first time then insert your the isntant otherwise dont
-        // otherwise you won't find anything with the type Instant
-        if extensions.get_mut::<Instant>().is_none() {
-            extensions.insert(Instant::now());
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(_internal) = internal::enter() else {
+            return;
+        };
+        let Some(span) = self.span(id, &ctx) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(timings) = extensions.get_mut::<SpanTimings>() {
+            timings.exit(self.exporter.clock.now());
+        }
+        drop(extensions);
+        if let Some(interval) = self.checkpoint_interval {
+            self.checkpoint(&span, Some(interval));
         }
     }
 
     fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
-        let span = ctx.span(&id).unwrap();
+        let Some(_internal) = internal::enter() else {
+            return;
+        };
+        let Some(span) = self.span(&id, &ctx) else {
+            return;
+        };
+        if span.extensions().get::<FilteredOut>().is_some() {
+            return;
+        }
+        if span.extensions().get::<NotSampled>().is_some() {
+            self.counters.span_sampled_out();
+            return;
+        }
 
-        // u128 values aren't supported or something. luckily u64 is plenty precise for things like
-        // web applications that operate at or above mili/micro second timescales.
-        // this is also a convenient way to get the elapsed time and allow the extensions to drop
-        // out of scope so we can get them later.
-        let elapsed_time: u64 = {
-            let extensions = span.extensions();
-            extensions
-                .get::<Instant>()
-                .map(|t| t.elapsed().as_millis())
-                // this should prevent us from failing
-                .unwrap_or(0)
-                .try_into()
-                .unwrap()
+        let parent = nearest_recorded(span.scope().skip(1));
+        #[cfg(feature = "opentelemetry")]
+        {
+            self.adopt_otel_ids(&span);
+            if let Some(parent) = &parent {
+                self.adopt_otel_ids(parent);
+            }
+        }
+        let parent_ids = parent
+            .as_ref()
+            .and_then(|p| p.extensions().get::<SpanIds>().copied());
+        // with the span tree, only the root is exported and everything else is rolled up into it
+        let roll_up_into = parent.filter(|_| self.span_export_mode == SpanExportMode::Tree);
+
+        // the span is going away so we can take its fields rather than copying them
+        let (mut event_fields, created_at, events, children, saw_error, timings, ids) = {
+            let mut extensions = span.extensions_mut();
+            let ids = extensions.remove::<SpanIds>();
+            let timings = extensions.remove::<SpanTimings>();
+            let saw_error = extensions.remove::<SawError>().is_some();
+            let created_at = extensions
+                .remove::<SpanTimestamp>()
+                .map(|t| t.0)
+                .unwrap_or_else(|| self.exporter.clock.system_time());
+            let events = extensions.remove::<SpanEvents>();
+            let children = extensions.remove::<SpanChildren>();
+            let Some(event_fields) = extensions.remove::<EventStorage>() else {
+                drop(extensions);
+                return self.missing_span_data(&span);
+            };
+            (
+                event_fields,
+                created_at,
+                events,
+                children,
+                saw_error,
+                timings,
+                ids,
+            )
         };
+        // a child in the tree already sits inside the spans it would inherit from
+        if roll_up_into.is_none() {
+            let inheritance = match self.span_export_mode {
+                SpanExportMode::References => FieldInheritance::None,
+                _ => self.field_inheritance,
+            };
+            self.inherit(span.scope().skip(1), &mut event_fields.0, inheritance);
+        }
+        let times = timings
+            .map(|timings| timings.close(self.exporter.clock.now()))
+            .unwrap_or_default();
+        if let Some(aggregator) = &self.aggregator {
+            if aggregator.aggregates(span.name()) {
+                aggregator.record(span.name(), times.elapsed, saw_error);
+                if aggregator.exclude_from_export() {
+                    return;
+                }
+            }
+        }
+        if let Some(metrics) = &self.span_metrics {
+            let fields = metrics.fields(span.name(), &event_fields.0, &times, &self.elapsed_time);
+            self.exporter
+                .export_metric(fields, created_at, metrics.index.as_ref());
+            if !metrics.span_events {
+                return;
+            }
+        }
+        let fields = &mut event_fields.0;
+        if let Some(ids) = ids {
+            fields.insert("trace_id".into(), ids.trace_id_hex().into());
+            fields.insert("span_id".into(), ids.span_id_hex().into());
+            if let Some(remote) = ids.remote_parent {
+                fields.insert("parent_span_id".into(), format!("{:016x}", remote).into());
+            }
+        }
+        if let Some(links) = span.extensions_mut().remove::<SpanLinks>() {
+            let links = links
+                .0
+                .iter()
+                .map(|link| {
+                    serde_json::json!({
+                        "trace_id": link.trace_id_hex(),
+                        "span_id": link.span_id_hex(),
+                    })
+                })
+                .collect();
+            fields.insert("links".into(), serde_json::Value::Array(links));
+        }
+        if let Some(parent) = parent_ids {
+            // with references that's the only thing pointing at the parent, so it goes under the
+            // same name a remote parent does
+            let name = match self.span_export_mode {
+                SpanExportMode::References => "parent_span_id",
+                _ => "parent_id",
+            };
+            fields.insert(name.into(), parent.span_id_hex().into());
+        }
+        fields.insert(
+            intern(&self.elapsed_time.field),
+            self.elapsed_time.value(times.elapsed),
+        );
+        fields.insert("busy_time".into(), self.elapsed_time.value(times.busy));
+        fields.insert("idle_time".into(), self.elapsed_time.value(times.idle));
+        if let Some(waited) = times.time_to_first_enter {
+            fields.insert(
+                "time_to_first_enter".into(),
+                self.elapsed_time.value(waited),
+            );
+        }
+        if let Some(events) = events {
+            events.record(&mut event_fields.0);
+        }
+        if let Some(children) = children {
+            event_fields
+                .0
+                .insert("children".into(), serde_json::Value::Array(children.0));
+        }
+        if let Some(parent) = roll_up_into {
+            return self.roll_up(&parent, event_fields.0, created_at, saw_error);
+        }
+        if self.span_hierarchy {
+            self.record_hierarchy(&span, &mut event_fields.0);
+        }
 
-        let mut extensions = span.extensions_mut();
-        let event_fields = extensions.get_mut::<EventStorage>().unwrap();
-        event_fields
-            .0
-            .insert("elapsed_time", serde_json::to_value(elapsed_time).unwrap());
-        println!("{}", serde_json::to_string_pretty(&event_fields).unwrap());
+        let sample = TailSample {
+            name: span.name(),
+            fields: &event_fields.0,
+            saw_error,
+        };
+        if !self.tail_sampler.keep(&sample) {
+            self.counters.span_suppressed();
+            return;
+        }
+
+        if !self.allowed(span.metadata()) {
+            return;
+        }
+        self.exporter
+            .export(event_fields.0, created_at, span.metadata().level());
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn dynamic_field_names_are_owned() {
+        let mut storage = EventStorage::new();
+        storage.insert("static", 1);
+        storage.insert(format!("request.{}", "id"), "abc");
+
+        assert!(matches!(
+            storage.events().get_key_value("static"),
+            Some((Cow::Borrowed(_), _))
+        ));
+        assert_eq!(storage.events()["request.id"], "abc");
+    }
 }
@@ -0,0 +1,38 @@
+use tracing::Metadata;
+use tracing_log::NormalizeEvent;
+
+pub use tracing_log::log::SetLoggerError;
+
+use crate::EventHash;
+
+// send records from the `log` crate through tracing, and so to HEC, for dependencies that still
+// log that way. call it once at startup, it fails if something else has installed a logger:
+//
+//   tracing_splunk_layer::init_log_bridge()?;
+//
+// tracing-subscriber's init() and set_default() install the same bridge when its `tracing-log`
+// feature is on, as it is by default, this is for when they aren't used or it's off.
+//
+// a log record has no spans, so it's exported as an event of its own even if it happened inside
+// one, with the record's own level and target rather than the bridge's.
+pub fn init_log_bridge() -> Result<(), SetLoggerError> {
+    tracing_log::LogTracer::init()
+}
+
+// the metadata a `log` record came with, for an event that came through the bridge
+pub(crate) fn log_metadata<'a>(event: &'a tracing::Event<'a>) -> Option<Metadata<'a>> {
+    event.normalized_metadata()
+}
+
+// whether `metadata` is the bridge's callsite, which every record of that level shares whatever
+// target it's from
+pub(crate) fn is_log_callsite(metadata: &Metadata<'_>) -> bool {
+    metadata.fields().field("log.target").is_some()
+}
+
+// the fields the bridge adds to carry the record's metadata, which is recorded the usual way
+pub(crate) fn remove_log_fields(fields: &mut EventHash) {
+    for name in ["log.target", "log.module_path", "log.file", "log.line"] {
+        fields.remove(name);
+    }
+}
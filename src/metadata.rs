@@ -0,0 +1,64 @@
+use tracing::Metadata;
+
+use crate::EventHash;
+
+// which bits of tracing's own metadata get recorded alongside the user's fields. these are
+// recorded before the user's fields, so a user field with the same name wins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct MetadataFields {
+    pub(crate) level: bool,
+    pub(crate) target: bool,
+    pub(crate) name: bool,
+    pub(crate) module_path: bool,
+    pub(crate) file: bool,
+    pub(crate) line: bool,
+}
+
+impl Default for MetadataFields {
+    fn default() -> Self {
+        MetadataFields {
+            level: true,
+            target: true,
+            name: true,
+            module_path: false,
+            file: false,
+            line: false,
+        }
+    }
+}
+
+impl MetadataFields {
+    // whether `name` is one of the fields record() sets on every span
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        match name {
+            "level" => self.level,
+            "target" => self.target,
+            "name" => self.name,
+            "module_path" => self.module_path,
+            "file" => self.file,
+            "line" => self.line,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn record(&self, metadata: &Metadata<'_>, fields: &mut EventHash) {
+        if self.level {
+            fields.insert("level".into(), metadata.level().as_str().into());
+        }
+        if self.target {
+            fields.insert("target".into(), metadata.target().into());
+        }
+        if self.name {
+            fields.insert("name".into(), metadata.name().into());
+        }
+        if let (true, Some(module_path)) = (self.module_path, metadata.module_path()) {
+            fields.insert("module_path".into(), module_path.into());
+        }
+        if let (true, Some(file)) = (self.file, metadata.file()) {
+            fields.insert("file".into(), file.into());
+        }
+        if let (true, Some(line)) = (self.line, metadata.line()) {
+            fields.insert("line".into(), line.into());
+        }
+    }
+}
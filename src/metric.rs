@@ -0,0 +1,82 @@
+use crate::intern::intern_fmt;
+use crate::time::{ElapsedTime, SpanTimes};
+use crate::EventHash;
+
+// numbers about every span, sent to a metrics index in HEC's multiple metric format so latency
+// can be charted with mstats instead of worked out at search time. each closed span is one
+// metric event with
+//
+//   metric_name:span.duration, span.busy_time, span.idle_time   in the elapsed time's unit
+//   metric_name:span.count                                      always 1, sum it for a count
+//   metric_name:span.<field>                                    its numeric fields
+//
+// and `span.name`, the global fields and any `dimensions` as dimensions. spans that are head
+// sampled out aren't counted, tail sampling only decides about the span's event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanMetrics {
+    // has to be a metrics index, HEC won't put metrics in an event one
+    pub index: Option<String>,
+    // what every metric name starts with
+    pub prefix: String,
+    // whether the span's numeric fields are metrics too
+    pub numeric_fields: bool,
+    // string fields of the span to keep as dimensions
+    pub dimensions: Vec<String>,
+    // send the span as an event as well as its metrics
+    pub span_events: bool,
+}
+
+impl Default for SpanMetrics {
+    fn default() -> Self {
+        SpanMetrics {
+            index: None,
+            prefix: "span".to_string(),
+            numeric_fields: true,
+            dimensions: Vec::new(),
+            span_events: true,
+        }
+    }
+}
+
+impl SpanMetrics {
+    // HEC's `fields` for a span called `name` with these fields that took `times`
+    pub(crate) fn fields(
+        &self,
+        name: &'static str,
+        fields: &EventHash,
+        times: &SpanTimes,
+        elapsed: &ElapsedTime,
+    ) -> EventHash {
+        let mut metrics = EventHash::new();
+        let mut metric = |name: &str, value: serde_json::Value| {
+            metrics.insert(
+                intern_fmt(format_args!("metric_name:{}.{}", self.prefix, name)),
+                value,
+            );
+        };
+        metric("duration", elapsed.value(times.elapsed));
+        metric("busy_time", elapsed.value(times.busy));
+        metric("idle_time", elapsed.value(times.idle));
+        metric("count", 1.into());
+        if self.numeric_fields {
+            for (field, value) in fields {
+                if value.is_number() {
+                    metric(field, value.clone());
+                }
+            }
+        }
+
+        metrics.insert(
+            intern_fmt(format_args!("{}.name", self.prefix)),
+            name.into(),
+        );
+        for dimension in &self.dimensions {
+            if let Some((key, value @ serde_json::Value::String(_))) =
+                fields.get_key_value(dimension.as_str())
+            {
+                metrics.insert(key.clone(), value.clone());
+            }
+        }
+        metrics
+    }
+}
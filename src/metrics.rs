@@ -0,0 +1,217 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::circuit::CircuitState;
+
+// why an event never made it to splunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DropReason {
+    // the worker's queue was full, see QueueFullPolicy
+    QueueFull,
+    // the event couldn't be serialized
+    Serialize,
+    // delivery failed for good, after any retries and without a spool to fall back on
+    ExportFailed,
+    // HEC took the event but never acknowledged indexing it
+    Unacknowledged,
+    // it was over the rate limit, see RateLimitConfig
+    RateLimited,
+    // it was too big to send, see OversizedEvent
+    Oversized,
+    // the circuit breaker was open and there was no spool to keep it in, see CircuitBreakerConfig
+    CircuitOpen,
+    // the queue was past its high-water mark and the event wasn't severe enough to keep, see
+    // LoadShedding
+    Shed,
+    // it came after WorkerGuard::shutdown, which stops taking new events
+    Closed,
+}
+
+// counters shared between the layer, the worker and whoever is holding the guard
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    spans_suppressed: AtomicU64,
+    spans_sampled_out: AtomicU64,
+    events_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    batches_sent: AtomicU64,
+    retries: AtomicU64,
+    fallback_events: AtomicU64,
+    dropped_queue_full: AtomicU64,
+    dropped_serialize: AtomicU64,
+    dropped_export_failed: AtomicU64,
+    dropped_unacknowledged: AtomicU64,
+    dropped_rate_limited: AtomicU64,
+    dropped_oversized: AtomicU64,
+    dropped_circuit_open: AtomicU64,
+    dropped_shed: AtomicU64,
+    dropped_closed: AtomicU64,
+    queue_depth: AtomicU64,
+    circuit_state: AtomicU8,
+    circuit_opened: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn span_suppressed(&self) {
+        self.spans_suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn spans_suppressed(&self) -> u64 {
+        self.spans_suppressed.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn span_sampled_out(&self) {
+        self.spans_sampled_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn spans_sampled_out(&self) -> u64 {
+        self.spans_sampled_out.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn batch_sent(&self, events: usize, bytes: usize) {
+        self.events_sent.fetch_add(events as u64, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.batches_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn retried(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn fell_back(&self, events: usize) {
+        self.fallback_events
+            .fetch_add(events as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dropped(&self, reason: DropReason, events: usize) {
+        let counter = match reason {
+            DropReason::QueueFull => &self.dropped_queue_full,
+            DropReason::Serialize => &self.dropped_serialize,
+            DropReason::ExportFailed => &self.dropped_export_failed,
+            DropReason::Unacknowledged => &self.dropped_unacknowledged,
+            DropReason::RateLimited => &self.dropped_rate_limited,
+            DropReason::Oversized => &self.dropped_oversized,
+            DropReason::CircuitOpen => &self.dropped_circuit_open,
+            DropReason::Shed => &self.dropped_shed,
+            DropReason::Closed => &self.dropped_closed,
+        };
+        counter.fetch_add(events as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn enqueued(&self) {
+        self.queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dequeued(&self) {
+        self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn queue_depth(&self) -> u64 {
+        self.queue_depth.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn circuit(&self, state: CircuitState) {
+        let previous = self.circuit_state.swap(state.to_u8(), Ordering::Relaxed);
+        if state == CircuitState::Open && previous != state.to_u8() {
+            self.circuit_opened.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// a cheap, cloneable view of how the layer is doing, to poll from a health check or report to
+// whatever metrics system you already have. get one from WorkerGuard::metrics.
+#[derive(Clone, Debug)]
+pub struct LayerMetrics {
+    counters: Arc<Counters>,
+}
+
+// the numbers at the moment LayerMetrics::snapshot was called. everything but queue_depth only
+// ever goes up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub events_sent: u64,
+    pub bytes_sent: u64,
+    pub batches_sent: u64,
+    // how many times a batch was sent again because the last try failed
+    pub retries: u64,
+    // events written to the fallback sink instead of splunk, see FallbackSink
+    pub fallback_events: u64,
+    pub dropped_queue_full: u64,
+    pub dropped_serialize: u64,
+    pub dropped_export_failed: u64,
+    pub dropped_unacknowledged: u64,
+    pub dropped_rate_limited: u64,
+    pub dropped_oversized: u64,
+    pub dropped_circuit_open: u64,
+    pub dropped_shed: u64,
+    pub dropped_closed: u64,
+    // events waiting on the worker right now
+    pub queue_depth: u64,
+    pub spans_suppressed: u64,
+    pub spans_sampled_out: u64,
+    // always Closed without a circuit breaker
+    pub circuit: CircuitState,
+    // how many times the circuit breaker has opened
+    pub circuit_opened: u64,
+}
+
+impl MetricsSnapshot {
+    pub fn dropped(&self, reason: DropReason) -> u64 {
+        match reason {
+            DropReason::QueueFull => self.dropped_queue_full,
+            DropReason::Serialize => self.dropped_serialize,
+            DropReason::ExportFailed => self.dropped_export_failed,
+            DropReason::Unacknowledged => self.dropped_unacknowledged,
+            DropReason::RateLimited => self.dropped_rate_limited,
+            DropReason::Oversized => self.dropped_oversized,
+            DropReason::CircuitOpen => self.dropped_circuit_open,
+            DropReason::Shed => self.dropped_shed,
+            DropReason::Closed => self.dropped_closed,
+        }
+    }
+
+    // every event that was dropped, whatever the reason
+    pub fn dropped_total(&self) -> u64 {
+        self.dropped_queue_full
+            + self.dropped_serialize
+            + self.dropped_export_failed
+            + self.dropped_unacknowledged
+            + self.dropped_rate_limited
+            + self.dropped_oversized
+            + self.dropped_circuit_open
+            + self.dropped_shed
+            + self.dropped_closed
+    }
+}
+
+impl LayerMetrics {
+    pub(crate) fn new(counters: Arc<Counters>) -> Self {
+        LayerMetrics { counters }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let c = &self.counters;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MetricsSnapshot {
+            events_sent: load(&c.events_sent),
+            bytes_sent: load(&c.bytes_sent),
+            batches_sent: load(&c.batches_sent),
+            retries: load(&c.retries),
+            fallback_events: load(&c.fallback_events),
+            dropped_queue_full: load(&c.dropped_queue_full),
+            dropped_serialize: load(&c.dropped_serialize),
+            dropped_export_failed: load(&c.dropped_export_failed),
+            dropped_unacknowledged: load(&c.dropped_unacknowledged),
+            dropped_rate_limited: load(&c.dropped_rate_limited),
+            dropped_oversized: load(&c.dropped_oversized),
+            dropped_circuit_open: load(&c.dropped_circuit_open),
+            dropped_shed: load(&c.dropped_shed),
+            dropped_closed: load(&c.dropped_closed),
+            queue_depth: load(&c.queue_depth),
+            spans_suppressed: load(&c.spans_suppressed),
+            spans_sampled_out: load(&c.spans_sampled_out),
+            circuit: CircuitState::from_u8(c.circuit_state.load(Ordering::Relaxed)),
+            circuit_opened: load(&c.circuit_opened),
+        }
+    }
+}
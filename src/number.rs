@@ -0,0 +1,19 @@
+// json numbers stop at 64 bits as far as serde_json is concerned (and as far as splunk is too, it
+// parses them as doubles), so 128 bit integers are only numbers when they fit. past that they're
+// decimal strings, which at least keep every digit.
+pub(crate) fn from_u128(value: u128) -> serde_json::Value {
+    match u64::try_from(value) {
+        Ok(value) => value.into(),
+        Err(_) => value.to_string().into(),
+    }
+}
+
+pub(crate) fn from_i128(value: i128) -> serde_json::Value {
+    if let Ok(value) = u64::try_from(value) {
+        return value.into();
+    }
+    match i64::try_from(value) {
+        Ok(value) => value.into(),
+        Err(_) => value.to_string().into(),
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::OnceLock;
+
+use opentelemetry::trace::TraceContextExt;
+use tracing::dispatcher::WeakDispatch;
+use tracing::{span, Dispatch};
+
+use crate::trace::SpanIds;
+
+// the subscriber the layer was added to, for asking tracing-opentelemetry about its spans. weak,
+// since the subscriber is what owns the layer.
+#[derive(Default)]
+pub(crate) struct OtelDispatch(OnceLock<WeakDispatch>);
+
+// marks a span we've already asked tracing-opentelemetry about, whatever the answer was
+pub(crate) struct OtelChecked;
+
+impl OtelDispatch {
+    pub(crate) fn register(&self, dispatch: &Dispatch) {
+        let _ = self.0.set(dispatch.downgrade());
+    }
+
+    // the ids tracing-opentelemetry has for the span, or None if there's no OpenTelemetryLayer in
+    // the subscriber or it isn't recording the span. this locks the span's extensions, so they
+    // can't be held while calling it.
+    pub(crate) fn ids(&self, id: &span::Id) -> Option<SpanIds> {
+        let dispatch = self.0.get()?.upgrade()?;
+        let cx = tracing_opentelemetry::get_otel_context(id, &dispatch)?;
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return None;
+        }
+        Some(SpanIds {
+            trace_id: u128::from_be_bytes(span_context.trace_id().to_bytes()),
+            span_id: u64::from_be_bytes(span_context.span_id().to_bytes()),
+            remote_parent: None,
+        })
+    }
+}
@@ -0,0 +1,187 @@
+use serde_json::Value;
+
+use crate::record::EventRecord;
+
+// what happens to an event that's bigger than SplunkHecLayerBuilder::max_event_bytes once it's
+// serialized, since HEC turns away anything over its max content length
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OversizedEvent {
+    // cut the largest string values down until it fits, and mark it `truncated=true`
+    #[default]
+    Truncate,
+    // send a span's `events` list (see SpanEventMode::List) in as many parts as it takes, each
+    // with `events.part` and `events.parts`. anything that doesn't fit even then is dropped.
+    Split,
+    // drop it, which is counted as DropReason::Oversized
+    Drop,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SizeLimit {
+    pub(crate) max_bytes: usize,
+    pub(crate) policy: OversizedEvent,
+}
+
+// what was left of an event after making it fit
+pub(crate) struct Fitted {
+    pub(crate) payloads: Vec<String>,
+    pub(crate) dropped: usize,
+}
+
+impl SizeLimit {
+    // `record` encoded as one or more payloads that are all within the limit
+    pub(crate) fn fit<E>(
+        &self,
+        record: EventRecord,
+        encode: impl Fn(&EventRecord) -> Result<String, E>,
+    ) -> Result<Fitted, E> {
+        let payload = encode(&record)?;
+        if payload.len() <= self.max_bytes {
+            return Ok(Fitted {
+                payloads: vec![payload],
+                dropped: 0,
+            });
+        }
+        match self.policy {
+            OversizedEvent::Truncate => self.truncate(record, payload, &encode),
+            OversizedEvent::Split => self.split(record, &encode),
+            OversizedEvent::Drop => Ok(Fitted {
+                payloads: Vec::new(),
+                dropped: 1,
+            }),
+        }
+    }
+
+    fn truncate<E>(
+        &self,
+        mut record: EventRecord,
+        mut payload: String,
+        encode: &impl Fn(&EventRecord) -> Result<String, E>,
+    ) -> Result<Fitted, E> {
+        record.event.insert("truncated".into(), true.into());
+        while payload.len() > self.max_bytes {
+            // a byte less of string is at least a byte less of json, escaping only adds to it
+            let excess = payload.len() - self.max_bytes;
+            if !truncate_largest(&mut record, excess) {
+                // there's nothing left to cut, it's all keys and numbers
+                return Ok(Fitted {
+                    payloads: Vec::new(),
+                    dropped: 1,
+                });
+            }
+            payload = encode(&record)?;
+        }
+        Ok(Fitted {
+            payloads: vec![payload],
+            dropped: 0,
+        })
+    }
+
+    fn split<E>(
+        &self,
+        record: EventRecord,
+        encode: &impl Fn(&EventRecord) -> Result<String, E>,
+    ) -> Result<Fitted, E> {
+        let mut parts = Vec::new();
+        let mut dropped = 0;
+        self.halve(record, &mut parts, &mut dropped, encode)?;
+
+        let total = parts.len();
+        let mut payloads = Vec::with_capacity(total);
+        for (i, mut part) in parts.into_iter().enumerate() {
+            if total > 1 {
+                part.event.insert("events.part".into(), (i + 1).into());
+                part.event.insert("events.parts".into(), total.into());
+            }
+            let payload = encode(&part)?;
+            // the part numbers might just tip it over
+            if payload.len() > self.max_bytes {
+                dropped += 1;
+                continue;
+            }
+            payloads.push(payload);
+        }
+        Ok(Fitted { payloads, dropped })
+    }
+
+    fn halve<E>(
+        &self,
+        mut record: EventRecord,
+        parts: &mut Vec<EventRecord>,
+        dropped: &mut usize,
+        encode: &impl Fn(&EventRecord) -> Result<String, E>,
+    ) -> Result<(), E> {
+        if encode(&record)?.len() <= self.max_bytes {
+            parts.push(record);
+            return Ok(());
+        }
+        let events = match record.event.get_mut("events") {
+            Some(Value::Array(events)) if events.len() > 1 => std::mem::take(events),
+            _ => {
+                *dropped += 1;
+                return Ok(());
+            }
+        };
+        let mut first = events;
+        let second = first.split_off(first.len() / 2);
+        let mut other = record.clone();
+        record.event.insert("events".into(), Value::Array(first));
+        other.event.insert("events".into(), Value::Array(second));
+        self.halve(record, parts, dropped, encode)?;
+        self.halve(other, parts, dropped, encode)
+    }
+}
+
+// cut `by` bytes off the longest string in the record, or as much of it as there is. false when
+// there are no strings left to cut.
+fn truncate_largest(record: &mut EventRecord, by: usize) -> bool {
+    let longest = record
+        .event
+        .values()
+        .chain(record.fields.values())
+        .map(longest_string)
+        .chain(record.message.as_ref().map(String::len))
+        .max()
+        .unwrap_or(0);
+    if longest == 0 {
+        return false;
+    }
+
+    let cut = |s: &mut String| {
+        let mut keep = longest.saturating_sub(by);
+        while !s.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        s.truncate(keep);
+    };
+    if let Some(message) = record.message.as_mut().filter(|m| m.len() == longest) {
+        cut(message);
+        return true;
+    }
+    let values = record.event.values_mut().chain(record.fields.values_mut());
+    for value in values {
+        if let Some(s) = find_string(value, longest) {
+            cut(s);
+            return true;
+        }
+    }
+    false
+}
+
+fn longest_string(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.len(),
+        Value::Array(values) => values.iter().map(longest_string).max().unwrap_or(0),
+        Value::Object(map) => map.values().map(longest_string).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+fn find_string(value: &mut Value, len: usize) -> Option<&mut String> {
+    match value {
+        Value::String(s) if s.len() == len => Some(s),
+        Value::Array(values) => values.iter_mut().find_map(|v| find_string(v, len)),
+        Value::Object(map) => map.values_mut().find_map(|v| find_string(v, len)),
+        _ => None,
+    }
+}
@@ -0,0 +1,59 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic::{self, PanicHookInfo};
+
+use crate::export::Exporter;
+use crate::internal;
+use crate::worker::DEFAULT_SHUTDOWN_TIMEOUT;
+use crate::{EventHash, SplunkHecLayer};
+
+impl SplunkHecLayer {
+    // ship panics off as ERROR events with their message, where they happened and a backtrace
+    // when RUST_BACKTRACE asks for one. the worker is flushed before the panic carries on, so
+    // they make it out even when the panic is about to take the process down. whatever hook was
+    // installed before still runs afterwards, so the panic gets printed like usual.
+    pub fn install_panic_hook(&self) {
+        let exporter = self.exporter.clone();
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            report(&exporter, info);
+            previous(info);
+        }));
+    }
+}
+
+fn report(exporter: &Exporter, info: &PanicHookInfo<'_>) {
+    // a panic inside the layer or on the worker thread can't wait on the worker
+    let Some(_internal) = internal::enter() else {
+        return;
+    };
+
+    let payload = info.payload();
+    let message = match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        (None, None) => "Box<dyn Any>".to_string(),
+    };
+
+    let mut event = EventHash::new();
+    event.insert("message".into(), message.into());
+    event.insert("level".into(), "ERROR".into());
+    event.insert("panic".into(), true.into());
+    if let Some(location) = info.location() {
+        event.insert("panic.location".into(), location.to_string().into());
+    }
+    if let Some(name) = std::thread::current().name() {
+        event.insert("panic.thread".into(), name.into());
+    }
+    // capture() is what checks RUST_BACKTRACE and RUST_LIB_BACKTRACE
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        event.insert("panic.backtrace".into(), backtrace.to_string().into());
+    }
+
+    exporter.export(event, exporter.clock.system_time(), &tracing::Level::ERROR);
+    // there's nobody left to tell if this doesn't work out, the panic has to go on either way
+    let _ = exporter.worker.flush(DEFAULT_SHUTDOWN_TIMEOUT);
+}
@@ -0,0 +1,39 @@
+use std::collections::HashSet;
+
+// which Debug (and Display) formatted values get turned back into numbers and bools when that's
+// what they look like, see SplunkHecLayerBuilder::with_debug_value_parsing
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct DebugParsing {
+    pub(crate) all: bool,
+    pub(crate) fields: HashSet<String>,
+}
+
+impl DebugParsing {
+    pub(crate) fn applies(&self, name: &str) -> bool {
+        // a message that happens to be "404" is still a message, unless it was asked for by name
+        (self.all && name != "message") || self.fields.contains(name)
+    }
+}
+
+// the json a Debug formatted value stands for, if it's an integer, a float or a bool
+pub(crate) fn parse(value: &str) -> Option<serde_json::Value> {
+    match value {
+        "true" => return Some(true.into()),
+        "false" => return Some(false.into()),
+        _ => {}
+    }
+    if let Ok(n) = value.parse::<u64>() {
+        return Some(n.into());
+    }
+    if let Ok(n) = value.parse::<i64>() {
+        return Some(n.into());
+    }
+    // an integer too big for 64 bits would lose digits as a float, it's better off as a string
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // f64's parse takes "inf" and "NaN" too, and json has no way to write those
+    let n = value.parse::<f64>().ok().filter(|n| n.is_finite())?;
+    serde_json::Number::from_f64(n).map(serde_json::Value::Number)
+}
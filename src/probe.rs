@@ -0,0 +1,76 @@
+use std::fmt;
+
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+use crate::hec::{HecError, HecResponse};
+
+// what the startup probe found wrong with the HEC input, see SplunkHecLayerBuilder::startup_probe
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProbeError {
+    // we couldn't get an answer out of HEC at all
+    Unreachable(String),
+    // /services/collector/health says HEC isn't taking events, e.g. because its queues are full
+    Unhealthy {
+        status: u16,
+        text: String,
+    },
+    // HEC turned the token away, it's wrong, disabled or not allowed to use the input
+    InvalidToken {
+        status: u16,
+        code: Option<i64>,
+        text: String,
+    },
+}
+
+impl fmt::Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::Unreachable(e) => write!(f, "failed to reach HEC: {}", e),
+            ProbeError::Unhealthy { status, text } => {
+                write!(f, "HEC isn't healthy ({}): {}", status, text)
+            }
+            ProbeError::InvalidToken { status, text, .. } => {
+                write!(f, "HEC rejected the token ({}): {}", status, text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+// HEC's "no data" code, which is what an empty POST gets back once the token checks out
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+const NO_DATA: i64 = 5;
+
+// how the answer from /services/collector/health turns into a ProbeError
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn health(status: u16, body: &str) -> Result<(), ProbeError> {
+    if (200..300).contains(&status) {
+        return Ok(());
+    }
+    let text = serde_json::from_str::<HecResponse>(body)
+        .map(|r| r.text)
+        .unwrap_or_else(|_| body.to_owned());
+    Err(ProbeError::Unhealthy { status, text })
+}
+
+// how the answer to a POST without any events turns into a ProbeError. HEC checks the token before
+// it looks at the body, so complaining there's no data means the token is fine.
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+pub(crate) fn token(response: Result<HecResponse, HecError>) -> Result<(), ProbeError> {
+    match response {
+        Ok(_) => Ok(()),
+        Err(HecError::Status {
+            code: Some(NO_DATA),
+            ..
+        }) => Ok(()),
+        Err(HecError::Status {
+            status: status @ (401 | 403),
+            code,
+            text,
+            ..
+        }) => Err(ProbeError::InvalidToken { status, code, text }),
+        // whatever else went wrong, HEC isn't going to take our events
+        Err(HecError::Status { status, text, .. }) => Err(ProbeError::Unhealthy { status, text }),
+        Err(HecError::Transport(e)) => Err(ProbeError::Unreachable(e.to_string())),
+    }
+}
@@ -0,0 +1,71 @@
+use crate::hec::HecMetadata;
+use crate::EventHash;
+
+// what the layer adds about the machine and process it's running in. the hostname goes on the
+// HEC envelope's `host` (unless it was set on the builder), the rest are fields. everything but
+// the thread is the same for every event, so it's worked out once at build time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ProcessFields {
+    pub(crate) hostname: bool,
+    pub(crate) pid: bool,
+    pub(crate) executable: bool,
+    pub(crate) thread_id: bool,
+    pub(crate) thread_name: bool,
+}
+
+impl Default for ProcessFields {
+    fn default() -> Self {
+        ProcessFields {
+            hostname: true,
+            pid: true,
+            executable: true,
+            thread_id: false,
+            thread_name: false,
+        }
+    }
+}
+
+impl ProcessFields {
+    // the parts that never change, added to the metadata and global fields every event gets.
+    // anything already set on the builder is left alone.
+    pub(crate) fn apply(&self, metadata: &mut HecMetadata, global_fields: &mut EventHash) {
+        if self.hostname && metadata.host.is_none() {
+            metadata.host = gethostname::gethostname().into_string().ok();
+        }
+        if self.pid {
+            global_fields
+                .entry("pid".into())
+                .or_insert_with(|| std::process::id().into());
+        }
+        if self.executable {
+            let executable = std::env::current_exe().ok().and_then(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            });
+            if let Some(executable) = executable {
+                global_fields
+                    .entry("executable".into())
+                    .or_insert_with(|| executable.into());
+            }
+        }
+    }
+
+    // the thread the span closed or the event happened on
+    pub(crate) fn record_thread(&self, fields: &mut EventHash) {
+        if !self.thread_id && !self.thread_name {
+            return;
+        }
+        let thread = std::thread::current();
+        if self.thread_id {
+            // ThreadId::as_u64 isn't stable, so it's dug out of the Debug output, `ThreadId(5)`
+            let id = format!("{:?}", thread.id());
+            let id: String = id.chars().filter(char::is_ascii_digit).collect();
+            if let Ok(id) = id.parse::<u64>() {
+                fields.insert("thread.id".into(), id.into());
+            }
+        }
+        if let (true, Some(name)) = (self.thread_name, thread.name()) {
+            fields.insert("thread.name".into(), name.into());
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::ops::ControlFlow;
+
+use crate::record::EventRecord;
+
+// a step every event goes through right before it's handed to the worker, after redaction and
+// renaming, to enrich it, change it or drop it. they run in the order they were added to the
+// builder, and any one returning ControlFlow::Break drops the event without the rest seeing it.
+pub trait Processor: Send + Sync + 'static {
+    fn process(&self, event: &mut EventRecord) -> ControlFlow<()>;
+}
+
+impl<F> Processor for F
+where
+    F: Fn(&mut EventRecord) -> ControlFlow<()> + Send + Sync + 'static,
+{
+    fn process(&self, event: &mut EventRecord) -> ControlFlow<()> {
+        self(event)
+    }
+}
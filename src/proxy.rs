@@ -0,0 +1,72 @@
+use std::fmt;
+
+// how the built in transports get out to splunk
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Proxy {
+    // whatever HTTPS_PROXY (or ALL_PROXY and HTTP_PROXY) and NO_PROXY say, the same as curl
+    #[default]
+    FromEnv,
+    // always connect straight to splunk, whatever the environment says
+    Direct,
+    Custom(ProxyConfig),
+}
+
+// a proxy to send every request through, e.g. an authenticated egress proxy
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyConfig {
+    // e.g. http://proxy.internal:3128
+    pub url: String,
+    pub credentials: Option<ProxyCredentials>,
+    // hosts that are connected to directly, in the same format as NO_PROXY, e.g.
+    // `localhost,.internal.example.com`. None takes them from NO_PROXY.
+    pub no_proxy: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn new(url: impl Into<String>) -> Self {
+        ProxyConfig {
+            url: url.into(),
+            credentials: None,
+            no_proxy: None,
+        }
+    }
+
+    // authenticate with the proxy using basic auth
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some(ProxyCredentials {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    pub fn no_proxy(mut self, hosts: impl Into<String>) -> Self {
+        self.no_proxy = Some(hosts.into());
+        self
+    }
+
+    // the explicit list, or NO_PROXY if there isn't one
+    #[cfg(any(feature = "ureq", feature = "reqwest"))]
+    pub(crate) fn no_proxy_hosts(&self) -> Option<String> {
+        self.no_proxy.clone().or_else(|| {
+            std::env::var("NO_PROXY")
+                .or_else(|_| std::env::var("no_proxy"))
+                .ok()
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ProxyCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+// keep the password out of debug output
+impl fmt::Debug for ProxyCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyCredentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+// the worker's queue. it's a bounded channel like mpsc::sync_channel, and has the same errors, but
+// a sender can also give up waiting for room after a while, or make room by taking the oldest
+// record off the front, which QueueFullPolicy needs and std's channel can't do.
+pub(crate) fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            items: VecDeque::new(),
+            senders: 1,
+            receiving: true,
+        }),
+        capacity: capacity.max(1),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+    (Sender(shared.clone()), Receiver(shared))
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    senders: usize,
+    // false once the receiver is gone, after which nothing can be sent
+    receiving: bool,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub(crate) struct Sender<T>(Arc<Shared<T>>);
+
+impl<T> Sender<T> {
+    pub(crate) fn try_send(&self, item: T) -> Result<(), TrySendError<T>> {
+        let state = self.0.lock();
+        if !state.receiving {
+            return Err(TrySendError::Disconnected(item));
+        }
+        if state.items.len() >= self.0.capacity {
+            return Err(TrySendError::Full(item));
+        }
+        self.push(state, item);
+        Ok(())
+    }
+
+    // wait for room as long as it takes
+    pub(crate) fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut state = self.0.lock();
+        while state.receiving && state.items.len() >= self.0.capacity {
+            state = self
+                .0
+                .not_full
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        if !state.receiving {
+            return Err(SendError(item));
+        }
+        self.push(state, item);
+        Ok(())
+    }
+
+    // wait for room for at most `timeout`, handing the item back as Full if there still isn't any
+    pub(crate) fn send_timeout(&self, item: T, timeout: Duration) -> Result<(), TrySendError<T>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.0.lock();
+        while state.receiving && state.items.len() >= self.0.capacity {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(TrySendError::Full(item));
+            }
+            state = self
+                .0
+                .not_full
+                .wait_timeout(state, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        if !state.receiving {
+            return Err(TrySendError::Disconnected(item));
+        }
+        self.push(state, item);
+        Ok(())
+    }
+
+    // send without waiting, making room if there isn't any by taking the oldest item `displace`
+    // agrees to off the front. that's handed back, or the new item is as Full if nothing could go.
+    pub(crate) fn send_displacing(
+        &self,
+        item: T,
+        displace: impl Fn(&T) -> bool,
+    ) -> Result<Option<T>, TrySendError<T>> {
+        let mut state = self.0.lock();
+        if !state.receiving {
+            return Err(TrySendError::Disconnected(item));
+        }
+        let mut displaced = None;
+        if state.items.len() >= self.0.capacity {
+            let Some(oldest) = state.items.iter().position(displace) else {
+                return Err(TrySendError::Full(item));
+            };
+            displaced = state.items.remove(oldest);
+        }
+        self.push(state, item);
+        Ok(displaced)
+    }
+
+    fn push(&self, mut state: MutexGuard<'_, State<T>>, item: T) {
+        state.items.push_back(item);
+        drop(state);
+        self.0.not_empty.notify_one();
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.0.lock().senders += 1;
+        Sender(self.0.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let last = {
+            let mut state = self.0.lock();
+            state.senders -= 1;
+            state.senders == 0
+        };
+        if last {
+            self.0.not_empty.notify_all();
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+pub(crate) struct Receiver<T>(Arc<Shared<T>>);
+
+impl<T> Receiver<T> {
+    pub(crate) fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.0.lock();
+        loop {
+            if let Some(item) = self.pop(&mut state) {
+                return Ok(item);
+            }
+            if state.senders == 0 {
+                return Err(RecvError);
+            }
+            state = self
+                .0
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    pub(crate) fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.0.lock();
+        loop {
+            if let Some(item) = self.pop(&mut state) {
+                return Ok(item);
+            }
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .0
+                .not_empty
+                .wait_timeout(state, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    #[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+    pub(crate) fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.0.lock();
+        match self.pop(&mut state) {
+            Some(item) => Ok(item),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn pop(&self, state: &mut State<T>) -> Option<T> {
+        let item = state.items.pop_front()?;
+        self.0.not_full.notify_one();
+        Some(item)
+    }
+}
+
+// like std's, whatever was still queued is dropped along with the receiver, so anyone waiting on
+// a reply to a message in there hears that it's never coming
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let left = {
+            let mut state = self.0.lock();
+            state.receiving = false;
+            std::mem::take(&mut state.items)
+        };
+        self.0.not_full.notify_all();
+        drop(left);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::hec::HecMetadata;
+use crate::record::EventRecord;
+use crate::time::{HecTime, TimestampPrecision};
+use crate::EventHash;
+
+// a token bucket, `burst` events can go out at once and after that they're let through at
+// `per_second`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn per_second(per_second: f64) -> Self {
+        RateLimit {
+            per_second,
+            burst: per_second.ceil().max(1.0) as u32,
+        }
+    }
+}
+
+// how many spans and events can be exported, so a debug loop gone wrong can't eat the splunk
+// license. anything over the limit is dropped and counted, and every `summary_interval` a warning
+// says how many were dropped from each target:
+//
+//   message="1200 events from my_app::poller were suppressed by the rate limit"
+//   rate_limited.count=1200 rate_limited.target=my_app::poller
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimitConfig {
+    // shared by everything
+    pub global: Option<RateLimit>,
+    // each target gets a bucket of its own this size
+    pub per_target: Option<RateLimit>,
+    pub summary_interval: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            global: None,
+            per_target: None,
+            summary_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(limit: &RateLimit) -> Self {
+        Bucket {
+            tokens: f64::from(limit.burst),
+            last: Instant::now(),
+        }
+    }
+
+    fn take(&mut self, limit: &RateLimit) -> bool {
+        let now = Instant::now();
+        let refill = (now - self.last).as_secs_f64() * limit.per_second;
+        self.tokens = (self.tokens + refill).min(f64::from(limit.burst));
+        self.last = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+struct State {
+    global: Option<Bucket>,
+    targets: HashMap<&'static str, Bucket>,
+    suppressed: HashMap<&'static str, u64>,
+    due: Instant,
+}
+
+// the limiter the layer checks, and the suppressed counts the worker sends summaries of
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    metadata: HecMetadata,
+    global_fields: EventHash,
+    precision: TimestampPrecision,
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(
+        config: RateLimitConfig,
+        metadata: HecMetadata,
+        global_fields: EventHash,
+        precision: TimestampPrecision,
+    ) -> Self {
+        let state = Mutex::new(State {
+            global: config.global.as_ref().map(Bucket::new),
+            targets: HashMap::new(),
+            suppressed: HashMap::new(),
+            due: Instant::now() + config.summary_interval,
+        });
+        RateLimiter {
+            config,
+            metadata,
+            global_fields,
+            precision,
+            state,
+        }
+    }
+
+    // whether something from `target` can be exported, which uses up the tokens for it
+    pub(crate) fn allow(&self, target: &'static str) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let state = &mut *state;
+        let allowed = self.config.per_target.as_ref().is_none_or(|limit| {
+            state
+                .targets
+                .entry(target)
+                .or_insert_with(|| Bucket::new(limit))
+                .take(limit)
+        }) && match (&mut state.global, &self.config.global) {
+            (Some(bucket), Some(limit)) => bucket.take(limit),
+            _ => true,
+        };
+        if !allowed {
+            *state.suppressed.entry(target).or_default() += 1;
+        }
+        allowed
+    }
+
+    pub(crate) fn time_until_due(&self) -> Option<Duration> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.suppressed.is_empty() {
+            return None;
+        }
+        Some(state.due.saturating_duration_since(Instant::now()))
+    }
+
+    // a warning for each target that had something suppressed, once the summary interval is up.
+    // `force` doesn't wait for it, for shutting down.
+    pub(crate) fn take_due(&self, force: bool) -> Vec<EventRecord> {
+        let suppressed = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            if !force && state.due > now {
+                return Vec::new();
+            }
+            state.due = now + self.config.summary_interval;
+            std::mem::take(&mut state.suppressed)
+        };
+        suppressed
+            .into_iter()
+            .map(|(target, count)| self.summary_record(target, count))
+            .collect()
+    }
+
+    fn summary_record(&self, target: &str, count: u64) -> EventRecord {
+        let mut event = EventHash::new();
+        let message = format!(
+            "{} events from {} were suppressed by the rate limit",
+            count, target
+        );
+        event.insert("message".into(), message.into());
+        event.insert("level".into(), "WARN".into());
+        event.insert("rate_limited.count".into(), count.into());
+        event.insert("rate_limited.target".into(), target.to_owned().into());
+        for (name, value) in &self.global_fields {
+            event.entry(name.clone()).or_insert_with(|| value.clone());
+        }
+        EventRecord {
+            time: HecTime::new(SystemTime::now(), self.precision),
+            metadata: self.metadata.clone(),
+            event,
+            ..EventRecord::default()
+        }
+    }
+}
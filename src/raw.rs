@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+use crate::record::EventRecord;
+
+// how an event is turned into a line of text for HEC's raw endpoint, see
+// SplunkHecLayerBuilder::raw_endpoint. the line shouldn't have any newlines in it, splunk breaks
+// raw data into events on them.
+pub trait LineFormatter: Send + Sync + 'static {
+    fn format(&self, event: &EventRecord) -> String;
+}
+
+impl<F> LineFormatter for F
+where
+    F: Fn(&EventRecord) -> String + Send + Sync + 'static,
+{
+    fn format(&self, event: &EventRecord) -> String {
+        self(event)
+    }
+}
+
+// space separated key=value pairs, e.g.
+//
+//   time=1700000000.123 message="logged in" level=INFO user=bob
+//
+// the time comes first and the message right after it, everything else is sorted by name so the
+// same event always comes out the same. values with spaces, quotes or `=` in them are quoted, and
+// anything that isn't a string, number or bool is written out as json.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Logfmt;
+
+impl LineFormatter for Logfmt {
+    fn format(&self, event: &EventRecord) -> String {
+        let mut line = String::new();
+        if let Some(time) = &event.time {
+            let time = serde_json::to_string(time).unwrap_or_default();
+            pair(&mut line, "time", &time);
+        }
+        let message = event
+            .message
+            .as_deref()
+            .or_else(|| event.event.get("message")?.as_str());
+        if let Some(message) = message {
+            pair(&mut line, "message", &quoted(message));
+        }
+
+        let mut fields: Vec<_> = event
+            .event
+            .iter()
+            .chain(&event.fields)
+            .filter(|(name, _)| message.is_none() || *name != "message")
+            .collect();
+        fields.sort_by(|a, b| a.0.cmp(b.0));
+        fields.dedup_by(|a, b| a.0 == b.0);
+        for (name, value) in fields {
+            let value = match value {
+                serde_json::Value::String(s) => quoted(s),
+                other => quoted(&other.to_string()),
+            };
+            pair(&mut line, name, &value);
+        }
+        line
+    }
+}
+
+fn pair(line: &mut String, name: &str, value: &str) {
+    if !line.is_empty() {
+        line.push(' ');
+    }
+    let _ = write!(line, "{}={}", name, value);
+}
+
+// `value` as it has to be written for a logfmt parser to get it back
+fn quoted(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value.contains(|c: char| c == ' ' || c == '=' || c == '"' || c.is_control());
+    if !needs_quotes {
+        return value.to_owned();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
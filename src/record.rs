@@ -0,0 +1,131 @@
+use std::borrow::Cow;
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::hec::HecMetadata;
+use crate::time::HecTime;
+use crate::EventHash;
+
+// what happens to an event's `message`, i.e. the text in info!("text")
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MessageField {
+    // it's just another field in the event body
+    #[default]
+    Field,
+    // it's the event body itself, an HEC `event` string, and every other field is sent as an
+    // indexed field instead (as json text where it's nested, which HEC can't index as it is).
+    // spans and events without a message are sent the usual way.
+    Event,
+    // like Event, but it's kept as an indexed `message` field as well
+    Both,
+}
+
+// a single HEC event in the envelope format the /services/collector/event endpoint expects
+// (https://docs.splunk.com/Documentation/Splunk/latest/Data/FormateventsforHTTPEventCollector)
+#[derive(Clone, Debug, Default)]
+pub struct EventRecord {
+    // epoch seconds, HEC falls back to the time it received the event when this is missing
+    pub time: Option<HecTime>,
+    pub metadata: HecMetadata,
+    // the span or event fields, this is what shows up as the event body in splunk
+    pub event: EventHash,
+    // fields that get indexed alongside the event rather than extracted at search time
+    pub fields: EventHash,
+    // the event body as plain text instead, see MessageField. when it's set the `event` fields are
+    // sent along with `fields`.
+    pub message: Option<String>,
+}
+
+impl EventRecord {
+    // take the message out of `event` (or copy it) as MessageField says
+    pub(crate) fn extract_message(&mut self, mode: MessageField) {
+        let message = match (mode, self.event.get("message")) {
+            (MessageField::Field, _) => return,
+            (_, Some(serde_json::Value::String(message))) => message.clone(),
+            // there's nothing to show as text when it isn't one
+            _ => return,
+        };
+        if mode == MessageField::Event {
+            self.event.remove("message");
+        }
+        self.message = Some(message);
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Envelope<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<&'a HecTime>,
+    #[serde(flatten)]
+    metadata: &'a HecMetadata,
+    event: Body<'a>,
+    #[serde(skip_serializing_if = "Fields::is_empty")]
+    fields: Fields<'a>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+enum Body<'a> {
+    Fields(&'a EventHash),
+    Text(&'a str),
+}
+
+// the indexed fields, and the event fields too when the event itself is a string
+struct Fields<'a>(&'a EventHash, Option<&'a EventHash>);
+
+impl Fields<'_> {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty() && self.1.is_none_or(EventHash::is_empty)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&str, Cow<'_, serde_json::Value>)> {
+        // indexed fields win where both have one
+        let event = self.1.into_iter().flatten();
+        let event = event.filter(|(name, _)| !self.0.contains_key(name));
+        self.0
+            .iter()
+            .chain(event)
+            .filter_map(|(k, v)| Some((k.as_ref(), indexed(v)?)))
+    }
+}
+
+// HEC only indexes strings and numbers, or arrays of them, and turns the whole batch away over
+// anything else. so a nested value (a span's `events` list, say) goes as its json text instead,
+// and a null is left out.
+fn indexed(value: &serde_json::Value) -> Option<Cow<'_, serde_json::Value>> {
+    use serde_json::Value;
+
+    let flat = |value: &Value| matches!(value, Value::String(_) | Value::Number(_));
+    match value {
+        Value::Null => None,
+        Value::Array(values) if values.iter().all(flat) => Some(Cow::Borrowed(value)),
+        value if flat(value) => Some(Cow::Borrowed(value)),
+        value => Some(Cow::Owned(Value::String(value.to_string()))),
+    }
+}
+
+impl Serialize for Fields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (name, value) in self.iter() {
+            map.serialize_entry(name, &*value)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for EventRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (event, fields) = match &self.message {
+            Some(message) => (Body::Text(message), Fields(&self.fields, Some(&self.event))),
+            None => (Body::Fields(&self.event), Fields(&self.fields, None)),
+        };
+        Envelope {
+            time: self.time.as_ref(),
+            metadata: &self.metadata,
+            event,
+            fields,
+        }
+        .serialize(serializer)
+    }
+}
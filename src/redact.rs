@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::EventHash;
+
+// what redacted values get replaced with unless told otherwise
+pub const DEFAULT_REDACTION_MASK: &str = "[REDACTED]";
+
+type RedactFn = dyn Fn(&str, &mut Value) + Send + Sync;
+
+// scrubs fields before they're handed to the worker, so anything redacted here never makes it
+// into a batch. nested values (span event lists, the span hierarchy) are scrubbed too.
+#[derive(Clone)]
+pub(crate) struct Redactor {
+    // compared case insensitively, so `password` also catches `Password`
+    names: HashSet<String>,
+    #[cfg(feature = "regex")]
+    patterns: Vec<regex::Regex>,
+    callbacks: Vec<Arc<RedactFn>>,
+    mask: String,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Redactor {
+            names: HashSet::new(),
+            #[cfg(feature = "regex")]
+            patterns: Vec::new(),
+            callbacks: Vec::new(),
+            mask: DEFAULT_REDACTION_MASK.to_string(),
+        }
+    }
+}
+
+impl Redactor {
+    pub(crate) fn deny_name(&mut self, name: &str) {
+        self.names.insert(name.to_lowercase());
+    }
+
+    #[cfg(feature = "regex")]
+    pub(crate) fn deny_pattern(&mut self, pattern: regex::Regex) {
+        self.patterns.push(pattern);
+    }
+
+    pub(crate) fn add_callback(&mut self, callback: Arc<RedactFn>) {
+        self.callbacks.push(callback);
+    }
+
+    pub(crate) fn set_mask(&mut self, mask: String) {
+        self.mask = mask;
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        #[cfg(feature = "regex")]
+        if !self.patterns.is_empty() {
+            return false;
+        }
+        self.names.is_empty() && self.callbacks.is_empty()
+    }
+
+    pub(crate) fn redact(&self, fields: &mut EventHash) {
+        if self.is_empty() {
+            return;
+        }
+        for (name, value) in fields.iter_mut() {
+            self.redact_field(name, value);
+        }
+    }
+
+    fn redact_field(&self, name: &str, value: &mut Value) {
+        if self.names.contains(&name.to_lowercase()) {
+            *value = Value::from(self.mask.as_str());
+            return;
+        }
+
+        match value {
+            Value::Object(map) => {
+                for (name, value) in map.iter_mut() {
+                    self.redact_field(name, value);
+                }
+            }
+            Value::Array(values) => {
+                for value in values.iter_mut() {
+                    self.redact_field(name, value);
+                }
+            }
+            #[cfg(feature = "regex")]
+            Value::String(s) => {
+                for pattern in &self.patterns {
+                    if let std::borrow::Cow::Owned(replaced) =
+                        pattern.replace_all(s, self.mask.as_str())
+                    {
+                        *s = replaced;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        for callback in &self.callbacks {
+            callback(name, value);
+        }
+    }
+}
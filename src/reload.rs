@@ -0,0 +1,84 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::batch::BatchConfig;
+use crate::sampling::HeadSampleRatio;
+use crate::worker::{FlushError, Reconfigure, WorkerHandle};
+
+// for changing how the layer exports while it's running, e.g. from a config watcher or a secret
+// rotation hook. get one from SplunkHecLayer::config_handle before the layer is handed to the
+// subscriber, it can be cloned and kept anywhere.
+//
+// the head sampling ratio takes effect straight away for the next root span. batching and the
+// token are handed to the worker, which applies them between batches so nothing in flight is
+// sent half one way and half the other.
+#[derive(Clone, Debug)]
+pub struct ConfigHandle {
+    worker: WorkerHandle,
+    head_sample_ratio: Arc<HeadSampleRatio>,
+}
+
+impl ConfigHandle {
+    pub(crate) fn new(worker: WorkerHandle, head_sample_ratio: Arc<HeadSampleRatio>) -> Self {
+        ConfigHandle {
+            worker,
+            head_sample_ratio,
+        }
+    }
+
+    pub fn head_sample_ratio(&self) -> f64 {
+        self.head_sample_ratio.get()
+    }
+
+    // the same as SplunkHecLayerBuilder::head_sample_ratio. traces that have already started keep
+    // the decision made at their root.
+    pub fn set_head_sample_ratio(&self, ratio: f64) {
+        self.head_sample_ratio.set(ratio);
+    }
+
+    // replace the batching limits, waiting at most `timeout` for the worker to take them. if
+    // what's already batched is over the new limits it's shipped right away.
+    pub fn set_batching(&self, batch: BatchConfig, timeout: Duration) -> Result<(), ReloadError> {
+        self.reconfigure(Reconfigure::Batching(batch), timeout)
+    }
+
+    // start sending a new HEC token, for rotating it without restarting. everything still
+    // batched, spooled or waiting to be retried goes out with the new one. only the built in
+    // http transports (or a custom one implementing Transport::set_token) can change it.
+    pub fn set_token(&self, token: &str, timeout: Duration) -> Result<(), ReloadError> {
+        self.reconfigure(Reconfigure::Token(token.to_owned()), timeout)
+    }
+
+    fn reconfigure(&self, change: Reconfigure, timeout: Duration) -> Result<(), ReloadError> {
+        match self.worker.reconfigure(change, timeout) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(ReloadError::Unsupported),
+            Err(FlushError::Timeout) => Err(ReloadError::Timeout),
+            Err(FlushError::Disconnected) => Err(ReloadError::Disconnected),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadError {
+    // the worker didn't get to the change before the timeout ran out. it's still queued, so it
+    // may yet be applied.
+    Timeout,
+    // the worker has already shut down
+    Disconnected,
+    // the transport has no way to take the change, e.g. a new token for a WriterTransport
+    Unsupported,
+}
+
+impl fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReloadError::Timeout => write!(f, "timed out waiting for the splunk hec worker"),
+            ReloadError::Disconnected => write!(f, "the splunk hec worker has shut down"),
+            ReloadError::Unsupported => write!(f, "the transport doesn't support this change"),
+        }
+    }
+}
+
+impl std::error::Error for ReloadError {}
@@ -0,0 +1,136 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::intern::intern;
+use crate::EventHash;
+
+// a naming convention for field names, applied to every word between the dots so `http.statusCode`
+// becomes `http.status_code` and not `http_status_code`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCase {
+    Snake,
+    Camel,
+}
+
+impl KeyCase {
+    pub fn apply(self, name: &str) -> String {
+        name.split('.')
+            .map(|segment| match self {
+                KeyCase::Snake => snake_case(segment),
+                KeyCase::Camel => camel_case(segment),
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+// httpStatus, HTTPStatus, http-status and `http status` all come out as http_status
+fn snake_case(segment: &str) -> String {
+    let chars: Vec<char> = segment.chars().collect();
+    let mut out = String::with_capacity(segment.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '-' || c == ' ' || c == '_' {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            let boundary = previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_is_lower);
+            if boundary && !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn camel_case(segment: &str) -> String {
+    let snake = snake_case(segment);
+    let mut out = String::with_capacity(snake.len());
+    for (i, word) in snake.split('_').filter(|w| !w.is_empty()).enumerate() {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) if i > 0 => out.extend(first.to_uppercase()),
+            Some(first) => out.push(first),
+            None => {}
+        }
+        out.push_str(chars.as_str());
+    }
+    out
+}
+
+// renames fields on their way out, so the exported names can match what dashboards expect without
+// touching the instrumentation. a field with a rename of its own isn't put through the KeyCase.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FieldRenames {
+    renames: HashMap<String, String>,
+    presets: HashMap<String, String>,
+    case: Option<KeyCase>,
+    // what each &'static name (a callsite's own, or an interned one) was renamed to last time,
+    // there are only ever so many of those
+    cache: Arc<RwLock<HashMap<&'static str, Cow<'static, str>>>>,
+}
+
+impl FieldRenames {
+    pub(crate) fn rename(&mut self, from: String, to: String) {
+        self.renames.insert(from, to);
+    }
+
+    // a rename that came with a preset like a CimModel, which rename() always wins over
+    pub(crate) fn preset(&mut self, from: String, to: String) {
+        self.presets.insert(from, to);
+    }
+
+    pub(crate) fn set_case(&mut self, case: KeyCase) {
+        self.case = Some(case);
+    }
+
+    pub(crate) fn apply(&self, fields: &mut EventHash) {
+        if self.renames.is_empty() && self.presets.is_empty() && self.case.is_none() {
+            return;
+        }
+        let renamed = std::mem::take(fields)
+            .into_iter()
+            .map(|(name, value)| (self.renamed(name), value));
+        fields.extend(renamed);
+    }
+
+    fn renamed(&self, name: Cow<'static, str>) -> Cow<'static, str> {
+        let Cow::Borrowed(name) = name else {
+            return self.convert(name);
+        };
+        let cached = self.cache.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(renamed) = cached.get(name) {
+            return renamed.clone();
+        }
+        drop(cached);
+        let renamed = self.convert(Cow::Borrowed(name));
+        self.cache
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name, renamed.clone());
+        renamed
+    }
+
+    fn convert(&self, name: Cow<'static, str>) -> Cow<'static, str> {
+        let renamed = self
+            .renames
+            .get(name.as_ref())
+            .or_else(|| self.presets.get(name.as_ref()));
+        match (renamed, self.case) {
+            (Some(to), _) => intern(to),
+            (None, Some(case)) => match case.apply(&name) {
+                converted if converted == name => name,
+                converted => intern(&converted),
+            },
+            (None, None) => name,
+        }
+    }
+}
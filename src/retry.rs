@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use crate::hec::HecError;
+
+// how hard the worker tries to get a batch into splunk before giving up on it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // total number of tries, including the first one. 1 disables retries entirely.
+    pub max_attempts: u32,
+    // the backoff before the first retry, doubled on every retry after that
+    pub initial_backoff: Duration,
+    // the backoff never grows past this, and neither does HEC's Retry-After
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // give up after the first failure
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    // how long to wait before retrying after `attempt` failed tries (starting at 1), or None if
+    // the batch shouldn't be retried at all
+    pub(crate) fn backoff(&self, attempt: u32, error: &HecError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !error.is_retryable() {
+            return None;
+        }
+
+        // if HEC told us how long to back off for then do exactly that
+        if let Some(retry_after) = error.retry_after() {
+            return Some(retry_after.min(self.max_backoff));
+        }
+
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_backoff);
+
+        // "equal jitter": always wait at least half the backoff, and a random amount on top of
+        // that so a fleet of workers that failed together don't all retry together
+        let half = exponential / 2;
+        let jitter = half.mul_f64(fastrand::f64());
+        Some(half + jitter)
+    }
+}
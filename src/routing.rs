@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tracing::Level;
+
+use crate::record::EventRecord;
+use crate::worker::WorkerHandle;
+
+// which index spans and events of each level go to, see SplunkHecLayerBuilder::route_levels.
+// levels without a route go wherever the builder's index says.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LevelRoutes {
+    indexes: [Option<String>; 5],
+}
+
+impl LevelRoutes {
+    pub(crate) fn set(&mut self, level: Level, index: String) {
+        self.indexes[slot(&level)] = Some(index);
+    }
+
+    pub(crate) fn index(&self, level: &Level) -> Option<&str> {
+        self.indexes[slot(level)].as_deref()
+    }
+}
+
+fn slot(level: &Level) -> usize {
+    match *level {
+        Level::TRACE => 0,
+        Level::DEBUG => 1,
+        Level::INFO => 2,
+        Level::WARN => 3,
+        Level::ERROR => 4,
+    }
+}
+
+// which tenant a record belongs to, see SplunkHecLayerBuilder::tenant_routes
+pub type RouteKey = String;
+
+// a HEC input of its own for one tenant. TLS and the proxy are the same as the main endpoint's.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Route {
+    pub endpoint: String,
+    pub token: String,
+    // overrides whatever index the record was going to, None leaves it as it is
+    pub index: Option<String>,
+}
+
+impl Route {
+    pub fn new(endpoint: impl Into<String>, token: impl Into<String>) -> Self {
+        Route {
+            endpoint: endpoint.into(),
+            token: token.into(),
+            index: None,
+        }
+    }
+}
+
+pub(crate) type Router = dyn Fn(&EventRecord) -> Option<RouteKey> + Send + Sync;
+
+// the exporter's side of the tenant routes, with a worker per route so each batch only has one
+// tenant's events in it
+pub(crate) struct TenantRoutes {
+    router: Arc<Router>,
+    routes: HashMap<RouteKey, (WorkerHandle, Option<String>)>,
+}
+
+impl TenantRoutes {
+    pub(crate) fn new(router: Arc<Router>) -> Self {
+        TenantRoutes {
+            router,
+            routes: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, key: RouteKey, worker: WorkerHandle, index: Option<String>) {
+        self.routes.insert(key, (worker, index));
+    }
+
+    // send `record` to its tenant's worker, or hand it back if it doesn't have one
+    pub(crate) fn send(&self, mut record: EventRecord, level: &Level) -> Option<EventRecord> {
+        let Some((worker, index)) = (self.router)(&record).and_then(|key| self.routes.get(&key))
+        else {
+            return Some(record);
+        };
+        if let Some(index) = index {
+            record.metadata.index = Some(index.clone());
+        }
+        worker.send(record, level);
+        None
+    }
+}
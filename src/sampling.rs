@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::EventHash;
+
+// what a tail sampling predicate gets to look at when a span closes
+#[derive(Debug)]
+pub struct TailSample<'a> {
+    pub name: &'static str,
+    // everything that's about to be exported for the span
+    pub fields: &'a EventHash,
+    // whether an ERROR event happened inside the span or any of its children
+    pub saw_error: bool,
+}
+
+type TailPredicate = dyn Fn(&TailSample<'_>) -> bool + Send + Sync;
+
+// decides at on_close whether a span is worth exporting at all
+#[derive(Clone, Default)]
+pub(crate) enum TailSampler {
+    #[default]
+    Off,
+    Errors,
+    Predicate(Arc<TailPredicate>),
+}
+
+impl TailSampler {
+    pub(crate) fn is_enabled(&self) -> bool {
+        !matches!(self, TailSampler::Off)
+    }
+
+    pub(crate) fn keep(&self, sample: &TailSample<'_>) -> bool {
+        match self {
+            TailSampler::Off => true,
+            TailSampler::Errors => sample.saw_error,
+            TailSampler::Predicate(predicate) => predicate(sample),
+        }
+    }
+}
+
+// marks a span that had an ERROR event inside of it, or inside one of its children
+pub(crate) struct SawError;
+
+// marks a span whose trace lost the head sampling coin toss. nothing else is stored for these
+// spans, and their children inherit the marker so the whole trace is left out together.
+pub(crate) struct NotSampled;
+
+// the head sampling ratio, shared between the layer and its ConfigHandles so it can be changed
+// while the layer is running. the f64 is kept as its bits since there's no AtomicF64.
+#[derive(Debug)]
+pub(crate) struct HeadSampleRatio(AtomicU64);
+
+impl HeadSampleRatio {
+    pub(crate) fn new(ratio: f64) -> Self {
+        HeadSampleRatio(AtomicU64::new(sample_ratio(ratio).to_bits()))
+    }
+
+    pub(crate) fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set(&self, ratio: f64) {
+        self.0
+            .store(sample_ratio(ratio).to_bits(), Ordering::Relaxed);
+    }
+}
+
+// `ratio` kept between 0 and 1. one that isn't a number at all (NaN or infinite, most likely from
+// dividing by zero somewhere) exports everything, rather than NaN quietly dropping every trace.
+pub(crate) fn sample_ratio(ratio: f64) -> f64 {
+    if ratio.is_finite() {
+        ratio.clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
+
+// the head sampling decision for a new root span, made once and then inherited by its children
+pub(crate) fn head_sample(ratio: f64) -> bool {
+    ratio >= 1.0 || fastrand::f64() < ratio
+}
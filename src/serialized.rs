@@ -0,0 +1,75 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::EventHash;
+
+// the field a Serialized value is recorded under. the layer spreads it out into one field per key
+// instead of keeping it under this name.
+pub(crate) const FIELD: &str = "splunk.fields";
+
+// any Serialize value as a bunch of fields, for teams whose events are already typed structs, e.g.
+//
+//   info!(splunk.fields = %Serialized(&order), "order placed");
+//
+// or splunk_event! which does that for you. the value's keys become the event's (or span's) own
+// fields, nested values stay nested. other layers just see it as json.
+pub struct Serialized<'a, T: ?Sized>(pub &'a T);
+
+impl<T: Serialize + ?Sized> fmt::Display for Serialized<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // an error here would make whoever's formatting us panic, so a value that can't be
+        // serialized (e.g. a map with non-string keys) shows up as why instead
+        let json = serde_json::to_string(self.0)
+            .unwrap_or_else(|e| serde_json::Value::from(e.to_string()).to_string());
+        f.write_str(&json)
+    }
+}
+
+impl<T: Serialize + ?Sized> fmt::Debug for Serialized<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+// spread what a Serialized value wrote out into `fields`. anything that isn't an object (a number,
+// an array, the error from above) is kept as is under FIELD.
+pub(crate) fn flatten(json: &str, fields: &mut EventHash) {
+    match serde_json::from_str(json) {
+        Ok(serde_json::Value::Object(map)) => {
+            for (key, value) in map {
+                fields.insert(key.into(), value);
+            }
+        }
+        Ok(other) => {
+            fields.insert(FIELD.into(), other);
+        }
+        Err(_) => {
+            fields.insert(FIELD.into(), json.into());
+        }
+    }
+}
+
+// an event built from a Serialize value, at INFO unless a level is given first:
+//
+//   splunk_event!(&order);
+//   splunk_event!(Level::WARN, &order, "order {} is late", order.id);
+//   splunk_event!(Level::INFO, &order, customer = %name, "order placed");
+//
+// anything after the value is passed on to tracing::event! as more fields and the message
+#[macro_export]
+macro_rules! splunk_event {
+    ($value:expr) => {
+        $crate::splunk_event!($crate::__tracing::Level::INFO, $value)
+    };
+    ($level:expr, $value:expr) => {
+        $crate::__tracing::event!($level, splunk.fields = %$crate::Serialized($value))
+    };
+    ($level:expr, $value:expr, $($rest:tt)+) => {
+        $crate::__tracing::event!(
+            $level,
+            splunk.fields = %$crate::Serialized($value),
+            $($rest)+
+        )
+    };
+}
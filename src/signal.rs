@@ -0,0 +1,26 @@
+use std::time::Duration;
+
+pub use ctrlc::Error as SignalError;
+
+use crate::worker::WorkerGuard;
+
+// flush what's buffered when the process gets ctrl-c or SIGTERM (or SIGHUP), waiting at most
+// `timeout` for it, and then exit with status 130. that's always 130, ctrl-c's usual status,
+// whichever signal it was, since the handler isn't told which (the signal itself would have given
+// 143 for SIGTERM and 129 for SIGHUP). for when the guard can't easily be dropped on the way out,
+// or in case it isn't:
+//
+//   let (layer, guard) = SplunkHecLayer::builder().build()?;
+//   tracing_splunk_layer::flush_on_signal(&guard, Duration::from_secs(5))?;
+//
+// the guard stays where it is, so returning from main still shuts the worker down as usual. a
+// process only gets one handler for these, so this fails if something else has already set one,
+// and anything that wants to run its own shutdown should set a handler of its own and call
+// WorkerGuard::shutdown from it instead. it does nothing for SIGKILL, nothing can.
+pub fn flush_on_signal(guard: &WorkerGuard, timeout: Duration) -> Result<(), SignalError> {
+    let flush = guard.flusher();
+    ctrlc::set_handler(move || {
+        flush(timeout);
+        std::process::exit(130);
+    })
+}
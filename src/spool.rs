@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::batch::{Batch, BatchConfig};
+
+pub const DEFAULT_SPOOL_MAX_BYTES: u64 = 256 * 1024 * 1024;
+pub const DEFAULT_SPOOL_SEGMENT_BYTES: u64 = 8 * 1024 * 1024;
+pub const DEFAULT_SPOOL_REPLAY_INTERVAL: Duration = Duration::from_secs(30);
+
+// where batches go when splunk can't take them. everything the worker couldn't deliver (and that
+// would be worth trying again) is appended to segment files in `dir`, and replayed once splunk is
+// taking events again, including by the next run of the application if it never recovered.
+//
+// delivery from the spool is at least once: a segment that was only partly replayed before splunk
+// went away again is rewritten with what's left, but a batch that made it to splunk without us
+// hearing back will be sent again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpoolConfig {
+    pub dir: PathBuf,
+    // the most the spool can hold. once it's full the oldest segments are thrown away to make room.
+    pub max_bytes: u64,
+    // a new segment file is started once the current one is this big
+    pub segment_bytes: u64,
+    // how often to try replaying the spool while splunk looks to be down
+    pub replay_interval: Duration,
+}
+
+impl SpoolConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SpoolConfig {
+            dir: dir.into(),
+            max_bytes: DEFAULT_SPOOL_MAX_BYTES,
+            segment_bytes: DEFAULT_SPOOL_SEGMENT_BYTES,
+            replay_interval: DEFAULT_SPOOL_REPLAY_INTERVAL,
+        }
+    }
+}
+
+// a segment file, named after its sequence number so the directory sorts oldest first
+struct Segment {
+    path: PathBuf,
+    bytes: u64,
+}
+
+// the worker's handle on the spool directory. a segment is just events one per line, the same as
+// a batch, so a segment can be cut back up into batches however the batch config likes.
+pub(crate) struct Spool {
+    config: SpoolConfig,
+    segments: VecDeque<Segment>,
+    next_seq: u64,
+    next_replay: Instant,
+}
+
+impl Spool {
+    // picks up whatever an earlier run left behind in the directory, so it'll be replayed
+    pub(crate) fn open(config: SpoolConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+
+        let mut found = Vec::new();
+        for entry in fs::read_dir(&config.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("spool") {
+                continue;
+            }
+            let seq = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok());
+            if let Some(seq) = seq {
+                found.push((seq, path, entry.metadata()?.len()));
+            }
+        }
+        found.sort_by_key(|(seq, _, _)| *seq);
+
+        let next_seq = found.last().map(|(seq, _, _)| seq + 1).unwrap_or(0);
+        let segments = found
+            .into_iter()
+            .map(|(_, path, bytes)| Segment { path, bytes })
+            .collect();
+        Ok(Spool {
+            config,
+            segments,
+            next_seq,
+            next_replay: Instant::now(),
+        })
+    }
+
+    fn total_bytes(&self) -> u64 {
+        self.segments.iter().map(|s| s.bytes).sum()
+    }
+
+    // append a batch to the newest segment, starting a new one if it's full. returns how many
+    // bytes of older segments had to be thrown away to stay under max_bytes.
+    pub(crate) fn write(&mut self, batch: &Batch) -> io::Result<u64> {
+        let mut segment = match self.segments.pop_back() {
+            Some(segment) if segment.bytes < self.config.segment_bytes => segment,
+            full => {
+                self.segments.extend(full);
+                let path = self.config.dir.join(format!("{:020}.spool", self.next_seq));
+                self.next_seq += 1;
+                Segment { path, bytes: 0 }
+            }
+        };
+
+        let written = append(&segment.path, batch);
+        if written.is_ok() {
+            segment.bytes += batch.as_str().len() as u64 + 1;
+        }
+        self.segments.push_back(segment);
+        written?;
+
+        // keep the newest segment no matter what, it's the one holding the batch we just wrote
+        let mut discarded = 0;
+        while self.total_bytes() > self.config.max_bytes && self.segments.len() > 1 {
+            if let Some(oldest) = self.segments.pop_front() {
+                discarded += oldest.bytes;
+                fs::remove_file(&oldest.path)?;
+            }
+        }
+        Ok(discarded)
+    }
+
+    // how long until the spool is due to be replayed, or None if there's nothing in it
+    pub(crate) fn time_until_replay(&self) -> Option<Duration> {
+        if self.segments.is_empty() {
+            return None;
+        }
+        Some(self.next_replay.saturating_duration_since(Instant::now()))
+    }
+
+    // splunk is still down, don't bother again until the replay interval is up
+    pub(crate) fn replay_later(&mut self) {
+        self.next_replay = Instant::now() + self.config.replay_interval;
+    }
+
+    // the events in the oldest segment, cut up into batches
+    pub(crate) fn oldest(&self, config: &BatchConfig) -> Option<io::Result<Vec<Batch>>> {
+        let segment = self.segments.front()?;
+        Some(fs::read_to_string(&segment.path).map(|contents| rebatch(&contents, config)))
+    }
+
+    // the oldest segment made it to splunk, or was unreadable and never will
+    pub(crate) fn remove_oldest(&mut self) -> io::Result<()> {
+        match self.segments.pop_front() {
+            Some(segment) => fs::remove_file(segment.path),
+            None => Ok(()),
+        }
+    }
+
+    // only some of the oldest segment made it, so just keep what's left of it
+    pub(crate) fn truncate_oldest(&mut self, remaining: &[Batch]) -> io::Result<()> {
+        let Some(segment) = self.segments.front_mut() else {
+            return Ok(());
+        };
+        let mut file = File::create(&segment.path)?;
+        let mut bytes = 0;
+        for batch in remaining {
+            file.write_all(batch.as_str().as_bytes())?;
+            file.write_all(b"\n")?;
+            bytes += batch.as_str().len() as u64 + 1;
+        }
+        file.sync_data()?;
+        segment.bytes = bytes;
+        Ok(())
+    }
+}
+
+fn append(path: &Path, batch: &Batch) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(batch.as_str().as_bytes())?;
+    file.write_all(b"\n")?;
+    file.sync_data()
+}
+
+fn rebatch(contents: &str, config: &BatchConfig) -> Vec<Batch> {
+    let mut batches = Vec::new();
+    let mut batch = Batch::default();
+    for event in contents.lines().filter(|line| !line.is_empty()) {
+        if batch.would_overflow(event, config) || batch.is_full(config) {
+            batches.push(std::mem::take(&mut batch));
+        }
+        batch.push(event);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
@@ -0,0 +1,96 @@
+use serde_json::{Map, Value as Json};
+use valuable::{NamedValues, Valuable, Value, Visit};
+
+// a field recorded with tracing::field::valuable, as the nested json it really is instead of its
+// Debug output. structs and maps become objects, lists and tuples arrays, a unit enum variant is
+// just its name and any other variant is an object with its name as the only key.
+pub(crate) fn to_json(value: Value<'_>) -> Json {
+    match value {
+        Value::Bool(v) => v.into(),
+        Value::Char(v) => v.to_string().into(),
+        Value::F32(v) => f64::from(v).into(),
+        Value::F64(v) => v.into(),
+        Value::I8(v) => v.into(),
+        Value::I16(v) => v.into(),
+        Value::I32(v) => v.into(),
+        Value::I64(v) => v.into(),
+        Value::Isize(v) => v.into(),
+        Value::U8(v) => v.into(),
+        Value::U16(v) => v.into(),
+        Value::U32(v) => v.into(),
+        Value::U64(v) => v.into(),
+        Value::Usize(v) => v.into(),
+        // past 64 bits serde_json needs a string, the same as elapsed times
+        Value::I128(v) => i64::try_from(v).map_or_else(|_| v.to_string().into(), Json::from),
+        Value::U128(v) => u64::try_from(v).map_or_else(|_| v.to_string().into(), Json::from),
+        Value::String(v) => v.into(),
+        Value::Path(v) => v.display().to_string().into(),
+        Value::Error(v) => v.to_string().into(),
+        Value::Unit => Json::Null,
+        Value::Listable(v) => Json::Array(collect(v).items),
+        Value::Mappable(v) => Json::Object(collect(v).fields),
+        Value::Tuplable(v) => Json::Array(collect(v).items),
+        Value::Structable(v) => collect(v).into_json(),
+        Value::Enumerable(v) => {
+            let name = v.variant().name().to_owned();
+            let fields = collect(v);
+            if fields.items.is_empty() && fields.fields.is_empty() {
+                return name.into();
+            }
+            let mut variant = Map::new();
+            variant.insert(name, fields.into_json());
+            Json::Object(variant)
+        }
+        _ => Json::Null,
+    }
+}
+
+// whatever a value turned up while it was visited
+#[derive(Default)]
+struct Collected {
+    // list items, tuple and unnamed fields
+    items: Vec<Json>,
+    // named fields and map entries
+    fields: Map<String, Json>,
+}
+
+impl Collected {
+    fn into_json(self) -> Json {
+        if self.items.is_empty() {
+            Json::Object(self.fields)
+        } else {
+            Json::Array(self.items)
+        }
+    }
+}
+
+fn collect<V: Valuable + ?Sized>(value: &V) -> Collected {
+    let mut collected = Collected::default();
+    value.visit(&mut collected);
+    collected
+}
+
+impl Visit for Collected {
+    fn visit_value(&mut self, value: Value<'_>) {
+        self.items.push(to_json(value));
+    }
+
+    fn visit_named_fields(&mut self, named_values: &NamedValues<'_>) {
+        for (field, value) in named_values {
+            self.fields.insert(field.name().to_owned(), to_json(*value));
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[Value<'_>]) {
+        self.items.extend(values.iter().map(|v| to_json(*v)));
+    }
+
+    fn visit_entry(&mut self, key: Value<'_>, value: Value<'_>) {
+        // json keys have to be strings, anything else is written out as json first
+        let key = match to_json(key) {
+            Json::String(key) => key,
+            key => key.to_string(),
+        };
+        self.fields.insert(key, to_json(value));
+    }
+}
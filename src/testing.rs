@@ -0,0 +1,330 @@
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+// just enough of an http server to stand in for HEC in tests, this crate's and yours, behind the
+// `testing` feature. it answers on 127.0.0.1 like a HEC input would:
+//
+//   let hec = MockHec::start().require_token("abc");
+//   let (layer, guard) = SplunkHecLayer::builder().endpoint(hec.url()).token("abc").build()?;
+//   ...
+//   guard.flush(Duration::from_secs(1))?;
+//   assert_eq!(hec.events()[0]["event"]["message"], "hello");
+//
+// every request succeeds unless it's been told otherwise with respond_with, require_token or
+// enable_acks. it's meant for tests, so it panics rather than reporting errors.
+pub struct MockHec {
+    addr: String,
+    state: Arc<Mutex<State>>,
+}
+
+// a request as the mock HEC saw it
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+// a canned reply for the mock to give instead of its usual one
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: u16,
+    pub body: String,
+    pub headers: Vec<(String, String)>,
+    // how long to sit on the request before answering
+    pub delay: Duration,
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<ReceivedRequest>,
+    responses: VecDeque<MockResponse>,
+    // requests that haven't been answered yet, and the most there have ever been at once
+    in_flight: usize,
+    most_in_flight: usize,
+    connections: usize,
+    token: Option<String>,
+    acks: Option<Acks>,
+}
+
+// indexer acknowledgment, once enable_acks has been called
+#[derive(Default)]
+struct Acks {
+    next_id: u64,
+    // what hasn't been acknowledged yet, because of hold_acks
+    held: HashSet<u64>,
+    holding: bool,
+}
+
+const EVENT_PATH: &str = "/services/collector/event";
+const RAW_PATH: &str = "/services/collector/raw";
+const ACK_PATH: &str = "/services/collector/ack";
+
+impl ReceivedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    // every event in the batch, HEC doesn't need them to be newline separated but we do that.
+    // lines that aren't json, like the raw endpoint's, are left out.
+    pub fn events(&self) -> Vec<Value> {
+        self.body
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    fn is_events(&self) -> bool {
+        self.path == EVENT_PATH || self.path.starts_with(RAW_PATH)
+    }
+}
+
+impl MockResponse {
+    pub fn success() -> Self {
+        MockResponse::status(200, r#"{"text":"Success","code":0}"#)
+    }
+
+    pub fn status(status: u16, body: &str) -> Self {
+        MockResponse {
+            status,
+            body: body.to_string(),
+            headers: Vec::new(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    // what HEC says when its queues are full
+    pub fn busy() -> Self {
+        MockResponse::status(503, r#"{"text":"Server is busy","code":9}"#)
+    }
+
+    // what a rate limiting proxy in front of HEC says, with a Retry-After
+    pub fn too_many_requests(retry_after: Duration) -> Self {
+        MockResponse::status(429, r#"{"text":"Too many requests","code":9}"#)
+            .header("Retry-After", &retry_after.as_secs().to_string())
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+impl MockHec {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to start the mock HEC");
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let shared = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = shared.clone();
+                lock(&shared).connections += 1;
+                // a client hanging up halfway through a request is its own business
+                thread::spawn(move || handle(stream, shared).ok());
+            }
+        });
+
+        MockHec { addr, state }
+    }
+
+    // turn away requests without `Authorization: Splunk <token>`, the way HEC does
+    pub fn require_token(self, token: &str) -> Self {
+        lock(&self.state).token = Some(token.to_owned());
+        self
+    }
+
+    // answer every batch with an ackId and ack queries with whether they've been indexed, which
+    // they all have unless hold_acks says otherwise. batches have to be on a channel, as with
+    // HEC.
+    pub fn enable_acks(self) -> Self {
+        lock(&self.state).acks = Some(Acks::default());
+        self
+    }
+
+    // stop acknowledging batches sent from now on (or start again, acknowledging everything that
+    // was held back), to test what happens when indexing falls behind
+    pub fn hold_acks(&self, holding: bool) {
+        if let Some(acks) = &mut lock(&self.state).acks {
+            acks.holding = holding;
+            if !holding {
+                acks.held.clear();
+            }
+        }
+    }
+
+    // queue up a reply for the next request, once these run out the mock goes back to its usual
+    pub fn respond_with(&self, response: MockResponse) {
+        lock(&self.state).responses.push_back(response);
+    }
+
+    pub fn url(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn requests(&self) -> Vec<ReceivedRequest> {
+        lock(&self.state).requests.clone()
+    }
+
+    // every event sent to it so far, oldest first, whatever request it came in
+    pub fn events(&self) -> Vec<Value> {
+        lock(&self.state)
+            .requests
+            .iter()
+            .filter(|request| request.is_events())
+            .flat_map(ReceivedRequest::events)
+            .collect()
+    }
+
+    // how many connections have been opened to it, however many requests went down each
+    pub fn connections(&self) -> usize {
+        lock(&self.state).connections
+    }
+
+    pub fn most_in_flight(&self) -> usize {
+        lock(&self.state).most_in_flight
+    }
+
+    // the layer ships from a background worker, so give it a moment to catch up
+    pub fn wait_for_requests(&self, count: usize) -> Vec<ReceivedRequest> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lock(&self.state).requests.len() < count && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.requests()
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn handle(stream: TcpStream, state: Arc<Mutex<State>>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    // keep serving requests on this connection until the client hangs up
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line)? == 0 {
+            return Ok(());
+        }
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                headers.push((k.trim().to_string(), v.trim().to_string()));
+            }
+        }
+
+        let length = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, v)| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+
+        let response = {
+            let mut state = lock(&state);
+            let request = ReceivedRequest {
+                path,
+                headers,
+                body: String::from_utf8_lossy(&body).into_owned(),
+            };
+            let response = respond(&mut state, &request);
+            state.requests.push(request);
+            state.in_flight += 1;
+            state.most_in_flight = state.most_in_flight.max(state.in_flight);
+            response
+        };
+
+        let mut head = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+            response.status,
+            response.body.len()
+        );
+        for (k, v) in &response.headers {
+            head.push_str(&format!("{}: {}\r\n", k, v));
+        }
+        thread::sleep(response.delay);
+        lock(&state).in_flight -= 1;
+        write!(stream, "{}\r\n{}", head, response.body)?;
+    }
+}
+
+// what HEC would say to `request`. a bad token is turned away before anything else, then it's
+// whatever was queued up with respond_with, and only then the usual answer.
+fn respond(state: &mut State, request: &ReceivedRequest) -> MockResponse {
+    let needs_token = request.is_events() || request.path == ACK_PATH;
+    if let (true, Some(token)) = (needs_token, &state.token) {
+        match request.header("Authorization") {
+            None => return MockResponse::status(401, r#"{"text":"Token is required","code":2}"#),
+            Some(given) if given != format!("Splunk {}", token) => {
+                return MockResponse::status(403, r#"{"text":"Invalid token","code":4}"#)
+            }
+            Some(_) => {}
+        }
+    }
+    if let Some(response) = state.responses.pop_front() {
+        return response;
+    }
+    let Some(acks) = &mut state.acks else {
+        return MockResponse::success();
+    };
+    if (request.is_events() || request.path == ACK_PATH)
+        && request.header("X-Splunk-Request-Channel").is_none()
+    {
+        return MockResponse::status(400, r#"{"text":"Data channel is missing","code":10}"#);
+    }
+    if request.is_events() {
+        let ack_id = acks.next_id;
+        acks.next_id += 1;
+        if acks.holding {
+            acks.held.insert(ack_id);
+        }
+        let body = format!(r#"{{"text":"Success","code":0,"ackId":{}}}"#, ack_id);
+        return MockResponse::status(200, &body);
+    }
+    if request.path == ACK_PATH {
+        let queried = serde_json::from_str::<Value>(&request.body).unwrap_or_default();
+        let statuses: serde_json::Map<String, Value> = queried["acks"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_u64)
+            .map(|id| {
+                let indexed = id < acks.next_id && !acks.held.contains(&id);
+                (id.to_string(), indexed.into())
+            })
+            .collect();
+        return MockResponse::status(200, &serde_json::json!({ "acks": statuses }).to_string());
+    }
+    MockResponse::success()
+}
@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Serializer};
+
+// how many digits after the decimal point the HEC `time` field gets
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimestampPrecision {
+    Seconds,
+    #[default]
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimestampPrecision {
+    fn digits(self) -> usize {
+        match self {
+            TimestampPrecision::Seconds => 0,
+            TimestampPrecision::Milliseconds => 3,
+            TimestampPrecision::Microseconds => 6,
+            TimestampPrecision::Nanoseconds => 9,
+        }
+    }
+}
+
+// what a span's elapsed time is counted in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ElapsedUnit {
+    Seconds,
+    #[default]
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl ElapsedUnit {
+    fn nanos(self) -> u128 {
+        match self {
+            ElapsedUnit::Seconds => 1_000_000_000,
+            ElapsedUnit::Milliseconds => 1_000_000,
+            ElapsedUnit::Microseconds => 1_000,
+            ElapsedUnit::Nanoseconds => 1,
+        }
+    }
+}
+
+// how long a span was open for, and how that gets recorded on it. whole milliseconds under
+// `elapsed_time` unless told otherwise.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ElapsedTime {
+    pub field: String,
+    pub unit: ElapsedUnit,
+    // a float with whatever is left over after the last whole unit, instead of an integer
+    pub fractional: bool,
+}
+
+impl Default for ElapsedTime {
+    fn default() -> Self {
+        ElapsedTime {
+            field: "elapsed_time".to_string(),
+            unit: ElapsedUnit::default(),
+            fractional: false,
+        }
+    }
+}
+
+impl ElapsedTime {
+    // what a span that was open for `elapsed` gets recorded as
+    pub fn value(&self, elapsed: Duration) -> serde_json::Value {
+        let nanos = elapsed.as_nanos();
+        let per_unit = self.unit.nanos();
+        if self.fractional {
+            // f64 keeps ~15 significant digits, more than this needs for anything but spans that
+            // are open for months measured in nanoseconds
+            return (nanos as f64 / per_unit as f64).into();
+        }
+        crate::number::from_u128(nanos / per_unit)
+    }
+
+    // `bound` in this unit for naming something by, with whatever is left over past the last whole
+    // unit even when values aren't fractional, so 1ms and 5ms aren't both "0" in seconds
+    pub(crate) fn label(&self, bound: Duration) -> String {
+        (bound.as_nanos() as f64 / self.unit.nanos() as f64).to_string()
+    }
+}
+
+// where a span's time went, kept the same way fmt::Layer does. the clock starts when the span is
+// created, and an async span is entered and exited at every await, so it's busy while it's entered
+// and idle the rest of the time.
+pub(crate) struct SpanTimings {
+    started: Instant,
+    last: Instant,
+    busy: Duration,
+    idle: Duration,
+    // how long the span sat around before anyone entered it, e.g. a future waiting to be polled
+    first_enter: Option<Duration>,
+    // the same span can be entered more than once at a time, it's only idle once they've all exited
+    entered: usize,
+}
+
+// what a span's time added up to once it closed
+#[derive(Default)]
+pub(crate) struct SpanTimes {
+    pub(crate) elapsed: Duration,
+    pub(crate) busy: Duration,
+    pub(crate) idle: Duration,
+    // None for spans that were never entered
+    pub(crate) time_to_first_enter: Option<Duration>,
+}
+
+impl SpanTimings {
+    // all of these are handed the time by the layer, from its Clock
+    pub(crate) fn start(now: Instant) -> Self {
+        SpanTimings {
+            started: now,
+            last: now,
+            busy: Duration::ZERO,
+            idle: Duration::ZERO,
+            first_enter: None,
+            entered: 0,
+        }
+    }
+
+    pub(crate) fn enter(&mut self, now: Instant) {
+        if self.entered == 0 {
+            self.idle += now - self.last;
+            self.last = now;
+            self.first_enter.get_or_insert(now - self.started);
+        }
+        self.entered += 1;
+    }
+
+    pub(crate) fn exit(&mut self, now: Instant) {
+        self.entered = self.entered.saturating_sub(1);
+        if self.entered == 0 {
+            self.busy += now - self.last;
+            self.last = now;
+        }
+    }
+
+    // how long it's been open so far
+    pub(crate) fn elapsed(&self, now: Instant) -> Duration {
+        now - self.started
+    }
+
+    pub(crate) fn close(mut self, now: Instant) -> SpanTimes {
+        if self.entered == 0 {
+            self.idle += now - self.last;
+        } else {
+            self.busy += now - self.last;
+        }
+        SpanTimes {
+            elapsed: now - self.started,
+            busy: self.busy,
+            idle: self.idle,
+            time_to_first_enter: self.first_enter,
+        }
+    }
+}
+
+// an epoch timestamp in the form HEC wants it, fractional seconds since the epoch. we hang on to
+// the whole seconds and nanoseconds separately since an f64 can't hold nanosecond precision for
+// present day timestamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HecTime {
+    secs: u64,
+    nanos: u32,
+    precision: TimestampPrecision,
+}
+
+impl HecTime {
+    // None for times before the epoch, which HEC has no way to represent
+    pub fn new(time: SystemTime, precision: TimestampPrecision) -> Option<Self> {
+        let since_epoch = time.duration_since(UNIX_EPOCH).ok()?;
+        let truncate_to = 10u32.pow(9 - precision.digits() as u32);
+        Some(HecTime {
+            secs: since_epoch.as_secs(),
+            nanos: since_epoch.subsec_nanos() / truncate_to * truncate_to,
+            precision,
+        })
+    }
+
+    pub fn now(precision: TimestampPrecision) -> Option<Self> {
+        HecTime::new(SystemTime::now(), precision)
+    }
+
+    pub fn as_secs_f64(&self) -> f64 {
+        self.secs as f64 + f64::from(self.nanos) / 1e9
+    }
+}
+
+impl Serialize for HecTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let digits = self.precision.digits();
+        if digits == 0 {
+            return serializer.serialize_u64(self.secs);
+        }
+
+        // write the number out ourselves so we don't lose any digits going through an f64
+        let fraction = self.nanos / 10u32.pow(9 - digits as u32);
+        let number = format!("{}.{:0width$}", self.secs, fraction, width = digits);
+        serde_json::value::RawValue::from_string(number)
+            .map_err(serde::ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt;
+
+// which TLS implementation the built in transports use. each one is behind the cargo feature of the
+// same name, rustls being the default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TlsBackend {
+    Rustls,
+    // the platform's own TLS library, e.g. openssl on linux and schannel on windows
+    NativeTls,
+}
+
+impl Default for TlsBackend {
+    fn default() -> Self {
+        if cfg!(feature = "rustls") || !cfg!(feature = "native-tls") {
+            TlsBackend::Rustls
+        } else {
+            TlsBackend::NativeTls
+        }
+    }
+}
+
+impl TlsBackend {
+    #[cfg(any(feature = "rustls", feature = "native-tls"))]
+    pub(crate) fn is_enabled(&self) -> bool {
+        match self {
+            TlsBackend::Rustls => cfg!(feature = "rustls"),
+            TlsBackend::NativeTls => cfg!(feature = "native-tls"),
+        }
+    }
+}
+
+// a certificate chain and the private key that goes with it, both PEM encoded, for HEC inputs that
+// want clients to authenticate with a certificate (mTLS)
+#[derive(Clone, PartialEq, Eq)]
+pub struct ClientIdentity {
+    pub cert_chain: Vec<u8>,
+    pub private_key: Vec<u8>,
+}
+
+// keep the key out of debug output
+impl fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientIdentity")
+            .field("cert_chain", &String::from_utf8_lossy(&self.cert_chain))
+            .finish_non_exhaustive()
+    }
+}
+
+// how the built in transports set up TLS, for HEC inputs behind an internal CA or that require
+// client certificates. a custom transport is on its own here.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsConfig {
+    pub backend: TlsBackend,
+    // PEM encoded CA certificates, each of which can hold a whole bundle. when there are any
+    // they're the only roots that are trusted, the built in ones are left out.
+    pub root_certificates: Vec<Vec<u8>>,
+    pub client_identity: Option<ClientIdentity>,
+    // don't check the server's certificate at all. this throws away everything TLS is there for,
+    // so only ever reach for it to get a test environment going, never in production.
+    pub danger_accept_invalid_certs: bool,
+}
+
+impl TlsConfig {
+    pub fn new() -> Self {
+        TlsConfig::default()
+    }
+
+    // trust the CA certificates in `pem` instead of the built in roots
+    pub fn root_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    // present this certificate chain to the server
+    pub fn client_identity(
+        mut self,
+        cert_chain: impl Into<Vec<u8>>,
+        private_key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.client_identity = Some(ClientIdentity {
+            cert_chain: cert_chain.into(),
+            private_key: private_key.into(),
+        });
+        self
+    }
+}
+
+// a TlsConfig that the transport couldn't make sense of
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TlsError {
+    // the crate was built without the feature for this backend
+    BackendUnavailable(TlsBackend),
+    InvalidRootCertificate(String),
+    InvalidClientIdentity(String),
+    // the backend itself wouldn't start with this config
+    Setup(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsError::BackendUnavailable(TlsBackend::Rustls) => {
+                write!(f, "TLS with rustls needs the `rustls` feature")
+            }
+            TlsError::BackendUnavailable(TlsBackend::NativeTls) => {
+                write!(f, "TLS with native-tls needs the `native-tls` feature")
+            }
+            TlsError::InvalidRootCertificate(e) => {
+                write!(f, "failed to load a root certificate: {}", e)
+            }
+            TlsError::InvalidClientIdentity(e) => {
+                write!(f, "failed to load the client certificate: {}", e)
+            }
+            TlsError::Setup(e) => write!(f, "failed to set up TLS: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
@@ -0,0 +1,143 @@
+use std::fmt;
+
+use tracing::field::{Field, Visit};
+use tracing::span;
+
+// the ids a span is exported under. the trace id is made up at the root and inherited by every
+// span under it, so a request's whole tree can be put back together in splunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SpanIds {
+    pub(crate) trace_id: u128,
+    // tracing's own id for the span, unless tracing-opentelemetry gave it one
+    pub(crate) span_id: u64,
+    // the upstream service's span, for a root span that was handed a traceparent
+    pub(crate) remote_parent: Option<u64>,
+}
+
+impl SpanIds {
+    pub(crate) fn root(id: &span::Id) -> Self {
+        SpanIds {
+            // all zeroes isn't a valid trace id as far as w3c trace context is concerned
+            trace_id: fastrand::u128(1..),
+            span_id: id.into_u64(),
+            remote_parent: None,
+        }
+    }
+
+    pub(crate) fn child(&self, id: &span::Id) -> Self {
+        SpanIds {
+            trace_id: self.trace_id,
+            span_id: id.into_u64(),
+            remote_parent: None,
+        }
+    }
+
+    // carry on the trace an upstream service started
+    pub(crate) fn continue_from(&mut self, parent: &TraceParent) {
+        self.trace_id = parent.trace_id;
+        self.remote_parent = Some(parent.parent_id);
+    }
+
+    // 32 lowercase hex digits, the same as everything else that speaks trace context
+    pub(crate) fn trace_id_hex(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+
+    // 16 hex digits, to match
+    pub(crate) fn span_id_hex(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+}
+
+// a w3c `traceparent` header (https://www.w3.org/TR/trace-context/#traceparent-header), e.g.
+// 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01
+//
+// a span with a `traceparent` field joins the upstream trace instead of starting its own, and
+// gets the upstream span as its `parent_span_id`:
+//
+//   info_span!("request", traceparent = %header)
+//
+// or `traceparent = tracing::field::Empty` and span.record("traceparent", header) later on, as
+// long as it's before any child spans are made
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceParent {
+    pub trace_id: u128,
+    pub parent_id: u64,
+    pub flags: u8,
+}
+
+// the field the layer looks for a traceparent in
+const TRACEPARENT_FIELD: &str = "traceparent";
+
+impl TraceParent {
+    // None for anything that isn't a valid traceparent, in which case the span just starts a trace
+    // of its own
+    pub fn parse(header: &str) -> Option<Self> {
+        let mut parts = header.trim().split('-');
+        let version = hex(parts.next()?, 2)?;
+        let trace_id = hex(parts.next()?, 32)?;
+        let parent_id = hex(parts.next()?, 16)?;
+        let flags = hex(parts.next()?, 2)?;
+        // later versions are allowed to add fields on the end, version 00 isn't
+        if version == 0xff || (version == 0 && parts.next().is_some()) {
+            return None;
+        }
+        if trace_id == 0 || parent_id == 0 {
+            return None;
+        }
+        Some(TraceParent {
+            trace_id,
+            parent_id: parent_id as u64,
+            flags: flags as u8,
+        })
+    }
+
+    // the first `traceparent` in a set of request headers, whatever case its name is in
+    pub fn from_headers<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<Self> {
+        headers
+            .into_iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(TRACEPARENT_FIELD))
+            .and_then(|(_, value)| TraceParent::parse(value))
+    }
+
+    pub fn sampled(&self) -> bool {
+        self.flags & 1 == 1
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+// exactly `digits` lowercase hex digits, which is all trace context allows
+fn hex(s: &str, digits: usize) -> Option<u128> {
+    if s.len() != digits || !s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+    u128::from_str_radix(s, 16).ok()
+}
+
+// picks a traceparent out of a span's fields
+#[derive(Default)]
+pub(crate) struct FindTraceParent(pub(crate) Option<TraceParent>);
+
+impl Visit for FindTraceParent {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == TRACEPARENT_FIELD {
+            self.0 = TraceParent::parse(value);
+        }
+    }
+
+    // `traceparent = %header` comes through here
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == TRACEPARENT_FIELD {
+            self.0 = TraceParent::parse(&format!("{:?}", value));
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::batch::Batch;
+use crate::hec::{HecError, HecResponse};
+use crate::transport::{Transport, TransportFuture};
+
+pub const DEFAULT_FILE_MAX_BYTES: u64 = 100 * 1024 * 1024;
+pub const DEFAULT_FILE_MAX_FILES: usize = 10;
+
+// where FileTransport writes, and when it starts a new file. rotated files are renamed to the
+// path with a number on the end (`events.json.1`, `events.json.2`, ..., the highest is the
+// newest), so a forwarder's monitor stanza for `events.json*` picks them all up and the file it's
+// tailing is always the same name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileConfig {
+    pub path: PathBuf,
+    // rotate once the file is this big. None never rotates by size.
+    pub max_bytes: Option<u64>,
+    // rotate once the file has been written to for this long, None never rotates by age
+    pub max_age: Option<Duration>,
+    // how many rotated files to keep, the oldest are deleted past that. None keeps them all.
+    pub max_files: Option<usize>,
+    // gzip rotated files, which then end in `.gz`
+    pub compress: bool,
+}
+
+impl FileConfig {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileConfig {
+            path: path.into(),
+            max_bytes: Some(DEFAULT_FILE_MAX_BYTES),
+            max_age: None,
+            max_files: Some(DEFAULT_FILE_MAX_FILES),
+            compress: false,
+        }
+    }
+}
+
+// writes batches to a file as newline delimited json, every event exactly as it would have been
+// sent to HEC, for a universal forwarder to tail when the application can't reach HEC itself.
+// the file is appended to if it's already there, and created (directory and all) if it isn't.
+pub struct FileTransport {
+    config: FileConfig,
+    // opened on the first batch, so the builder doesn't need to touch the disk
+    current: Mutex<Option<Current>>,
+}
+
+struct Current {
+    file: BufWriter<File>,
+    bytes: u64,
+    opened: Instant,
+}
+
+impl FileTransport {
+    pub fn new(config: FileConfig) -> Self {
+        FileTransport {
+            config,
+            current: Mutex::new(None),
+        }
+    }
+
+    fn write(&self, batch: &Batch) -> io::Result<()> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        if current.as_ref().is_some_and(|c| self.due(c)) {
+            if let Some(mut full) = current.take() {
+                full.file.flush()?;
+            }
+            self.rotate()?;
+        }
+        let current = match &mut *current {
+            Some(current) => current,
+            None => current.insert(self.open()?),
+        };
+        current.file.write_all(batch.as_str().as_bytes())?;
+        current.file.write_all(b"\n")?;
+        current.file.flush()?;
+        current.bytes += batch.as_str().len() as u64 + 1;
+        Ok(())
+    }
+
+    // a file that's empty isn't worth rotating, however old it is
+    fn due(&self, current: &Current) -> bool {
+        let too_big = self
+            .config
+            .max_bytes
+            .is_some_and(|max| current.bytes >= max);
+        let too_old = self
+            .config
+            .max_age
+            .is_some_and(|max| current.opened.elapsed() >= max);
+        current.bytes > 0 && (too_big || too_old)
+    }
+
+    fn open(&self) -> io::Result<Current> {
+        if let Some(dir) = self.config.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.path)?;
+        let bytes = file.metadata()?.len();
+        Ok(Current {
+            file: BufWriter::new(file),
+            bytes,
+            opened: Instant::now(),
+        })
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        let mut rotated = self.rotated()?;
+        let next = rotated.last().map_or(1, |(n, _)| n + 1);
+        let to = numbered(&self.config.path, next);
+        fs::rename(&self.config.path, &to)?;
+        let to = if self.config.compress {
+            compress(&to)?
+        } else {
+            to
+        };
+        rotated.push((next, to));
+
+        if let Some(max) = self.config.max_files {
+            let excess = rotated.len().saturating_sub(max);
+            for (_, path) in rotated.drain(..excess) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // the files rotated so far, oldest first
+    fn rotated(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        let path = &self.config.path;
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return Ok(Vec::new());
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let mut rotated = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let Some(suffix) = name
+                .to_string_lossy()
+                .strip_prefix(&prefix)
+                .map(str::to_owned)
+            else {
+                continue;
+            };
+            let number = suffix.strip_suffix(".gz").unwrap_or(&suffix);
+            if let Ok(n) = number.parse() {
+                rotated.push((n, entry.path()));
+            }
+        }
+        rotated.sort();
+        Ok(rotated)
+    }
+}
+
+fn numbered(path: &Path, n: u64) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+// gzip `path` next to itself and remove the original
+fn compress(path: &Path) -> io::Result<PathBuf> {
+    let mut gz = path.as_os_str().to_owned();
+    gz.push(".gz");
+    let gz = PathBuf::from(gz);
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(&gz)?), Compression::default());
+    io::copy(&mut BufReader::new(File::open(path)?), &mut encoder)?;
+    encoder.finish()?.flush()?;
+    fs::remove_file(path)?;
+    Ok(gz)
+}
+
+impl Transport for FileTransport {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let result = self
+            .write(batch)
+            .map(|()| HecResponse::success())
+            .map_err(HecError::transport);
+        Box::pin(std::future::ready(result))
+    }
+}
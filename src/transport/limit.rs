@@ -0,0 +1,64 @@
+#[cfg(any(feature = "ureq", feature = "reqwest"))]
+use std::sync::Arc;
+#[cfg(feature = "ureq")]
+use std::sync::{Condvar, Mutex};
+
+// a cap on how many requests can be going at once, shared by every transport it's handed to (the
+// builder gives the same one to the default transport and every tenant's route). a worker only
+// ever has one batch in flight, but after an outage they'd all be catching up at once.
+#[derive(Clone)]
+pub(crate) struct InFlightLimit {
+    // how many more requests can start right now, for transports that block the worker thread
+    #[cfg(feature = "ureq")]
+    free: Arc<(Mutex<usize>, Condvar)>,
+    #[cfg(feature = "reqwest")]
+    semaphore: Arc<tokio::sync::Semaphore>,
+}
+
+impl InFlightLimit {
+    // at least one, or nothing would ever be sent
+    pub(crate) fn new(max: usize) -> Self {
+        let max = max.max(1);
+        // which is unused with neither http transport around
+        let _ = max;
+        InFlightLimit {
+            #[cfg(feature = "ureq")]
+            free: Arc::new((Mutex::new(max), Condvar::new())),
+            #[cfg(feature = "reqwest")]
+            semaphore: Arc::new(tokio::sync::Semaphore::new(max)),
+        }
+    }
+
+    // block until there's room for another request. the requests waiting go out one at a time as
+    // the ones ahead of them finish.
+    #[cfg(feature = "ureq")]
+    pub(crate) fn wait(&self) -> Slot<'_> {
+        let (free, finished) = &*self.free;
+        let mut free = free.lock().unwrap_or_else(|e| e.into_inner());
+        while *free == 0 {
+            free = finished.wait(free).unwrap_or_else(|e| e.into_inner());
+        }
+        *free -= 1;
+        Slot(self)
+    }
+
+    // the same for a request running on a tokio runtime, where waiting shouldn't block a thread
+    #[cfg(feature = "reqwest")]
+    pub(crate) async fn acquire(self) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        // the semaphore is never closed, so this only ever gets a permit
+        self.semaphore.acquire_owned().await.ok()
+    }
+}
+
+// room for one request, given back when it's dropped
+#[cfg(feature = "ureq")]
+pub(crate) struct Slot<'a>(&'a InFlightLimit);
+
+#[cfg(feature = "ureq")]
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        let (free, finished) = &*self.0.free;
+        *free.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        finished.notify_one();
+    }
+}
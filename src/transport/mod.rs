@@ -0,0 +1,159 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+use crate::ack::AckStatus;
+use crate::batch::Batch;
+use crate::hec::{HecError, HecResponse};
+use crate::probe::ProbeError;
+
+mod file;
+mod limit;
+#[cfg(feature = "reqwest")]
+mod reqwest;
+mod socket;
+#[cfg(feature = "ureq")]
+mod ureq;
+mod writer;
+
+pub use self::file::{FileConfig, FileTransport, DEFAULT_FILE_MAX_BYTES, DEFAULT_FILE_MAX_FILES};
+#[cfg(feature = "reqwest")]
+pub(crate) use self::reqwest::client as reqwest_client;
+#[cfg(feature = "reqwest")]
+pub use self::reqwest::ReqwestTransport;
+pub(crate) use self::socket::SocketInput;
+pub use self::socket::{TcpTransport, UdpTransport};
+#[cfg(unix)]
+pub use self::socket::{UnixDatagramTransport, UnixStreamTransport};
+#[cfg(feature = "ureq")]
+pub use self::ureq::UreqTransport;
+pub use self::writer::WriterTransport;
+
+pub(crate) use self::limit::InFlightLimit;
+
+// how long the built in http transports wait to connect to HEC, and for a whole request
+// (connecting included) to finish, before counting it as failed and leaving it to the retry policy
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// how long a connection can sit unused in the pool before it's closed rather than reused for the
+// next batch. splunk closes idle keep-alive connections after 12 seconds by default
+// (busyKeepAliveIdleTimeout), so this is a little under that to be the one that hangs up first.
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Timeouts {
+    pub(crate) connect: Duration,
+    pub(crate) request: Duration,
+    pub(crate) pool_idle: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: DEFAULT_CONNECT_TIMEOUT,
+            request: DEFAULT_REQUEST_TIMEOUT,
+            pool_idle: DEFAULT_POOL_IDLE_TIMEOUT,
+        }
+    }
+}
+
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HecResponse, HecError>> + Send + 'a>>;
+pub type AckFuture<'a> = Pin<Box<dyn Future<Output = Result<AckStatus, HecError>> + Send + 'a>>;
+pub type ProbeFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ProbeError>> + Send + 'a>>;
+
+// whatever actually gets a batch to splunk. the built in transports are behind the `blocking`
+// (ureq, the default) and `reqwest` features, but anything that can POST a body can be plugged in
+// with SplunkHecLayerBuilder::transport, be it hyper, an in house client or a test double. for
+// sinks that aren't http at all there's WriterTransport, FileTransport for a forwarder to tail,
+// and TcpTransport and UdpTransport for plain splunk network inputs (or their unix socket cousins
+// for a forwarder on the same host).
+//
+// send is async so async clients fit naturally, but it's driven from the exporter's worker thread
+// so a blocking client is free to just do its I/O and return a ready future. returning
+// HecError::Status for anything HEC rejected lets the retry policy tell what's worth retrying.
+pub trait Transport: Send + Sync + 'static {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a>;
+
+    // ask HEC which of `ack_ids` have been indexed, for indexer acknowledgment. only transports
+    // that send the X-Splunk-Request-Channel header can take part in that, so by default this
+    // just says it isn't supported.
+    fn query_acks<'a>(&'a self, ack_ids: &'a [u64]) -> AckFuture<'a> {
+        let _ = ack_ids;
+        Box::pin(std::future::ready(Err(HecError::transport(
+            "this transport doesn't support indexer acknowledgment",
+        ))))
+    }
+
+    // check that HEC is up and takes our token, for SplunkHecLayerBuilder::startup_probe. only a
+    // transport that actually talks to HEC can tell, so by default this just says it's fine.
+    fn probe(&self) -> ProbeFuture<'_> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    // start sending `token` instead of the one it was made with, for ConfigHandle::set_token.
+    // the worker only calls this between batches. returns false if the transport has no token
+    // to change, which is the default.
+    fn set_token(&mut self, token: &str) -> bool {
+        let _ = token;
+        false
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        (**self).send(batch)
+    }
+
+    fn query_acks<'a>(&'a self, ack_ids: &'a [u64]) -> AckFuture<'a> {
+        (**self).query_acks(ack_ids)
+    }
+
+    fn probe(&self) -> ProbeFuture<'_> {
+        (**self).probe()
+    }
+
+    fn set_token(&mut self, token: &str) -> bool {
+        (**self).set_token(token)
+    }
+}
+
+// a shared transport can't be changed through the Arc, so it keeps the default set_token
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        (**self).send(batch)
+    }
+
+    fn query_acks<'a>(&'a self, ack_ids: &'a [u64]) -> AckFuture<'a> {
+        (**self).query_acks(ack_ids)
+    }
+
+    fn probe(&self) -> ProbeFuture<'_> {
+        (**self).probe()
+    }
+}
+
+// the worker thread has nothing else to do while a batch is in flight, so a bare bones executor
+// that parks until it's woken is all we need to drive a transport's future
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
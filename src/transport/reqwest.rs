@@ -0,0 +1,366 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::ack::random_channel;
+use crate::ack::AckStatus;
+use crate::batch::Batch;
+use crate::builder::BuildError;
+use crate::hec::{self, HecError, HecMetadata, HecResponse};
+use crate::internal::{self, Internal};
+use crate::probe::{self, ProbeError};
+use crate::proxy::Proxy;
+use crate::tls::{TlsBackend, TlsConfig, TlsError};
+use crate::transport::{
+    AckFuture, InFlightLimit, ProbeFuture, Timeouts, Transport, TransportFuture,
+};
+
+// where the requests actually run. reqwest needs a tokio reactor, which the worker thread doesn't
+// have, so requests are spawned onto a runtime and the worker just waits on the JoinHandle.
+#[derive(Clone)]
+enum RuntimeHandle {
+    Borrowed(Handle),
+    Owned(Arc<Runtime>),
+}
+
+impl RuntimeHandle {
+    fn handle(&self) -> &Handle {
+        match self {
+            RuntimeHandle::Borrowed(handle) => handle,
+            RuntimeHandle::Owned(runtime) => runtime.handle(),
+        }
+    }
+}
+
+// an async transport built on reqwest, with connection pooling shared with the given runtime
+#[derive(Clone)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+    endpoint: String,
+    url: String,
+    ack_url: String,
+    health_url: String,
+    authorization: String,
+    user_agent: String,
+    // sent with every request, see SplunkHecLayerBuilder::header
+    headers: Vec<(String, String)>,
+    channel: Option<String>,
+    content_type: &'static str,
+    limit: Option<InFlightLimit>,
+    runtime: RuntimeHandle,
+}
+
+impl ReqwestTransport {
+    // uses the ambient tokio runtime if there is one, otherwise starts a small one of its own
+    pub fn new(endpoint: &str, token: &str) -> std::io::Result<Self> {
+        let runtime = match Handle::try_current() {
+            Ok(handle) => RuntimeHandle::Borrowed(handle),
+            Err(_) => RuntimeHandle::Owned(Arc::new(
+                tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .thread_name("splunk-hec-reqwest")
+                    .on_thread_start(internal::mark_thread)
+                    .enable_all()
+                    .build()?,
+            )),
+        };
+        Ok(ReqwestTransport::with_client(
+            client(None, &Proxy::FromEnv, Timeouts::default()).map_err(std::io::Error::other)?,
+            endpoint,
+            token,
+            runtime,
+        ))
+    }
+
+    // send requests with `client` on the runtime behind `handle`
+    pub fn with_runtime(
+        client: reqwest::Client,
+        endpoint: &str,
+        token: &str,
+        handle: Handle,
+    ) -> Self {
+        ReqwestTransport::with_client(client, endpoint, token, RuntimeHandle::Borrowed(handle))
+    }
+
+    fn with_client(
+        client: reqwest::Client,
+        endpoint: &str,
+        token: &str,
+        runtime: RuntimeHandle,
+    ) -> Self {
+        ReqwestTransport {
+            client,
+            endpoint: endpoint.to_owned(),
+            url: hec::event_url(endpoint),
+            ack_url: hec::ack_url(endpoint),
+            health_url: hec::health_url(endpoint),
+            authorization: hec::authorization(token),
+            user_agent: hec::USER_AGENT.to_owned(),
+            headers: Vec::new(),
+            channel: None,
+            content_type: "application/json",
+            limit: None,
+            runtime,
+        }
+    }
+
+    // send every request on this X-Splunk-Request-Channel, which HEC requires for tokens with
+    // indexer acknowledgment turned on
+    pub fn with_channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    // send `name: value` with every request, e.g. for a gateway in front of HEC that wants a
+    // header of its own
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    // instead of tracing-splunk-layer/<version>
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    // have at most `max` requests going at once, between this transport and any clones of it. the
+    // rest wait their turn on the runtime without holding a connection open.
+    pub fn with_max_in_flight(self, max: usize) -> Self {
+        self.with_limit(InFlightLimit::new(max))
+    }
+
+    pub(crate) fn with_limit(mut self, limit: InFlightLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    // what has to be held while a request is going, if anything
+    fn permit(&self) -> impl Future<Output = Option<OwnedSemaphorePermit>> + Send + 'static {
+        let limit = self.limit.clone();
+        async move {
+            match limit {
+                Some(limit) => limit.acquire().await,
+                None => None,
+            }
+        }
+    }
+
+    // send batches to the raw endpoint, tagged with `metadata`, instead of the event one. raw
+    // requests have to be on a channel, so this makes one up unless it's been given one.
+    pub fn raw(mut self, metadata: &HecMetadata) -> Self {
+        self.url = hec::raw_url(&self.endpoint, metadata);
+        self.channel.get_or_insert_with(random_channel);
+        self.content_type = "text/plain";
+        self
+    }
+
+    fn post(&self, url: &str, content_type: &str, body: String) -> reqwest::RequestBuilder {
+        let mut request = self
+            .headers(self.client.post(url))
+            .header("Authorization", &self.authorization)
+            .header("Content-Type", content_type);
+        if let Some(channel) = &self.channel {
+            request = request.header("X-Splunk-Request-Channel", channel);
+        }
+        request.body(body)
+    }
+
+    // what goes on every request, authorized or not
+    fn headers(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request = request.header("User-Agent", &self.user_agent);
+        self.headers.iter().fold(request, |request, (name, value)| {
+            request.header(name, value)
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+// a client with TLS set up by `tls`, or the default backend if there's no config
+pub(crate) fn client(
+    tls: Option<&TlsConfig>,
+    proxy: &Proxy,
+    timeouts: Timeouts,
+) -> Result<reqwest::Client, BuildError> {
+    let builder = match tls {
+        Some(tls) => configure(reqwest::Client::builder(), tls).map_err(BuildError::Tls)?,
+        None => use_backend(reqwest::Client::builder(), TlsBackend::default()),
+    };
+    // connections are pooled and kept alive between batches. with the http2 feature HTTP/2 is
+    // offered during the TLS handshake too, and used if HEC takes it up.
+    let builder = with_proxy(builder, proxy)?
+        .connect_timeout(timeouts.connect)
+        .timeout(timeouts.request)
+        .pool_idle_timeout(timeouts.pool_idle);
+    builder
+        .build()
+        .map_err(|e| BuildError::Tls(TlsError::Setup(e.to_string())))
+}
+
+// reqwest reads the environment unless it's given a proxy, or told not to use one
+fn with_proxy(
+    builder: reqwest::ClientBuilder,
+    proxy: &Proxy,
+) -> Result<reqwest::ClientBuilder, BuildError> {
+    let proxy = match proxy {
+        Proxy::FromEnv => return Ok(builder),
+        Proxy::Direct => return Ok(builder.no_proxy()),
+        Proxy::Custom(proxy) => proxy,
+    };
+    let mut custom =
+        reqwest::Proxy::all(&proxy.url).map_err(|e| BuildError::Proxy(e.to_string()))?;
+    if let Some(credentials) = &proxy.credentials {
+        custom = custom.basic_auth(&credentials.username, &credentials.password);
+    }
+    let no_proxy = proxy
+        .no_proxy_hosts()
+        .and_then(|hosts| reqwest::NoProxy::from_string(&hosts));
+    Ok(builder.proxy(custom.no_proxy(no_proxy)))
+}
+
+// reqwest picks native-tls over rustls when it has both, we'd rather it was up to TlsBackend
+fn use_backend(builder: reqwest::ClientBuilder, backend: TlsBackend) -> reqwest::ClientBuilder {
+    #[cfg(feature = "rustls")]
+    if backend == TlsBackend::Rustls {
+        return builder.use_rustls_tls();
+    }
+    #[cfg(feature = "native-tls")]
+    if backend == TlsBackend::NativeTls {
+        return builder.use_native_tls();
+    }
+    let _ = backend;
+    builder
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn configure(
+    builder: reqwest::ClientBuilder,
+    tls: &TlsConfig,
+) -> Result<reqwest::ClientBuilder, TlsError> {
+    if !tls.backend.is_enabled() {
+        return Err(TlsError::BackendUnavailable(tls.backend));
+    }
+    let mut builder = use_backend(builder, tls.backend)
+        .danger_accept_invalid_certs(tls.danger_accept_invalid_certs);
+
+    if !tls.root_certificates.is_empty() {
+        builder = builder.tls_built_in_root_certs(false);
+        for pem in &tls.root_certificates {
+            let certs = reqwest::Certificate::from_pem_bundle(pem)
+                .map_err(|e| TlsError::InvalidRootCertificate(e.to_string()))?;
+            if certs.is_empty() {
+                return Err(TlsError::InvalidRootCertificate(
+                    "no certificates in the PEM".to_owned(),
+                ));
+            }
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+    }
+    if let Some(identity) = &tls.client_identity {
+        builder = builder.identity(
+            client_identity(tls.backend, identity)
+                .map_err(|e| TlsError::InvalidClientIdentity(e.to_string()))?,
+        );
+    }
+    Ok(builder)
+}
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+fn configure(
+    _builder: reqwest::ClientBuilder,
+    tls: &TlsConfig,
+) -> Result<reqwest::ClientBuilder, TlsError> {
+    Err(TlsError::BackendUnavailable(tls.backend))
+}
+
+// each backend wants the key and certificates handed over differently
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn client_identity(
+    backend: TlsBackend,
+    identity: &crate::tls::ClientIdentity,
+) -> reqwest::Result<reqwest::Identity> {
+    #[cfg(feature = "rustls")]
+    if backend == TlsBackend::Rustls {
+        let mut pem = identity.private_key.clone();
+        pem.push(b'\n');
+        pem.extend_from_slice(&identity.cert_chain);
+        return reqwest::Identity::from_pem(&pem);
+    }
+    #[cfg(feature = "native-tls")]
+    if backend == TlsBackend::NativeTls {
+        return reqwest::Identity::from_pkcs8_pem(&identity.cert_chain, &identity.private_key);
+    }
+    unreachable!("the backend was checked to be enabled")
+}
+
+impl Transport for ReqwestTransport {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let request = self.post(&self.url, self.content_type, batch.as_str().to_owned());
+        let permit = self.permit();
+
+        // the runtime might be the application's, so the request itself has to be marked as ours
+        let task = self.runtime.handle().spawn(Internal(Box::pin(async move {
+            let _permit = permit.await;
+            let response = request.send().await.map_err(HecError::transport)?;
+            let status = response.status().as_u16();
+            let retry_after = response
+                .headers()
+                .get("retry-after")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned);
+            let body = response.text().await.unwrap_or_default();
+            HecResponse::parse(status, retry_after.as_deref(), &body)
+        })));
+
+        Box::pin(async move { task.await.map_err(HecError::transport)? })
+    }
+
+    fn query_acks<'a>(&'a self, ack_ids: &'a [u64]) -> AckFuture<'a> {
+        let request = self.post(&self.ack_url, "application/json", hec::ack_query(ack_ids));
+        let permit = self.permit();
+
+        let task = self.runtime.handle().spawn(Internal(Box::pin(async move {
+            let _permit = permit.await;
+            let response = request.send().await.map_err(HecError::transport)?;
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            AckStatus::parse(status, &body)
+        })));
+
+        Box::pin(async move { task.await.map_err(HecError::transport)? })
+    }
+
+    fn probe(&self) -> ProbeFuture<'_> {
+        let health = self.headers(self.client.get(&self.health_url));
+        let token = self.post(&self.url, self.content_type, String::new());
+        let unreachable = |e: &dyn std::fmt::Display| ProbeError::Unreachable(e.to_string());
+        let permit = self.permit();
+
+        let task = self.runtime.handle().spawn(Internal(Box::pin(async move {
+            let _permit = permit.await;
+            let response = health.send().await.map_err(|e| unreachable(&e))?;
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            probe::health(status, &body)?;
+
+            let response = token.send().await.map_err(|e| unreachable(&e))?;
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            probe::token(HecResponse::parse(status, None, &body))
+        })));
+
+        Box::pin(async move { task.await.map_err(|e| unreachable(&e))? })
+    }
+
+    fn set_token(&mut self, token: &str) -> bool {
+        self.authorization = hec::authorization(token);
+        true
+    }
+}
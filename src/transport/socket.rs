@@ -0,0 +1,336 @@
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::{UnixDatagram, UnixStream};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::batch::Batch;
+use crate::hec::{HecError, HecResponse};
+use crate::transport::{Timeouts, Transport, TransportFuture};
+
+// how long TcpTransport and UnixStreamTransport wait before connecting again after they couldn't,
+// doubling up to the max
+const RECONNECT_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// writes batches as newline delimited json to a splunk TCP input, every event exactly as it would
+// have been sent to HEC. the input needs a sourcetype that knows what to do with that, since
+// nothing in the line itself is taken as metadata the way HEC takes it.
+//
+// the connection is made on the first batch and kept open. when it breaks the batch fails (and
+// is retried by the retry policy like any other) and the next one connects again, though not
+// until a backoff has passed if connecting failed too, so a dead input isn't hammered.
+pub struct TcpTransport {
+    addr: String,
+    // connect for connecting, request for how long writing a batch can block
+    timeouts: Timeouts,
+    stream: Mutex<Reconnecting<TcpStream>>,
+}
+
+impl TcpTransport {
+    // `addr` is a host:port, looked up again whenever it connects
+    pub fn new(addr: impl Into<String>) -> Self {
+        TcpTransport {
+            addr: addr.into(),
+            timeouts: Timeouts::default(),
+            stream: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+}
+
+// a connection that's made when it's first needed and again after it breaks, backing off while
+// connecting keeps failing
+struct Reconnecting<S> {
+    stream: Option<S>,
+    backoff: Option<Duration>,
+    next_connect: Option<Instant>,
+}
+
+impl<S> Default for Reconnecting<S> {
+    fn default() -> Self {
+        Reconnecting {
+            stream: None,
+            backoff: None,
+            next_connect: None,
+        }
+    }
+}
+
+impl<S: Write> Reconnecting<S> {
+    // write a batch as lines, connecting with `connect` first if there's no connection
+    fn write(&mut self, batch: &Batch, connect: impl FnOnce() -> io::Result<S>) -> io::Result<()> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let stream = self.connect(connect)?;
+                self.stream.insert(stream)
+            }
+        };
+        let written = stream
+            .write_all(batch.as_str().as_bytes())
+            .and_then(|()| stream.write_all(b"\n"))
+            .and_then(|()| stream.flush());
+        // whatever went wrong, the connection can't be trusted with the next batch
+        if written.is_err() {
+            self.stream = None;
+        }
+        written
+    }
+
+    fn connect(&mut self, connect: impl FnOnce() -> io::Result<S>) -> io::Result<S> {
+        if self.next_connect.is_some_and(|at| Instant::now() < at) {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "waiting to connect to the splunk input again",
+            ));
+        }
+        match connect() {
+            Ok(stream) => {
+                self.backoff = None;
+                self.next_connect = None;
+                Ok(stream)
+            }
+            Err(e) => {
+                let backoff = self
+                    .backoff
+                    .map_or(RECONNECT_INITIAL_BACKOFF, |backoff| backoff * 2)
+                    .min(RECONNECT_MAX_BACKOFF);
+                self.backoff = Some(backoff);
+                self.next_connect = Some(Instant::now() + backoff);
+                Err(e)
+            }
+        }
+    }
+}
+
+// the first address `addr` resolves to that takes the connection
+fn connect(addr: &str, timeouts: Timeouts) -> io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeouts.connect) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(timeouts.request))?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("{} didn't resolve", addr))
+    }))
+}
+
+impl Transport for TcpTransport {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        let result = stream
+            .write(batch, || connect(&self.addr, self.timeouts))
+            .map(|()| HecResponse::success())
+            .map_err(HecError::transport);
+        Box::pin(std::future::ready(result))
+    }
+}
+
+// sends every event as a datagram of its own to a splunk UDP input, fire and forget: there's no
+// telling whether anything arrived, so the only failures are ones on this end, like an event too
+// big for a datagram.
+pub struct UdpTransport {
+    addr: String,
+    // only request matters, for how long a send can block on a full socket buffer
+    timeouts: Timeouts,
+    socket: Mutex<Option<UdpSocket>>,
+}
+
+impl UdpTransport {
+    // `addr` is a host:port, looked up when the socket is made
+    pub fn new(addr: impl Into<String>) -> Self {
+        UdpTransport {
+            addr: addr.into(),
+            timeouts: Timeouts::default(),
+            socket: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    fn write(&self, batch: &Batch) -> io::Result<()> {
+        let mut socket = self.socket.lock().unwrap_or_else(|e| e.into_inner());
+        let socket = match &mut *socket {
+            Some(socket) => socket,
+            None => socket.insert(bind(&self.addr, self.timeouts)?),
+        };
+        for line in batch.as_str().lines() {
+            match socket.send(line.as_bytes()) {
+                Ok(_) => {}
+                // an earlier datagram bounced because nothing was listening, which is no concern
+                // of ours
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+// a socket connected to `addr`, so sends only need the datagram
+fn bind(addr: &str, timeouts: Timeouts) -> io::Result<UdpSocket> {
+    let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("{} didn't resolve", addr))
+    })?;
+    let local = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(addr)?;
+    socket.set_write_timeout(Some(timeouts.request))?;
+    Ok(socket)
+}
+
+impl Transport for UdpTransport {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let result = self
+            .write(batch)
+            .map(|()| HecResponse::success())
+            .map_err(HecError::transport);
+        Box::pin(std::future::ready(result))
+    }
+}
+
+// the same as TcpTransport, over a unix socket at `path`, for a forwarder running alongside the
+// application. nothing leaves the host, and there's no token for the application to hold.
+#[cfg(unix)]
+pub struct UnixStreamTransport {
+    path: PathBuf,
+    // connecting is immediate, so only request matters
+    timeouts: Timeouts,
+    stream: Mutex<Reconnecting<UnixStream>>,
+}
+
+#[cfg(unix)]
+impl UnixStreamTransport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixStreamTransport {
+            path: path.into(),
+            timeouts: Timeouts::default(),
+            stream: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixStreamTransport {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        let connect = || {
+            let stream = UnixStream::connect(&self.path)?;
+            stream.set_write_timeout(Some(self.timeouts.request))?;
+            Ok(stream)
+        };
+        let result = stream
+            .write(batch, connect)
+            .map(|()| HecResponse::success())
+            .map_err(HecError::transport);
+        Box::pin(std::future::ready(result))
+    }
+}
+
+// sends every event as a datagram of its own to a unix datagram socket at `path`. unlike UDP it's
+// known straight away when nothing is listening there, so that fails the batch like it would
+// with a stream.
+#[cfg(unix)]
+pub struct UnixDatagramTransport {
+    path: PathBuf,
+    // only request matters, the same as for UdpTransport
+    timeouts: Timeouts,
+    socket: Mutex<Option<UnixDatagram>>,
+}
+
+#[cfg(unix)]
+impl UnixDatagramTransport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixDatagramTransport {
+            path: path.into(),
+            timeouts: Timeouts::default(),
+            socket: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    fn write(&self, batch: &Batch) -> io::Result<()> {
+        let mut socket = self.socket.lock().unwrap_or_else(|e| e.into_inner());
+        let socket = match &mut *socket {
+            Some(socket) => socket,
+            None => {
+                let unbound = UnixDatagram::unbound()?;
+                unbound.set_write_timeout(Some(self.timeouts.request))?;
+                socket.insert(unbound)
+            }
+        };
+        for line in batch.as_str().lines() {
+            socket.send_to(line.as_bytes(), &self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Transport for UnixDatagramTransport {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let result = self
+            .write(batch)
+            .map(|()| HecResponse::success())
+            .map_err(HecError::transport);
+        Box::pin(std::future::ready(result))
+    }
+}
+
+// a socket input the builder was told to send to, made into its transport once build knows
+// what the timeouts are
+#[derive(Debug)]
+pub(crate) enum SocketInput {
+    Tcp(String),
+    Udp(String),
+    #[cfg(unix)]
+    UnixStream(PathBuf),
+    #[cfg(unix)]
+    UnixDatagram(PathBuf),
+}
+
+impl SocketInput {
+    pub(crate) fn transport(self, timeouts: Timeouts) -> Box<dyn Transport> {
+        match self {
+            SocketInput::Tcp(addr) => Box::new(TcpTransport::new(addr).with_timeouts(timeouts)),
+            SocketInput::Udp(addr) => Box::new(UdpTransport::new(addr).with_timeouts(timeouts)),
+            #[cfg(unix)]
+            SocketInput::UnixStream(path) => {
+                Box::new(UnixStreamTransport::new(path).with_timeouts(timeouts))
+            }
+            #[cfg(unix)]
+            SocketInput::UnixDatagram(path) => {
+                Box::new(UnixDatagramTransport::new(path).with_timeouts(timeouts))
+            }
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::ack::random_channel;
+use crate::ack::AckStatus;
+use crate::batch::Batch;
+use crate::builder::BuildError;
+use crate::hec::{self, HecError, HecMetadata, HecResponse};
+use crate::probe::{self, ProbeError};
+use crate::proxy::Proxy;
+use crate::tls::{TlsConfig, TlsError};
+use crate::transport::{
+    AckFuture, InFlightLimit, ProbeFuture, Timeouts, Transport, TransportFuture,
+};
+
+// a blocking transport built on ureq. since the worker has a thread to itself this is the simplest
+// way to ship batches, and it's what the builder uses unless told otherwise.
+#[derive(Clone)]
+pub struct UreqTransport {
+    agent: ureq::Agent,
+    endpoint: String,
+    url: String,
+    ack_url: String,
+    health_url: String,
+    authorization: String,
+    user_agent: String,
+    // sent with every request, see SplunkHecLayerBuilder::header
+    headers: Vec<(String, String)>,
+    channel: Option<String>,
+    content_type: &'static str,
+    limit: Option<InFlightLimit>,
+}
+
+impl UreqTransport {
+    // `endpoint` is the base url of the HEC input, e.g. https://splunk.example.com:8088
+    pub fn new(endpoint: &str, token: &str) -> Self {
+        UreqTransport::with_agent(
+            agent_config(Timeouts::default()).build().new_agent(),
+            endpoint,
+            token,
+        )
+    }
+
+    // the same as new, with TLS set up by `tls`
+    pub fn with_tls(endpoint: &str, token: &str, tls: &TlsConfig) -> Result<Self, TlsError> {
+        let agent = with_tls_config(agent_config(Timeouts::default()), tls)?
+            .build()
+            .new_agent();
+        Ok(UreqTransport::with_agent(agent, endpoint, token))
+    }
+
+    // how the builder makes one, with everything it might have been told about the connection
+    pub(crate) fn configured(
+        endpoint: &str,
+        token: &str,
+        tls: Option<&TlsConfig>,
+        proxy: &Proxy,
+        timeouts: Timeouts,
+    ) -> Result<Self, BuildError> {
+        let mut config = agent_config(timeouts);
+        if let Some(tls) = tls {
+            config = with_tls_config(config, tls).map_err(BuildError::Tls)?;
+        }
+        let agent = with_proxy(config, proxy)?.build().new_agent();
+        Ok(UreqTransport::with_agent(agent, endpoint, token))
+    }
+
+    fn with_agent(agent: ureq::Agent, endpoint: &str, token: &str) -> Self {
+        UreqTransport {
+            agent,
+            endpoint: endpoint.to_owned(),
+            url: hec::event_url(endpoint),
+            ack_url: hec::ack_url(endpoint),
+            health_url: hec::health_url(endpoint),
+            authorization: hec::authorization(token),
+            user_agent: hec::USER_AGENT.to_owned(),
+            headers: Vec::new(),
+            channel: None,
+            content_type: "application/json",
+            limit: None,
+        }
+    }
+
+    // send every request on this X-Splunk-Request-Channel, which HEC requires for tokens with
+    // indexer acknowledgment turned on
+    pub fn with_channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    // send `name: value` with every request, e.g. for a gateway in front of HEC that wants a
+    // header of its own
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    // instead of tracing-splunk-layer/<version>
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    // have at most `max` requests going at once, between this transport and any clones of it.
+    // the rest wait on the thread that's sending them, and go out one at a time as there's room.
+    pub fn with_max_in_flight(self, max: usize) -> Self {
+        self.with_limit(InFlightLimit::new(max))
+    }
+
+    pub(crate) fn with_limit(mut self, limit: InFlightLimit) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    // send batches to the raw endpoint, tagged with `metadata`, instead of the event one. raw
+    // requests have to be on a channel, so this makes one up unless it's been given one.
+    pub fn raw(mut self, metadata: &HecMetadata) -> Self {
+        self.url = hec::raw_url(&self.endpoint, metadata);
+        self.channel.get_or_insert_with(random_channel);
+        self.content_type = "text/plain";
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn health(&self) -> Result<(), ProbeError> {
+        let _slot = self.limit.as_ref().map(InFlightLimit::wait);
+        let mut response = self
+            .headers(self.agent.get(&self.health_url))
+            .call()
+            .map_err(|e| ProbeError::Unreachable(e.to_string()))?;
+        let status = response.status().as_u16();
+        let body = response.body_mut().read_to_string().unwrap_or_default();
+        probe::health(status, &body)
+    }
+
+    fn post(
+        &self,
+        url: &str,
+        content_type: &str,
+        payload: &str,
+    ) -> Result<(u16, Option<String>, String), HecError> {
+        let _slot = self.limit.as_ref().map(InFlightLimit::wait);
+        let mut request = self
+            .headers(self.agent.post(url))
+            .header("Authorization", &self.authorization);
+        if let Some(channel) = &self.channel {
+            request = request.header("X-Splunk-Request-Channel", channel);
+        }
+        let mut response = request
+            .content_type(content_type)
+            .send(payload)
+            .map_err(HecError::transport)?;
+
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get("retry-after")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        let body = response.body_mut().read_to_string().unwrap_or_default();
+        Ok((status, retry_after, body))
+    }
+
+    // what goes on every request, authorized or not
+    fn headers<B>(&self, request: ureq::RequestBuilder<B>) -> ureq::RequestBuilder<B> {
+        let request = request.header("User-Agent", &self.user_agent);
+        self.headers.iter().fold(request, |request, (name, value)| {
+            request.header(name, value)
+        })
+    }
+}
+
+type AgentConfig = ureq::config::ConfigBuilder<ureq::typestate::AgentScope>;
+
+fn agent_config(timeouts: Timeouts) -> AgentConfig {
+    // we want to look at error bodies ourselves since HEC explains what went wrong in them
+    ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_connect(Some(timeouts.connect))
+        .timeout_global(Some(timeouts.request))
+        // the agent keeps connections alive for the next batch, but not forever
+        .max_idle_age(timeouts.pool_idle)
+}
+
+// ureq reads the environment unless it's given a proxy, or told not to use one
+fn with_proxy(config: AgentConfig, proxy: &Proxy) -> Result<AgentConfig, BuildError> {
+    let proxy = match proxy {
+        Proxy::FromEnv => return Ok(config),
+        Proxy::Direct => return Ok(config.proxy(None)),
+        Proxy::Custom(proxy) => proxy,
+    };
+    let invalid = |e: ureq::Error| BuildError::Proxy(e.to_string());
+    let parsed = ureq::Proxy::new(&proxy.url).map_err(invalid)?;
+
+    let mut builder = ureq::Proxy::builder(parsed.protocol())
+        .host(parsed.host())
+        .port(parsed.port());
+    match &proxy.credentials {
+        Some(credentials) => {
+            builder = builder
+                .username(&credentials.username)
+                .password(&credentials.password);
+        }
+        None => {
+            if let Some(username) = parsed.username() {
+                builder = builder.username(username);
+            }
+            if let Some(password) = parsed.password() {
+                builder = builder.password(password);
+            }
+        }
+    }
+    for host in proxy.no_proxy_hosts().iter().flat_map(|h| h.split(',')) {
+        builder = builder.no_proxy(host.trim());
+    }
+    Ok(config.proxy(Some(builder.build().map_err(invalid)?)))
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn with_tls_config(config: AgentConfig, tls: &TlsConfig) -> Result<AgentConfig, TlsError> {
+    use crate::tls::TlsBackend;
+    use ureq::tls::{ClientCert, PrivateKey, RootCerts, TlsProvider};
+
+    if !tls.backend.is_enabled() {
+        return Err(TlsError::BackendUnavailable(tls.backend));
+    }
+    let provider = match tls.backend {
+        TlsBackend::Rustls => TlsProvider::Rustls,
+        TlsBackend::NativeTls => TlsProvider::NativeTls,
+    };
+    let mut tls_config = ureq::tls::TlsConfig::builder()
+        .provider(provider)
+        .disable_verification(tls.danger_accept_invalid_certs);
+
+    if !tls.root_certificates.is_empty() {
+        let mut roots = Vec::new();
+        for pem in &tls.root_certificates {
+            roots.extend(certificates(pem).map_err(TlsError::InvalidRootCertificate)?);
+        }
+        tls_config = tls_config.root_certs(RootCerts::from(roots));
+    }
+    if let Some(identity) = &tls.client_identity {
+        let chain = certificates(&identity.cert_chain).map_err(TlsError::InvalidClientIdentity)?;
+        let key = PrivateKey::from_pem(&identity.private_key)
+            .map_err(|e| TlsError::InvalidClientIdentity(e.to_string()))?;
+        tls_config = tls_config.client_cert(Some(ClientCert::new_with_certs(&chain, key)));
+    }
+    Ok(config.tls_config(tls_config.build()))
+}
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+fn with_tls_config(_config: AgentConfig, tls: &TlsConfig) -> Result<AgentConfig, TlsError> {
+    Err(TlsError::BackendUnavailable(tls.backend))
+}
+
+// every certificate in a PEM bundle, of which there has to be at least one
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+fn certificates(pem: &[u8]) -> Result<Vec<ureq::tls::Certificate<'static>>, String> {
+    let mut certs = Vec::new();
+    for item in ureq::tls::parse_pem(pem) {
+        if let ureq::tls::PemItem::Certificate(cert) = item.map_err(|e| e.to_string())? {
+            certs.push(cert);
+        }
+    }
+    if certs.is_empty() {
+        return Err("no certificates in the PEM".to_owned());
+    }
+    Ok(certs)
+}
+
+impl Transport for UreqTransport {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        // all the work happens right here on the worker thread, the future is already done
+        let result = self
+            .post(&self.url, self.content_type, batch.as_str())
+            .and_then(|(status, retry_after, body)| {
+                HecResponse::parse(status, retry_after.as_deref(), &body)
+            });
+        Box::pin(std::future::ready(result))
+    }
+
+    fn query_acks<'a>(&'a self, ack_ids: &'a [u64]) -> AckFuture<'a> {
+        let result = self
+            .post(&self.ack_url, "application/json", &hec::ack_query(ack_ids))
+            .and_then(|(status, _, body)| AckStatus::parse(status, &body));
+        Box::pin(std::future::ready(result))
+    }
+
+    fn probe(&self) -> ProbeFuture<'_> {
+        let result = self.health().and_then(|()| {
+            let response = self.post(&self.url, self.content_type, "").and_then(
+                |(status, retry_after, body)| {
+                    HecResponse::parse(status, retry_after.as_deref(), &body)
+                },
+            );
+            probe::token(response)
+        });
+        Box::pin(std::future::ready(result))
+    }
+
+    fn set_token(&mut self, token: &str) -> bool {
+        self.authorization = hec::authorization(token);
+        true
+    }
+}
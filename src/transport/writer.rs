@@ -0,0 +1,43 @@
+use std::io::Write;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::batch::Batch;
+use crate::hec::{HecError, HecResponse};
+use crate::transport::{Transport, TransportFuture};
+
+// writes batches out as newline delimited json instead of POSTing them, using the same MakeWriter
+// fmt::Layer does. that's a file, stdout for something like splunk connect for kubernetes to pick
+// up, or a buffer in a test, with every event looking exactly like it would have over HEC.
+pub struct WriterTransport<W> {
+    make_writer: W,
+}
+
+impl<W> WriterTransport<W>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    pub fn new(make_writer: W) -> Self {
+        WriterTransport { make_writer }
+    }
+
+    fn write(&self, batch: &Batch) -> std::io::Result<()> {
+        let mut writer = self.make_writer.make_writer();
+        writer.write_all(batch.as_str().as_bytes())?;
+        writer.write_all(b"\n")?;
+        writer.flush()
+    }
+}
+
+impl<W> Transport for WriterTransport<W>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let result = self
+            .write(batch)
+            .map(|()| HecResponse::success())
+            .map_err(HecError::transport);
+        Box::pin(std::future::ready(result))
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::fmt;
+
+// how long string values can get before they're cut short, see
+// SplunkHecLayerBuilder::max_field_length. anything longer keeps its first `limit` bytes with an
+// ellipsis after them, and its full length is recorded as `<field>.original_length`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct FieldLengths {
+    pub(crate) all: Option<usize>,
+    pub(crate) fields: HashMap<String, usize>,
+}
+
+impl FieldLengths {
+    pub(crate) fn limit(&self, name: &str) -> Option<usize> {
+        self.fields.get(name).copied().or(self.all)
+    }
+}
+
+// a fmt::Write that only keeps the first `limit` bytes, so a Debug impl that dumps megabytes
+// never has all of it in memory at once
+pub(crate) struct Bounded {
+    buf: String,
+    limit: usize,
+    len: usize,
+}
+
+impl Bounded {
+    pub(crate) fn new(limit: usize) -> Self {
+        Bounded {
+            buf: String::new(),
+            limit,
+            len: 0,
+        }
+    }
+
+    // what was written, and how long it really was if that got cut short
+    pub(crate) fn finish(mut self) -> (String, Option<usize>) {
+        if self.len <= self.limit {
+            return (self.buf, None);
+        }
+        self.buf.push('…');
+        (self.buf, Some(self.len))
+    }
+}
+
+impl fmt::Write for Bounded {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        let room = self.limit.saturating_sub(self.buf.len());
+        if room >= s.len() {
+            self.buf.push_str(s);
+        } else if room > 0 {
+            let mut end = room;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            self.buf.push_str(&s[..end]);
+            // nothing after this will fit either, even if it's a single byte
+            self.limit = self.buf.len();
+        }
+        Ok(())
+    }
+}
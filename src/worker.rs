@@ -0,0 +1,1265 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::ack::{AckConfig, AckTracker};
+use crate::aggregate::Aggregator;
+use crate::batch::{AdaptiveBatching, Batch, BatchConfig};
+use crate::builder::BuildError;
+use crate::circuit::{CircuitBreaker, CircuitBreakerConfig};
+use crate::dead_letter::{DeadLetter, DeadLetterSink};
+use crate::error::{Error, ErrorPolicy, LayerError};
+use crate::fallback::FallbackSink;
+use crate::hec::{HecError, HecResponse};
+use crate::hooks::{BatchSummary, ExportHooks};
+use crate::internal;
+use crate::metrics::{Counters, DropReason, LayerMetrics};
+use crate::oversize::{Fitted, SizeLimit};
+use crate::queue::{self, Receiver, Sender};
+use crate::rate_limit::RateLimiter;
+use crate::raw::LineFormatter;
+use crate::record::EventRecord;
+use crate::retry::RetryPolicy;
+use crate::routing::RouteKey;
+use crate::spool::Spool;
+use crate::transport::{block_on, Transport};
+use tracing::Level;
+
+// how many events can be waiting on the worker before the queue is considered full
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+// how long dropping a WorkerGuard will wait for the worker to ship what it has
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// what on_close should do when the worker can't keep up. whatever gets dropped is counted in
+// MetricsSnapshot::dropped_queue_full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+    // throw the new event away so the instrumented code never waits on splunk
+    #[default]
+    Drop,
+    // throw away the oldest event still waiting to make room for the new one, for when what's
+    // happening now matters more than what happened a moment ago
+    DropOldest,
+    // wait for room in the queue, trading latency in the application for completeness
+    Block,
+    // wait for room, but only this long before dropping the new event after all
+    BlockFor(Duration),
+    // drop new events less severe than this, e.g. DEBUG and TRACE for Level::INFO, and wait for
+    // room for the rest
+    DropBelow(Level),
+}
+
+// drop the less important records before the queue is full, so there's still room for the ones
+// that matter when things are busy, which is usually when they're needed. once `high_water_mark`
+// records are waiting on the worker, anything less severe than `keep` is thrown away (and counted
+// in MetricsSnapshot::dropped_shed). WARN and ERROR are never shed, whatever `keep` is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadShedding {
+    // None is three quarters of whatever channel_capacity the builder ends up with
+    pub high_water_mark: Option<usize>,
+    pub keep: Level,
+}
+
+impl Default for LoadShedding {
+    // sheds DEBUG and TRACE once the queue is three quarters full
+    fn default() -> Self {
+        LoadShedding {
+            high_water_mark: None,
+            keep: Level::INFO,
+        }
+    }
+}
+
+impl LoadShedding {
+    // the same with the high water mark filled in for a queue that holds `capacity`. at least 1,
+    // so a tiny queue doesn't shed while it's empty
+    pub(crate) fn for_capacity(mut self, capacity: usize) -> Self {
+        self.high_water_mark
+            .get_or_insert((capacity.saturating_mul(3) / 4).max(1));
+        self
+    }
+
+    fn sheds(&self, level: &Level, queue_depth: u64) -> bool {
+        *level > self.keep.max(Level::WARN)
+            && self
+                .high_water_mark
+                .is_some_and(|mark| queue_depth >= mark as u64)
+    }
+}
+
+// everything the worker can be asked to do. control messages go through the same queue as the
+// records so a flush covers everything that was enqueued before it.
+pub(crate) enum Message {
+    // boxed, a record is a lot bigger than everything else that goes through the channel
+    Record(Box<EventRecord>),
+    // ship whatever is batched up and let the sender know once that's done
+    Flush(SyncSender<()>),
+    // same as a flush, but the worker exits afterwards
+    Shutdown(SyncSender<()>),
+    // a shutdown that gives up on whatever it can't get done by the deadline (see
+    // WorkerGuard::shutdown), the sender hears back how many events that was
+    Drain(SyncSender<u64>),
+    // change a setting between batches, the sender hears back whether it could be applied
+    Reconfigure(Reconfigure, SyncSender<bool>),
+}
+
+// the worker settings a ConfigHandle can change while it's running
+pub(crate) enum Reconfigure {
+    Batching(BatchConfig),
+    // for rotating the HEC token, only some transports can take a new one
+    Token(String),
+}
+
+// what the worker runs on
+#[derive(Clone, Debug, Default)]
+pub(crate) enum WorkerRuntime {
+    // a thread of its own
+    #[default]
+    Thread,
+    // a task on the application's tokio runtime
+    #[cfg(feature = "tokio")]
+    Tokio(tokio::runtime::Handle),
+}
+
+// how the layer lets the worker know there's something in the queue. a thread is already blocked
+// on the channel, a tokio task has to be woken up.
+#[derive(Clone, Debug, Default)]
+struct Wakeup {
+    #[cfg(feature = "tokio")]
+    notify: Option<Arc<tokio::sync::Notify>>,
+}
+
+impl Wakeup {
+    fn wake(&self) {
+        #[cfg(feature = "tokio")]
+        if let Some(notify) = &self.notify {
+            notify.notify_one();
+        }
+    }
+}
+
+// everything the builder decides about how the worker runs
+pub(crate) struct WorkerConfig {
+    pub(crate) runtime: WorkerRuntime,
+    pub(crate) capacity: usize,
+    pub(crate) queue_full_policy: QueueFullPolicy,
+    pub(crate) load_shedding: Option<LoadShedding>,
+    pub(crate) batch: BatchConfig,
+    pub(crate) adaptive: Option<AdaptiveBatching>,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) acks: Option<AckConfig>,
+    pub(crate) spool: Option<Spool>,
+    pub(crate) dead_letters: Option<DeadLetterSink>,
+    pub(crate) fallback: Option<FallbackSink>,
+    // only there when sending to the raw endpoint, records are json otherwise
+    pub(crate) formatter: Option<Arc<dyn LineFormatter>>,
+    // only there when spans are being aggregated, for sending the summaries
+    pub(crate) aggregator: Option<Arc<Aggregator>>,
+    // only there with a rate limit, for sending what it suppressed
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    pub(crate) size_limit: Option<SizeLimit>,
+    pub(crate) circuit_breaker: Option<CircuitBreakerConfig>,
+    pub(crate) hooks: ExportHooks,
+}
+
+impl WorkerConfig {
+    // the same for another Destination, with only what isn't tied to the main one
+    pub(crate) fn for_destination(&self) -> WorkerConfig {
+        WorkerConfig {
+            runtime: self.runtime.clone(),
+            capacity: self.capacity,
+            queue_full_policy: self.queue_full_policy,
+            load_shedding: self.load_shedding,
+            batch: self.batch,
+            adaptive: self.adaptive,
+            retry: self.retry,
+            acks: None,
+            spool: None,
+            dead_letters: None,
+            fallback: None,
+            formatter: None,
+            aggregator: None,
+            rate_limiter: None,
+            size_limit: self.size_limit,
+            circuit_breaker: self.circuit_breaker,
+            hooks: ExportHooks::default(),
+        }
+    }
+}
+
+// the layer's side of the worker. cheap to use from any thread since all it does is enqueue.
+#[derive(Clone, Debug)]
+pub(crate) struct WorkerHandle {
+    sender: Sender<Message>,
+    wakeup: Wakeup,
+    policy: QueueFullPolicy,
+    shedding: Option<LoadShedding>,
+    counters: Arc<Counters>,
+    errors: ErrorPolicy,
+    // whether the queue was full last time, so it's reported once rather than for every record
+    full: Arc<AtomicBool>,
+    // set by WorkerGuard::shutdown, after which records are turned away
+    closed: Arc<AtomicBool>,
+}
+
+impl WorkerHandle {
+    // start a worker (a thread, or a tokio task) which owns the transport and does all of the
+    // actual I/O
+    pub(crate) fn spawn(
+        transport: Box<dyn Transport>,
+        config: WorkerConfig,
+        counters: Arc<Counters>,
+        errors: ErrorPolicy,
+    ) -> Result<(Self, WorkerGuard), BuildError> {
+        let (sender, receiver) = queue::bounded(config.capacity);
+        let deadline = ShutdownDeadline::default();
+        let worker = Worker {
+            transport,
+            batch_config: match config.adaptive {
+                Some(adaptive) => adaptive.clamp(config.batch),
+                None => config.batch,
+            },
+            adaptive: config.adaptive,
+            retry_policy: config.retry,
+            batch: Batch::default(),
+            acks: config.acks.map(AckTracker::new),
+            spool: config.spool,
+            dead_letters: config.dead_letters,
+            fallback: config.fallback,
+            formatter: config.formatter,
+            aggregator: config.aggregator,
+            rate_limiter: config.rate_limiter,
+            size_limit: config.size_limit,
+            circuit: config.circuit_breaker.map(CircuitBreaker::new),
+            hooks: config.hooks,
+            errors: errors.clone(),
+            counters: counters.clone(),
+            runtime: config.runtime.clone(),
+            deadline: deadline.clone(),
+            abandoned: 0,
+        };
+
+        let (thread, wakeup) = match config.runtime {
+            WorkerRuntime::Thread => {
+                let thread = thread::Builder::new()
+                    .name("splunk-hec-worker".to_string())
+                    .spawn(move || {
+                        // nothing traced on this thread, like the http client's own logging,
+                        // should find its way back into the layer
+                        let _internal = internal::enter();
+                        worker.run(receiver)
+                    })
+                    .map_err(|e| BuildError::Worker(e.to_string()))?;
+                (Some(thread), Wakeup::default())
+            }
+            #[cfg(feature = "tokio")]
+            WorkerRuntime::Tokio(handle) => {
+                let notify = Arc::new(tokio::sync::Notify::new());
+                handle.spawn(internal::Internal(Box::pin(
+                    worker.run_async(receiver, notify.clone()),
+                )));
+                let wakeup = Wakeup {
+                    notify: Some(notify),
+                };
+                (None, wakeup)
+            }
+        };
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let guard = WorkerGuard {
+            sender: sender.clone(),
+            wakeup: wakeup.clone(),
+            thread,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            counters: counters.clone(),
+            errors: errors.clone(),
+            closed: closed.clone(),
+            deadline,
+            destinations: Vec::new(),
+            routes: Vec::new(),
+        };
+        let handle = WorkerHandle {
+            sender,
+            wakeup,
+            policy: config.queue_full_policy,
+            shedding: config.load_shedding,
+            counters,
+            errors,
+            full: Arc::default(),
+            closed,
+        };
+        Ok((handle, guard))
+    }
+
+    // hand a record off to the worker. returns false if the record was dropped. `level` is the
+    // span or event's own, for QueueFullPolicy::DropBelow.
+    pub(crate) fn send(&self, record: EventRecord, level: &Level) -> bool {
+        if self.closed.load(Ordering::Relaxed) {
+            self.counters.dropped(DropReason::Closed, 1);
+            return false;
+        }
+        let depth = self.counters.queue_depth();
+        if self
+            .shedding
+            .is_some_and(|shedding| shedding.sheds(level, depth))
+        {
+            self.counters.dropped(DropReason::Shed, 1);
+            return false;
+        }
+        let message = Message::Record(Box::new(record));
+        // counted before it's sent so the worker can never take it off the queue first
+        self.counters.enqueued();
+        let sent = match self.policy {
+            QueueFullPolicy::Drop => self.sender.try_send(message).is_ok(),
+            QueueFullPolicy::DropOldest => {
+                match self
+                    .sender
+                    .send_displacing(message, |m| matches!(m, Message::Record(_)))
+                {
+                    Ok(Some(_oldest)) => {
+                        self.counters.dequeued();
+                        self.counters.dropped(DropReason::QueueFull, 1);
+                        true
+                    }
+                    Ok(None) => true,
+                    Err(_) => false,
+                }
+            }
+            QueueFullPolicy::Block => self.sender.send(message).is_ok(),
+            QueueFullPolicy::BlockFor(timeout) => {
+                self.sender.send_timeout(message, timeout).is_ok()
+            }
+            QueueFullPolicy::DropBelow(threshold) if *level > threshold => {
+                self.sender.try_send(message).is_ok()
+            }
+            QueueFullPolicy::DropBelow(_) => self.sender.send(message).is_ok(),
+        };
+        if sent {
+            self.wakeup.wake();
+            self.full.store(false, Ordering::Relaxed);
+        } else {
+            self.counters.dequeued();
+            self.counters.dropped(DropReason::QueueFull, 1);
+            if !self.full.swap(true, Ordering::Relaxed) {
+                self.errors.handle(LayerError::QueueFull);
+            }
+        }
+        sent
+    }
+
+    // the same as WorkerGuard::flush, for whatever only has the layer's side to hand
+    pub(crate) fn flush(&self, timeout: Duration) -> Result<(), FlushError> {
+        request(&self.sender, &self.wakeup, Message::Flush, timeout)
+    }
+
+    // returns whether the worker could apply the change, once it has
+    pub(crate) fn reconfigure(
+        &self,
+        change: Reconfigure,
+        timeout: Duration,
+    ) -> Result<bool, FlushError> {
+        request(
+            &self.sender,
+            &self.wakeup,
+            |ack| Message::Reconfigure(change, ack),
+            timeout,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushError {
+    // the worker didn't finish shipping before the timeout ran out. it keeps trying in the
+    // background, so the events aren't necessarily lost.
+    Timeout,
+    // the worker has already shut down
+    Disconnected,
+}
+
+impl fmt::Display for FlushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlushError::Timeout => write!(f, "timed out waiting for the splunk hec worker"),
+            FlushError::Disconnected => write!(f, "the splunk hec worker has shut down"),
+        }
+    }
+}
+
+impl std::error::Error for FlushError {}
+
+// ties the lifetime of the worker to a value in your program, like tracing_appender's
+// WorkerGuard. when it's dropped everything still buffered is shipped and the worker is shut
+// down, so hold on to it in main rather than letting it go with `let _ = ...`.
+#[must_use = "dropping the guard shuts the worker down immediately"]
+#[derive(Debug)]
+pub struct WorkerGuard {
+    sender: Sender<Message>,
+    wakeup: Wakeup,
+    // only there when the worker has a thread of its own
+    thread: Option<JoinHandle<()>>,
+    shutdown_timeout: Duration,
+    counters: Arc<Counters>,
+    errors: ErrorPolicy,
+    // shared with the WorkerHandle, see shutdown
+    closed: Arc<AtomicBool>,
+    // shared with the worker, so it goes by the deadline for what's still queued ahead of the
+    // Drain message too
+    deadline: ShutdownDeadline,
+    // the workers of any other destinations, which shut down along with this one
+    destinations: Vec<WorkerGuard>,
+    // and those of any tenant routes
+    routes: Vec<(RouteKey, WorkerGuard)>,
+}
+
+impl WorkerGuard {
+    // ship everything that was enqueued before this call, waiting at most `timeout` for it. that
+    // includes every destination, each of which gets the whole timeout.
+    pub fn flush(&self, timeout: Duration) -> Result<(), Error> {
+        self.request(Message::Flush, timeout)?;
+        self.destinations
+            .iter()
+            .chain(self.routes.iter().map(|(_, route)| route))
+            .try_for_each(|other| other.flush(timeout))
+    }
+
+    // how many spans tail sampling has decided not to export
+    pub fn suppressed_spans(&self) -> u64 {
+        self.counters.spans_suppressed()
+    }
+
+    // how many spans were left out by head sampling
+    pub fn sampled_out_spans(&self) -> u64 {
+        self.counters.spans_sampled_out()
+    }
+
+    // everything that's been counted so far, and a handle to keep polling. only for the main
+    // destination, the others have destination_metrics.
+    pub fn metrics(&self) -> LayerMetrics {
+        LayerMetrics::new(self.counters.clone())
+    }
+
+    // the same as metrics for each Destination, in the order they were added
+    pub fn destination_metrics(&self) -> Vec<LayerMetrics> {
+        self.destinations.iter().map(WorkerGuard::metrics).collect()
+    }
+
+    // the same as metrics for a tenant's route, see SplunkHecLayerBuilder::tenant_routes
+    pub fn route_metrics(&self, key: &str) -> Option<LayerMetrics> {
+        self.routes
+            .iter()
+            .find(|(route, _)| route == key)
+            .map(|(_, guard)| guard.metrics())
+    }
+
+    // how long dropping the guard may block while the worker ships what it has left
+    pub fn set_shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = timeout;
+        for destination in &mut self.destinations {
+            destination.set_shutdown_timeout(timeout);
+        }
+        for (_, route) in &mut self.routes {
+            route.set_shutdown_timeout(timeout);
+        }
+    }
+
+    // shut down for good, for a pre-stop hook or the like: new records are turned away (counted
+    // as DropReason::Closed), and what was already queued is shipped, retries included, until
+    // `deadline` runs out. whatever can't be delivered by then goes to the spool or fallback sink
+    // if there is one, and is abandoned otherwise, along with anything still queued. every
+    // destination and tenant route gets the same deadline.
+    pub fn shutdown(mut self, deadline: Duration) -> ShutdownReport {
+        let deadline = Instant::now() + deadline;
+        let others = std::mem::take(&mut self.destinations).into_iter().chain(
+            std::mem::take(&mut self.routes)
+                .into_iter()
+                .map(|(_, route)| route),
+        );
+        let mut report = self.drain(deadline);
+        for mut other in others {
+            let drained = other.drain(deadline);
+            report.abandoned += drained.abandoned;
+            report.timed_out |= drained.timed_out;
+        }
+        report
+    }
+
+    fn drain(&mut self, deadline: Instant) -> ShutdownReport {
+        self.closed.store(true, Ordering::Relaxed);
+        self.deadline.set(deadline);
+        // the worker stops sending at the deadline, what it does after that (spooling, writing
+        // dead letters) gets a little longer before we give up on hearing back
+        let timeout = deadline.saturating_duration_since(Instant::now()) + DRAIN_GRACE;
+        let drained = request(&self.sender, &self.wakeup, Message::Drain, timeout);
+        let (abandoned, timed_out) = match drained {
+            Ok(abandoned) => {
+                if let Some(thread) = self.thread.take() {
+                    let _ = thread.join();
+                }
+                (abandoned, false)
+            }
+            Err(FlushError::Disconnected) => (0, false),
+            // the worker gives up on its own once it sees the deadline has passed, it's
+            // only a send that's already under way it can't cut short
+            Err(FlushError::Timeout) => {
+                self.thread = None;
+                (0, true)
+            }
+        };
+        ShutdownReport {
+            // some records can still sneak in behind the drain, they're never taken off the queue
+            abandoned: abandoned + self.counters.queue_depth(),
+            timed_out,
+        }
+    }
+
+    // the same as flush, for somewhere the guard itself can't go, like a signal handler
+    #[cfg(feature = "signal")]
+    pub(crate) fn flusher(&self) -> impl Fn(Duration) + Send + 'static {
+        let mut workers = vec![(self.sender.clone(), self.wakeup.clone())];
+        for other in self
+            .destinations
+            .iter()
+            .chain(self.routes.iter().map(|(_, route)| route))
+        {
+            workers.push((other.sender.clone(), other.wakeup.clone()));
+        }
+        let errors = self.errors.clone();
+        move |timeout| {
+            for (sender, wakeup) in &workers {
+                if let Err(FlushError::Timeout) = request(sender, wakeup, Message::Flush, timeout) {
+                    errors.handle(LayerError::ShutdownTimeout);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn add_destination(&mut self, guard: WorkerGuard) {
+        self.destinations.push(guard);
+    }
+
+    pub(crate) fn add_route(&mut self, key: RouteKey, guard: WorkerGuard) {
+        self.routes.push((key, guard));
+    }
+
+    fn request(
+        &self,
+        message: fn(SyncSender<()>) -> Message,
+        timeout: Duration,
+    ) -> Result<(), FlushError> {
+        request(&self.sender, &self.wakeup, message, timeout)
+    }
+}
+
+// send the worker a control message and wait for it to get done with it
+fn request<T>(
+    sender: &Sender<Message>,
+    wakeup: &Wakeup,
+    message: impl FnOnce(SyncSender<T>) -> Message,
+    timeout: Duration,
+) -> Result<T, FlushError> {
+    let deadline = Instant::now() + timeout;
+    let (ack, done) = mpsc::sync_channel(1);
+
+    // the queue might be full, but we don't get to block forever waiting for room in it
+    let mut message = message(ack);
+    loop {
+        match sender.try_send(message) {
+            Ok(()) => {
+                wakeup.wake();
+                break;
+            }
+            Err(TrySendError::Disconnected(_)) => return Err(FlushError::Disconnected),
+            Err(TrySendError::Full(m)) if Instant::now() < deadline => {
+                message = m;
+                thread::sleep(Duration::from_millis(1));
+            }
+            Err(TrySendError::Full(_)) => return Err(FlushError::Timeout),
+        }
+    }
+
+    match done.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(reply) => Ok(reply),
+        Err(RecvTimeoutError::Timeout) => Err(FlushError::Timeout),
+        Err(RecvTimeoutError::Disconnected) => Err(FlushError::Disconnected),
+    }
+}
+
+// when WorkerGuard::shutdown has to be done by, once it's been called
+#[derive(Clone, Debug, Default)]
+struct ShutdownDeadline(Arc<Mutex<Option<Instant>>>);
+
+impl ShutdownDeadline {
+    fn set(&self, deadline: Instant) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(deadline);
+    }
+
+    fn get(&self) -> Option<Instant> {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// how long past its deadline WorkerGuard::shutdown waits for the worker to wrap up
+const DRAIN_GRACE: Duration = Duration::from_millis(250);
+
+// what's left after WorkerGuard::shutdown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    // records that were given up on, because they couldn't be delivered before the deadline and
+    // there was nowhere else to keep them, or because they were still queued when it ran out
+    pub abandoned: u64,
+    // the deadline ran out with a batch still being sent, which is left to finish in the
+    // background and isn't counted in `abandoned`
+    pub timed_out: bool,
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        // shutdown has already been and gone
+        if self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        match self.request(Message::Shutdown, self.shutdown_timeout) {
+            // the worker is on its way out, so joining won't block for long
+            Ok(()) | Err(FlushError::Disconnected) => {
+                if let Some(thread) = self.thread.take() {
+                    let _ = thread.join();
+                }
+            }
+            // leave the worker to finish on its own rather than hanging the application
+            Err(FlushError::Timeout) => self.errors.handle(LayerError::ShutdownTimeout),
+        }
+    }
+}
+
+struct Worker {
+    transport: Box<dyn Transport>,
+    // what the batch limits are right now, which only changes on its own with adaptive batching
+    batch_config: BatchConfig,
+    adaptive: Option<AdaptiveBatching>,
+    retry_policy: RetryPolicy,
+    batch: Batch,
+    // only there with indexer acknowledgment turned on
+    acks: Option<AckTracker>,
+    // only there when the builder was given a spool directory
+    spool: Option<Spool>,
+    dead_letters: Option<DeadLetterSink>,
+    fallback: Option<FallbackSink>,
+    formatter: Option<Arc<dyn LineFormatter>>,
+    aggregator: Option<Arc<Aggregator>>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    size_limit: Option<SizeLimit>,
+    // only there when the builder was given a circuit breaker
+    circuit: Option<CircuitBreaker>,
+    hooks: ExportHooks,
+    errors: ErrorPolicy,
+    counters: Arc<Counters>,
+    runtime: WorkerRuntime,
+    // only set by WorkerGuard::shutdown, after which nothing more is sent
+    deadline: ShutdownDeadline,
+    // the events lost while shutting down by a deadline
+    abandoned: u64,
+}
+
+// a batch that couldn't be delivered, and why
+struct Failed {
+    // always a LayerError::Export
+    reason: LayerError,
+    retryable: bool,
+}
+
+impl Failed {
+    fn new(batch: &Batch, attempts: u32, source: HecError) -> Self {
+        Failed {
+            retryable: source.is_retryable(),
+            reason: LayerError::Export {
+                events: batch.len(),
+                attempts,
+                source,
+            },
+        }
+    }
+}
+
+impl Worker {
+    // the worker runs until it's told to shut down or every sender has been dropped
+    fn run(mut self, receiver: Receiver<Message>) {
+        loop {
+            // with nothing buffered we can sleep until the next event, otherwise only until
+            // something is due
+            let received = match self.time_until_due() {
+                None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                Some(timeout) => receiver.recv_timeout(timeout),
+            };
+            let keep_going = match received {
+                Ok(message) => block_on(self.handle(message)),
+                Err(RecvTimeoutError::Timeout) => true,
+                Err(RecvTimeoutError::Disconnected) => {
+                    block_on(self.shutdown());
+                    false
+                }
+            };
+            if !keep_going {
+                return;
+            }
+            block_on(self.tick());
+        }
+    }
+
+    // the same loop as a tokio task. the queue is still a std channel, since the layer has to be
+    // able to enqueue from anywhere without an async context, so senders poke `notify` to wake us
+    // up instead.
+    #[cfg(feature = "tokio")]
+    async fn run_async(mut self, receiver: Receiver<Message>, notify: Arc<tokio::sync::Notify>) {
+        loop {
+            loop {
+                let keep_going = match receiver.try_recv() {
+                    Ok(message) => self.handle(message).await,
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        self.shutdown().await;
+                        false
+                    }
+                };
+                if !keep_going {
+                    return;
+                }
+            }
+            self.tick().await;
+
+            match self.time_until_due() {
+                None => notify.notified().await,
+                Some(timeout) => {
+                    let _ = tokio::time::timeout(timeout, notify.notified()).await;
+                }
+            }
+        }
+    }
+
+    // how long until the batch is due to be flushed, the outstanding acks are due to be checked
+    // on, the spool is due to be replayed, any summaries are due or HEC is due to be probed,
+    // whichever comes first
+    fn time_until_due(&self) -> Option<Duration> {
+        let flush_in = self.batch.time_until_flush(&self.batch_config);
+        let poll_in = self.acks.as_ref().and_then(AckTracker::time_until_poll);
+        // the spool waits for the circuit to close
+        let replay_in = self
+            .spool
+            .as_ref()
+            .filter(|_| !self.circuit_open())
+            .and_then(Spool::time_until_replay);
+        let summary_in = self.aggregator.as_ref().map(|a| a.time_until_due());
+        let suppressed_in = self.rate_limiter.as_ref().and_then(|r| r.time_until_due());
+        let probe_in = self
+            .circuit
+            .as_ref()
+            .and_then(CircuitBreaker::time_until_probe);
+        flush_in
+            .into_iter()
+            .chain(poll_in)
+            .chain(replay_in)
+            .chain(summary_in)
+            .chain(suppressed_in)
+            .chain(probe_in)
+            .min()
+    }
+
+    // returns false once the worker should stop
+    async fn handle(&mut self, message: Message) -> bool {
+        match message {
+            Message::Record(record) => {
+                self.counters.dequeued();
+                self.push(*record).await;
+            }
+            Message::Flush(ack) => {
+                self.flush().await;
+                let _ = ack.send(());
+            }
+            Message::Shutdown(ack) => {
+                self.shutdown().await;
+                let _ = ack.send(());
+                return false;
+            }
+            Message::Drain(ack) => {
+                self.shutdown().await;
+                let _ = ack.send(self.abandoned);
+                return false;
+            }
+            Message::Reconfigure(change, ack) => {
+                let applied = self.reconfigure(change).await;
+                let _ = ack.send(applied);
+            }
+        }
+        true
+    }
+
+    // nothing is in flight while a message is handled, so whatever batch goes out next is the
+    // first to see the change
+    async fn reconfigure(&mut self, change: Reconfigure) -> bool {
+        match change {
+            Reconfigure::Batching(config) => {
+                self.batch_config = match self.adaptive {
+                    Some(adaptive) => adaptive.clamp(config),
+                    None => config,
+                };
+                // smaller limits can leave what's already batched over them
+                if self.batch.is_full(&self.batch_config) {
+                    self.flush().await;
+                }
+                true
+            }
+            Reconfigure::Token(token) => self.transport.set_token(&token),
+        }
+    }
+
+    // do whatever has come due. this runs after every message too, so a steady trickle of events
+    // can't hold the flush interval off forever.
+    async fn tick(&mut self) {
+        self.push_summaries(false).await;
+        if self.batch.time_until_flush(&self.batch_config) == Some(Duration::ZERO) {
+            self.adapt_to_interval();
+            self.flush().await;
+        }
+        self.poll_acks(false).await;
+        self.probe_circuit().await;
+        if self.spool.as_ref().and_then(Spool::time_until_replay) == Some(Duration::ZERO) {
+            self.replay_spool().await;
+        }
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        match &self.runtime {
+            WorkerRuntime::Thread => thread::sleep(duration),
+            #[cfg(feature = "tokio")]
+            WorkerRuntime::Tokio(_) => tokio::time::sleep(duration).await,
+        }
+    }
+
+    // the span summaries and rate limit warnings that are due
+    async fn push_summaries(&mut self, force: bool) {
+        let mut summaries = Vec::new();
+        if let Some(aggregator) = &self.aggregator {
+            summaries.extend(aggregator.take_due(force));
+        }
+        if let Some(limiter) = &self.rate_limiter {
+            summaries.extend(limiter.take_due(force));
+        }
+        for record in summaries {
+            self.push(record).await;
+        }
+    }
+
+    fn encode(&self, record: &EventRecord) -> Result<String, serde_json::Error> {
+        match &self.formatter {
+            Some(formatter) => Ok(formatter.format(record)),
+            None => serde_json::to_string(record),
+        }
+    }
+
+    async fn push(&mut self, record: EventRecord) {
+        // serializing here rather than in the layer keeps that cost off the application
+        if self.size_limit.is_none() && self.formatter.is_none() {
+            return self.push_json(record).await;
+        }
+        let fitted = match self.size_limit {
+            Some(limit) => limit.fit(record, |record| self.encode(record)),
+            None => self.encode(&record).map(|payload| Fitted {
+                payloads: vec![payload],
+                dropped: 0,
+            }),
+        };
+        let fitted = match fitted {
+            Ok(fitted) => fitted,
+            Err(e) => {
+                self.counters.dropped(DropReason::Serialize, 1);
+                self.errors.handle(LayerError::Serialize(e));
+                return;
+            }
+        };
+        if fitted.dropped > 0 {
+            self.counters.dropped(DropReason::Oversized, fitted.dropped);
+        }
+
+        for payload in fitted.payloads {
+            if self.batch.would_overflow(&payload, &self.batch_config) {
+                self.flush_full().await;
+            }
+            self.batch.push(&payload);
+            if self.batch.is_full(&self.batch_config) {
+                self.flush_full().await;
+            }
+        }
+    }
+
+    // the usual case, where the event can go straight into the batch's buffer as json
+    async fn push_json(&mut self, record: EventRecord) {
+        match self.batch.push_json(&record, &self.batch_config) {
+            Ok(None) => {}
+            Ok(Some(overflow)) => {
+                self.flush_full().await;
+                self.batch.push(&overflow);
+            }
+            Err(e) => {
+                self.counters.dropped(DropReason::Serialize, 1);
+                self.errors.handle(LayerError::Serialize(e));
+                return;
+            }
+        }
+        if self.batch.is_full(&self.batch_config) {
+            self.flush_full().await;
+        }
+    }
+
+    // a batch that filled up before the flush interval ran out means there's load, so with
+    // adaptive batching the next one gets to be bigger
+    async fn flush_full(&mut self) {
+        if let Some(adaptive) = self.adaptive {
+            self.batch_config = adaptive.grow(self.batch_config);
+        }
+        self.flush().await;
+    }
+
+    // the flush interval ran out, which with adaptive batching says how busy things are by how
+    // far the batch got, unless there's a batch's worth already waiting in the queue
+    fn adapt_to_interval(&mut self) {
+        let Some(adaptive) = self.adaptive else {
+            return;
+        };
+        let max_events = self.batch_config.max_events;
+        if self.counters.queue_depth() >= max_events as u64 {
+            self.batch_config = adaptive.grow(self.batch_config);
+        } else if self.batch.len() < max_events / 4 {
+            self.batch_config = adaptive.shrink(self.batch_config);
+        }
+    }
+
+    async fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let batch = std::mem::take(&mut self.batch);
+        // with the circuit open it's not even worth trying
+        if self.circuit_open() {
+            let events = batch.len();
+            self.unavailable(&batch, LayerError::CircuitOpen { events });
+            self.batch = batch;
+            self.batch.clear();
+            return;
+        }
+        let delivered = self.deliver(&batch, true).await;
+        let recovered = delivered.is_ok();
+        let leftover = match delivered {
+            Ok(response) => self.track(response, batch, 0),
+            Err(failed) => {
+                self.undeliverable(&batch, failed);
+                Some(batch)
+            }
+        };
+        // reuse the buffer's allocation for the next batch if we're not holding on to it
+        if let Some(mut batch) = leftover {
+            batch.clear();
+            self.batch = batch;
+        }
+
+        // splunk is taking events again, so this is a good time to catch up on the spool
+        if recovered {
+            self.replay_spool().await;
+        }
+    }
+
+    // send a batch, retrying as the retry policy allows (if `retry` is set and the circuit is
+    // closed)
+    async fn deliver(&mut self, batch: &Batch, retry: bool) -> Result<HecResponse, Failed> {
+        if self.circuit_open() {
+            let error = HecError::transport("the circuit breaker is open");
+            return Err(Failed::new(batch, 0, error));
+        }
+        if self.past_deadline(Duration::ZERO) {
+            let error = HecError::transport("the shutdown deadline has passed");
+            return Err(Failed::new(batch, 0, error));
+        }
+        let started = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let error = match self.transport.send(batch).await {
+                Ok(response) => {
+                    self.counters.batch_sent(batch.len(), batch.as_str().len());
+                    self.circuit_result(true);
+                    self.hooks.succeeded(|| BatchSummary {
+                        events: batch.len(),
+                        bytes: batch.as_str().len(),
+                        attempts: attempt,
+                        elapsed: started.elapsed(),
+                        ack_id: response.ack_id,
+                    });
+                    return Ok(response);
+                }
+                Err(error) => error,
+            };
+            if error.is_retryable() {
+                self.circuit_result(false);
+            }
+            let retry = retry && !self.circuit_open();
+            let backoff = self.retry_policy.backoff(attempt, &error);
+            match backoff.filter(|backoff| retry && !self.past_deadline(*backoff)) {
+                Some(backoff) => {
+                    self.counters.retried();
+                    self.sleep(backoff).await;
+                    attempt += 1;
+                }
+                None => {
+                    let mut failed = Failed::new(batch, attempt, error);
+                    failed.reason = self.hooks.failed(failed.reason);
+                    return Err(failed);
+                }
+            }
+        }
+    }
+
+    // hang on to a delivered batch until HEC says it was indexed, if we're doing that. otherwise
+    // the batch is handed back.
+    fn track(&mut self, response: HecResponse, batch: Batch, resends: u32) -> Option<Batch> {
+        match (&mut self.acks, response.ack_id) {
+            (Some(acks), Some(ack_id)) => {
+                acks.track(ack_id, batch, resends);
+                None
+            }
+            _ => Some(batch),
+        }
+    }
+
+    // a batch that didn't make it is handled as unavailable if it's worth trying again, otherwise
+    // it's reported as lost
+    fn undeliverable(&mut self, batch: &Batch, failed: Failed) {
+        if failed.retryable {
+            self.unavailable(batch, failed.reason);
+        } else {
+            self.lost(batch, failed.reason);
+        }
+    }
+
+    // a batch that couldn't be sent because splunk is down, rather than because it was rejected.
+    // it goes to the spool to be sent later if there is one, the fallback sink if there's one of
+    // those, and is lost otherwise.
+    fn unavailable(&mut self, batch: &Batch, reason: LayerError) {
+        if self.spool_batch(batch) {
+            return;
+        }
+        if let Some(fallback) = &self.fallback {
+            match fallback.write(batch) {
+                Ok(()) => {
+                    self.counters.fell_back(batch.len());
+                    return self.errors.handle(reason);
+                }
+                Err(e) => self.errors.handle(LayerError::Fallback(e)),
+            }
+        }
+        self.lost(batch, reason);
+    }
+
+    // keep a batch in the spool to be replayed later, if there is one. false if it couldn't be.
+    fn spool_batch(&mut self, batch: &Batch) -> bool {
+        let Some(spool) = self.spool.as_mut() else {
+            return false;
+        };
+        let written = spool.write(batch);
+        spool.replay_later();
+        match written {
+            Ok(0) => true,
+            Ok(bytes) => {
+                self.errors.handle(LayerError::SpoolFull { bytes });
+                true
+            }
+            Err(e) => {
+                self.errors.handle(LayerError::Spool(e));
+                false
+            }
+        }
+    }
+
+    // whether waiting `wait` more would take us past the shutdown deadline, if there is one
+    fn past_deadline(&self, wait: Duration) -> bool {
+        self.deadline
+            .get()
+            .is_some_and(|deadline| Instant::now() + wait >= deadline)
+    }
+
+    fn circuit_open(&self) -> bool {
+        self.circuit.as_ref().is_some_and(CircuitBreaker::is_open)
+    }
+
+    // let the circuit breaker know how a try went
+    fn circuit_result(&mut self, succeeded: bool) {
+        if let Some(circuit) = &mut self.circuit {
+            if succeeded {
+                circuit.succeeded();
+            } else {
+                circuit.failed();
+            }
+            self.counters.circuit(circuit.state());
+        }
+    }
+
+    // ask HEC whether it's back, if the circuit is open and it's been long enough. if it looks
+    // like it is the spool is replayed right away, which is as good a trial as the next batch.
+    async fn probe_circuit(&mut self) {
+        let due = self
+            .circuit
+            .as_ref()
+            .and_then(CircuitBreaker::time_until_probe);
+        if due != Some(Duration::ZERO) {
+            return;
+        }
+        let healthy = self.transport.probe().await.is_ok();
+        if let Some(circuit) = &mut self.circuit {
+            circuit.probed(healthy);
+            self.counters.circuit(circuit.state());
+        }
+        if healthy {
+            self.replay_spool().await;
+        }
+    }
+
+    // a batch we've given up on goes to the dead letter sink, if there is one
+    fn lost(&mut self, batch: &Batch, reason: LayerError) {
+        if self.deadline.get().is_some() {
+            self.abandoned += batch.len() as u64;
+        }
+        let drop_reason = match reason {
+            LayerError::Unacknowledged { .. } => DropReason::Unacknowledged,
+            LayerError::CircuitOpen { .. } => DropReason::CircuitOpen,
+            _ => DropReason::ExportFailed,
+        };
+        self.counters.dropped(drop_reason, batch.len());
+        if let Some(sink) = &self.dead_letters {
+            let letter = DeadLetter {
+                payload: batch.as_str(),
+                reason: &reason,
+            };
+            if let Err(e) = sink.send(&letter) {
+                self.errors.handle(LayerError::DeadLetter(e));
+            }
+        }
+        self.errors.handle(reason);
+    }
+
+    // send everything in the spool, oldest first, until it's empty or splunk stops taking events
+    async fn replay_spool(&mut self) {
+        if self.circuit_open() {
+            return;
+        }
+        let Some(mut spool) = self.spool.take() else {
+            return;
+        };
+        while let Some(batches) = spool.oldest(&self.batch_config) {
+            let replayed = match batches {
+                Ok(batches) => Ok(self.replay(batches).await),
+                Err(e) => Err(e),
+            };
+            let cleaned_up = match replayed {
+                Ok(Ok(())) => spool.remove_oldest(),
+                Ok(Err(remaining)) => {
+                    spool.replay_later();
+                    if let Err(e) = spool.truncate_oldest(&remaining) {
+                        self.errors.handle(LayerError::Spool(e));
+                    }
+                    break;
+                }
+                // a segment we can't read will never get any more readable
+                Err(e) => {
+                    self.errors.handle(LayerError::Spool(e));
+                    spool.remove_oldest()
+                }
+            };
+            if let Err(e) = cleaned_up {
+                self.errors.handle(LayerError::Spool(e));
+            }
+        }
+        self.spool = Some(spool);
+    }
+
+    // send batches from the spool, handing back whatever's left if splunk is still unhappy. there
+    // are no retries here since the whole spool will be tried again later anyway.
+    async fn replay(&mut self, batches: Vec<Batch>) -> Result<(), Vec<Batch>> {
+        let mut batches = batches.into_iter();
+        while let Some(batch) = batches.next() {
+            match self.deliver(&batch, false).await {
+                Ok(response) => {
+                    self.track(response, batch, 0);
+                }
+                Err(failed) if failed.retryable => {
+                    let mut remaining = vec![batch];
+                    remaining.extend(batches);
+                    return Err(remaining);
+                }
+                Err(failed) => self.lost(&batch, failed.reason),
+            }
+        }
+        Ok(())
+    }
+
+    // check on outstanding acks if they're due (or right away when `force`d), sending again
+    // anything that's waited too long
+    async fn poll_acks(&mut self, force: bool) {
+        let Some(mut acks) = self.acks.take() else {
+            return;
+        };
+        let due = acks.time_until_poll() == Some(Duration::ZERO);
+        if !acks.is_empty() && (force || due) {
+            match self.transport.query_acks(&acks.ack_ids()).await {
+                Ok(status) => acks.acknowledge(&status),
+                Err(e) => {
+                    acks.polled();
+                    self.errors.handle(LayerError::AckQuery(e));
+                }
+            }
+
+            for pending in acks.take_expired() {
+                if pending.resends >= acks.config.max_resends {
+                    self.lost(
+                        &pending.batch,
+                        LayerError::Unacknowledged {
+                            events: pending.batch.len(),
+                        },
+                    );
+                    continue;
+                }
+                match self.deliver(&pending.batch, true).await {
+                    Ok(HecResponse {
+                        ack_id: Some(ack_id),
+                        ..
+                    }) => acks.track(ack_id, pending.batch, pending.resends + 1),
+                    Ok(_) => {}
+                    Err(failed) => self.undeliverable(&pending.batch, failed),
+                }
+            }
+        }
+        self.acks = Some(acks);
+    }
+
+    // ship what's left and give HEC one last chance to acknowledge what it has. anything still
+    // unacknowledged goes to the spool, if there is one, so the next run can send it again.
+    async fn shutdown(&mut self) {
+        self.push_summaries(true).await;
+        self.flush().await;
+        self.poll_acks(true).await;
+        let unacked = match &mut self.acks {
+            Some(acks) => acks.take_all(),
+            None => Vec::new(),
+        };
+        for pending in unacked {
+            if !self.spool_batch(&pending.batch) {
+                self.lost(
+                    &pending.batch,
+                    LayerError::Unacknowledged {
+                        events: pending.batch.len(),
+                    },
+                );
+            }
+        }
+    }
+}
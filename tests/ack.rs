@@ -0,0 +1,124 @@
+use crate::common::{MockHec, MockResponse};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+use tracing_splunk_layer::{AckConfig, ErrorPolicy, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+fn acked(ack_id: u64) -> MockResponse {
+    MockResponse::status(
+        200,
+        &format!(r#"{{"text":"Success","code":0,"ackId":{}}}"#, ack_id),
+    )
+}
+
+#[test]
+fn batches_are_acknowledged_on_the_channel() {
+    let hec = MockHec::start();
+    hec.respond_with(acked(7));
+    hec.respond_with(MockResponse::status(200, r#"{"acks":{"7":true}}"#));
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let seen = errors.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .indexer_ack(AckConfig {
+            poll_interval: Duration::from_millis(50),
+            ..AckConfig::with_channel("0c8a2b3e-1f4d-4a5b-9c6d-7e8f9a0b1c2d")
+        })
+        .error_policy(ErrorPolicy::callback(move |e| {
+            seen.lock().unwrap().push(e.to_string())
+        }))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("indexed");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let requests = hec.wait_for_requests(2);
+    drop(guard);
+
+    assert_eq!(requests[0].path, "/services/collector/event");
+    assert_eq!(requests[1].path, "/services/collector/ack");
+    for request in &requests {
+        assert_eq!(
+            request.header("x-splunk-request-channel"),
+            Some("0c8a2b3e-1f4d-4a5b-9c6d-7e8f9a0b1c2d")
+        );
+    }
+    assert_eq!(requests[1].body, r#"{"acks":[7]}"#);
+    assert!(errors.lock().unwrap().is_empty());
+}
+
+#[test]
+fn unacknowledged_batches_are_sent_again() {
+    let hec = MockHec::start();
+    hec.respond_with(acked(1));
+    hec.respond_with(MockResponse::status(200, r#"{"acks":{"1":false}}"#));
+    hec.respond_with(acked(2));
+    hec.respond_with(MockResponse::status(200, r#"{"acks":{"2":true}}"#));
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .indexer_ack(AckConfig {
+            poll_interval: Duration::from_millis(100),
+            // anything that isn't acked on the first poll gets sent again
+            timeout: Duration::ZERO,
+            ..AckConfig::new()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("lost by the indexer");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let requests = hec.wait_for_requests(4);
+
+    let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/services/collector/event",
+            "/services/collector/ack",
+            "/services/collector/event",
+            "/services/collector/ack"
+        ]
+    );
+    assert_eq!(requests[2].body, requests[0].body);
+    assert_eq!(requests[3].body, r#"{"acks":[2]}"#);
+}
+
+#[test]
+fn the_mock_hec_acknowledges_batches_itself() {
+    let hec = MockHec::start().enable_acks();
+    // held back on the first poll, so the batch is sent again
+    hec.hold_acks(true);
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .indexer_ack(AckConfig {
+            poll_interval: Duration::from_millis(50),
+            timeout: Duration::ZERO,
+            ..AckConfig::new()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("indexed eventually");
+    hec.wait_for_requests(2);
+    hec.hold_acks(false);
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let requests = hec.wait_for_requests(4);
+    drop(guard);
+
+    let bodies: Vec<&str> = requests.iter().map(|r| r.body.as_str()).collect();
+    assert_eq!(bodies[1], r#"{"acks":[0]}"#);
+    assert_eq!(bodies[3], r#"{"acks":[1]}"#);
+    let messages: Vec<_> = hec
+        .events()
+        .iter()
+        .map(|e| e["event"]["message"].clone())
+        .collect();
+    assert_eq!(messages, vec!["indexed eventually", "indexed eventually"]);
+}
@@ -0,0 +1,188 @@
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing::{info, info_span};
+use tracing_splunk_layer::{AdaptiveBatching, OversizedEvent, SpanEventMode, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn batches_flush_on_count_and_interval() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_batch_events(2)
+        .flush_interval(Duration::from_millis(50))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..5 {
+        info_span!("request", i).in_scope(|| {});
+    }
+
+    // two full batches go out right away, the straggler goes out once the interval is up
+    let requests = hec.wait_for_requests(3);
+    let sizes: Vec<usize> = requests.iter().map(|r| r.events().len()).collect();
+    assert_eq!(sizes, vec![2, 2, 1]);
+}
+
+#[test]
+fn batches_flush_on_byte_size() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_batch_bytes(1)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..3 {
+        info_span!("request", i).in_scope(|| {});
+    }
+
+    // every event is over the limit on its own, so each one is shipped alone
+    let requests = hec.wait_for_requests(3);
+    assert_eq!(requests.len(), 3);
+    assert!(requests.iter().all(|r| r.events().len() == 1));
+}
+
+fn oversized(policy: OversizedEvent, emit: impl FnOnce()) -> (Vec<String>, u64) {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_event_mode(SpanEventMode::List)
+        .max_event_bytes(1000)
+        .oversized_events(policy)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    emit();
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let body = hec
+        .requests()
+        .iter()
+        .map(|r| r.body.clone())
+        .collect::<Vec<_>>();
+    let lines = body.iter().flat_map(|b| b.lines()).map(str::to_owned);
+    (
+        lines.collect(),
+        guard.metrics().snapshot().dropped_oversized,
+    )
+}
+
+#[test]
+fn oversized_events_are_truncated() {
+    let (lines, dropped) = oversized(OversizedEvent::Truncate, || {
+        info!(dump = "x".repeat(5000), small = "kept", "big");
+    });
+    assert_eq!(dropped, 0);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].len() <= 1000);
+    let event: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(event["event"]["truncated"], true);
+    assert_eq!(event["event"]["small"], "kept");
+    let dump = event["event"]["dump"].as_str().unwrap();
+    assert!(!dump.is_empty() && dump.len() < 1000);
+}
+
+#[test]
+fn oversized_event_lists_are_split() {
+    let (lines, dropped) = oversized(OversizedEvent::Split, || {
+        info_span!("batch").in_scope(|| {
+            for i in 0..20 {
+                info!(i, padding = "y".repeat(100), "item");
+            }
+        });
+    });
+    assert_eq!(dropped, 0);
+    assert!(lines.len() > 1);
+    let mut seen = Vec::new();
+    for line in &lines {
+        assert!(line.len() <= 1000);
+        let event: serde_json::Value = serde_json::from_str(line).unwrap();
+        assert_eq!(event["event"]["name"], "batch");
+        assert_eq!(event["event"]["events.parts"], lines.len());
+        for item in event["event"]["events"].as_array().unwrap() {
+            seen.push(item["i"].as_u64().unwrap());
+        }
+    }
+    assert_eq!(seen, (0..20).collect::<Vec<_>>());
+}
+
+#[test]
+fn oversized_events_can_be_dropped() {
+    let (lines, dropped) = oversized(OversizedEvent::Drop, || {
+        info!(dump = "x".repeat(5000), "big");
+        info!("small");
+    });
+    assert_eq!(dropped, 1);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("small"));
+}
+
+#[test]
+fn events_that_overflow_a_batch_start_the_next_one() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_batch_bytes(700)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..10 {
+        info!(i, padding = "x".repeat(100).as_str(), "numbered");
+    }
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    assert!(requests.len() > 1);
+    assert!(requests.iter().all(|r| r.body.len() <= 700));
+    let numbers: Vec<_> = requests
+        .iter()
+        .flat_map(|r| r.events())
+        .map(|e| e["event"]["i"].as_u64().unwrap())
+        .collect();
+    assert_eq!(numbers, (0..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn adaptive_batches_grow_under_load_and_shrink_when_idle() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_batch_events(2)
+        .flush_interval(Duration::from_millis(40))
+        .adaptive_batching(AdaptiveBatching {
+            min_events: 2,
+            max_events: 8,
+            min_flush_interval: Duration::from_millis(20),
+            max_flush_interval: Duration::from_secs(1),
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    // every batch that fills up makes the next one twice the size, up to the maximum
+    for i in 0..22 {
+        info!(i, "busy");
+    }
+    hec.wait_for_requests(4);
+    // a lone event only goes out once the interval runs out, after which batches are smaller
+    info!("quiet");
+    hec.wait_for_requests(5);
+    for i in 0..4 {
+        info!(i, "busy again");
+    }
+
+    let requests = hec.wait_for_requests(6);
+    let sizes: Vec<usize> = requests.iter().map(|r| r.events().len()).collect();
+    assert_eq!(sizes, vec![2, 4, 8, 8, 1, 4]);
+}
@@ -0,0 +1,125 @@
+use crate::common::{config_error, MockHec};
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{BuildError, QueueFullPolicy, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn builder_requires_endpoint_and_token() {
+    let err = SplunkHecLayer::builder()
+        .token("abc")
+        .build()
+        .err()
+        .map(config_error);
+    assert_eq!(err, Some(BuildError::MissingEndpoint));
+
+    let err = SplunkHecLayer::builder()
+        .endpoint("http://localhost:8088")
+        .build()
+        .err()
+        .map(config_error);
+    assert_eq!(err, Some(BuildError::MissingToken));
+}
+
+#[test]
+fn builder_metadata_is_sent_with_each_event() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .index("app_logs")
+        .source("my-service")
+        .sourcetype("_json")
+        .host("web-1")
+        .flush_interval(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", status = 200).in_scope(|| {});
+
+    let requests = hec.wait_for_requests(1);
+    assert_eq!(requests.len(), 1);
+    let body = &requests[0].events()[0];
+    assert_eq!(body["index"], "app_logs");
+    assert_eq!(body["source"], "my-service");
+    assert_eq!(body["sourcetype"], "_json");
+    assert_eq!(body["host"], "web-1");
+    assert_eq!(body["event"]["status"], 200);
+}
+
+#[test]
+fn full_queue_drops_instead_of_blocking() {
+    // nothing is listening here, so the worker will sit in connect while the queue fills up
+    let (layer, mut guard) = SplunkHecLayer::builder()
+        .endpoint("http://10.255.255.1:8088")
+        .token("abc")
+        .channel_capacity(1)
+        .queue_full_policy(QueueFullPolicy::Drop)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let start = std::time::Instant::now();
+    for _ in 0..100 {
+        info_span!("request").in_scope(|| {});
+    }
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // don't hang the test waiting on a worker that will never get through
+    guard.set_shutdown_timeout(Duration::ZERO);
+}
+
+#[test]
+fn extra_headers_and_the_user_agent_go_on_every_request() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .header("X-Gateway-Key", "secret")
+        .user_agent("payments/1.2")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    assert_eq!(requests[0].header("x-gateway-key"), Some("secret"));
+    assert_eq!(requests[0].header("user-agent"), Some("payments/1.2"));
+    assert_eq!(requests[0].header("authorization"), Some("Splunk abc"));
+}
+
+#[test]
+fn the_default_user_agent_names_the_crate() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let user_agent = hec.requests()[0].header("user-agent").unwrap().to_owned();
+    assert!(
+        user_agent.starts_with("tracing-splunk-layer/"),
+        "{}",
+        user_agent
+    );
+}
+
+#[test]
+fn headers_that_cant_be_sent_are_a_build_error() {
+    let err = SplunkHecLayer::builder()
+        .endpoint("http://localhost:8088")
+        .token("abc")
+        .header("X-Key", "line\r\nbreak")
+        .build()
+        .err()
+        .map(config_error);
+    assert_eq!(err, Some(BuildError::InvalidHeader("X-Key".to_string())));
+}
@@ -0,0 +1,49 @@
+use std::time::Duration;
+use tracing::{info, info_span};
+use tracing_splunk_layer::{SplunkHecLayer, TestSink};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn test_sink_captures_what_would_have_been_sent() {
+    let sink = TestSink::new();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .transport(sink.clone())
+        .index("app_logs")
+        .indexed_fields(["tenant"])
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("outer", answer = 42, tenant = "acme").in_scope(|| {
+        info!(rows = 5, "query done");
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let outer = sink.find_span("outer").unwrap();
+    outer.assert_field("answer", 42);
+    assert_eq!(outer.envelope()["index"], "app_logs");
+    assert_eq!(outer.indexed_field("tenant").unwrap(), "acme");
+    sink.find_event("query done")
+        .unwrap()
+        .assert_field("rows", 5);
+    sink.assert_field("answer", 42);
+    assert!(sink.find_span("inner").is_none());
+
+    sink.clear();
+    assert!(sink.records().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "no record has answer = 41")]
+fn test_sink_assertions_say_what_was_there() {
+    let sink = TestSink::new();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .transport(sink.clone())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("outer", answer = 42).in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+    sink.assert_field("answer", 41);
+}
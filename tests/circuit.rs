@@ -0,0 +1,90 @@
+use crate::common::{MockHec, MockResponse};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+use tracing_splunk_layer::{
+    CircuitBreakerConfig, CircuitState, DeadLetterSink, DropReason, ErrorPolicy, RetryPolicy,
+    SplunkHecLayer, WorkerGuard,
+};
+use tracing_subscriber::prelude::*;
+
+fn layer(
+    hec: &MockHec,
+    probe_interval: Duration,
+    sink: DeadLetterSink,
+) -> (SplunkHecLayer, WorkerGuard) {
+    SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .retry_policy(RetryPolicy::none())
+        .error_policy(ErrorPolicy::Ignore)
+        .circuit_breaker(CircuitBreakerConfig {
+            failure_threshold: 2,
+            probe_interval,
+        })
+        .dead_letter_sink(sink)
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn the_circuit_opens_after_enough_failures_in_a_row() {
+    let hec = MockHec::start();
+    for _ in 0..2 {
+        hec.respond_with(MockResponse::status(503, "unavailable"));
+    }
+    let reasons = Arc::new(Mutex::new(Vec::new()));
+    let seen = reasons.clone();
+    let sink = DeadLetterSink::callback(move |letter| {
+        seen.lock().unwrap().push(letter.reason.to_string());
+    });
+    let (layer, guard) = layer(&hec, Duration::from_secs(60), sink);
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..3 {
+        info!(i, "hello");
+        guard.flush(Duration::from_secs(5)).unwrap();
+    }
+
+    // the third batch never got as far as HEC
+    assert_eq!(hec.requests().len(), 2);
+    let snapshot = guard.metrics().snapshot();
+    assert_eq!(snapshot.circuit, CircuitState::Open);
+    assert_eq!(snapshot.circuit_opened, 1);
+    assert_eq!(snapshot.dropped(DropReason::CircuitOpen), 1);
+    assert_eq!(
+        reasons.lock().unwrap().last().unwrap(),
+        "splunk has been failing, so a batch of 1 events wasn't sent"
+    );
+}
+
+#[test]
+fn the_circuit_closes_once_hec_is_back() {
+    let hec = MockHec::start();
+    for _ in 0..2 {
+        hec.respond_with(MockResponse::status(503, "unavailable"));
+    }
+    let (layer, guard) = layer(
+        &hec,
+        Duration::from_millis(50),
+        DeadLetterSink::callback(|_| {}),
+    );
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..2 {
+        info!(i, "hello");
+        guard.flush(Duration::from_secs(5)).unwrap();
+    }
+    assert_eq!(guard.metrics().snapshot().circuit, CircuitState::Open);
+
+    // the mock is back to succeeding, so the probe finds it healthy and the next batch is let
+    // through
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(guard.metrics().snapshot().circuit, CircuitState::HalfOpen);
+    info!("back");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(guard.metrics().snapshot().circuit, CircuitState::Closed);
+    let last = hec.requests().pop().unwrap();
+    assert_eq!(last.events()[0]["event"]["message"], "back");
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "cloud-metadata")]
+
+use crate::common::{MockHec, MockResponse};
+use std::time::Duration;
+use tracing::info;
+use tracing_splunk_layer::{CloudMetadata, CloudProvider, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+fn exported(hec: &MockHec, cloud: CloudMetadata) -> serde_json::Value {
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .cloud_metadata(cloud)
+        .build()
+        .unwrap();
+    let subscriber = tracing_subscriber::registry().with(layer);
+    tracing::subscriber::with_default(subscriber, || info!("hello"));
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    events[0]["event"].clone()
+}
+
+#[test]
+fn aws_instance_is_recorded() {
+    let imds = MockHec::start();
+    imds.respond_with(MockResponse::status(200, "session-token"));
+    imds.respond_with(MockResponse::status(
+        200,
+        r#"{"region":"eu-west-1","instanceId":"i-0abc","accountId":"123"}"#,
+    ));
+    let hec = MockHec::start();
+
+    let fields = exported(
+        &hec,
+        CloudMetadata {
+            providers: vec![CloudProvider::Aws],
+            aws_endpoint: imds.url().to_string(),
+            ..CloudMetadata::default()
+        },
+    );
+    assert_eq!(fields["cloud.provider"], "aws");
+    assert_eq!(fields["cloud.region"], "eu-west-1");
+    assert_eq!(fields["instance.id"], "i-0abc");
+
+    let requests = imds.requests();
+    assert_eq!(requests[0].path, "/latest/api/token");
+    assert_eq!(
+        requests[1].header("X-aws-ec2-metadata-token"),
+        Some("session-token")
+    );
+}
+
+#[test]
+fn first_provider_to_answer_wins() {
+    let aws = MockHec::start();
+    aws.respond_with(MockResponse::status(404, "not found"));
+    aws.respond_with(MockResponse::status(404, "not found"));
+    let gcp = MockHec::start();
+    gcp.respond_with(MockResponse::status(200, "4242"));
+    gcp.respond_with(MockResponse::status(
+        200,
+        "projects/1234/zones/us-central1-a",
+    ));
+    let hec = MockHec::start();
+
+    let fields = exported(
+        &hec,
+        CloudMetadata {
+            providers: vec![CloudProvider::Aws, CloudProvider::Gcp],
+            aws_endpoint: aws.url().to_string(),
+            gcp_endpoint: gcp.url().to_string(),
+            ..CloudMetadata::default()
+        },
+    );
+    assert_eq!(fields["cloud.provider"], "gcp");
+    assert_eq!(fields["cloud.region"], "us-central1");
+    assert_eq!(fields["instance.id"], "4242");
+    assert_eq!(gcp.requests()[0].header("Metadata-Flavor"), Some("Google"));
+}
+
+#[test]
+fn nothing_is_recorded_off_cloud() {
+    let azure = MockHec::start();
+    azure.respond_with(MockResponse::status(404, "not found"));
+    let hec = MockHec::start();
+
+    let fields = exported(
+        &hec,
+        CloudMetadata {
+            providers: vec![CloudProvider::Azure],
+            azure_endpoint: azure.url().to_string(),
+            ..CloudMetadata::default()
+        },
+    );
+    assert!(fields.get("cloud.provider").is_none());
+    assert!(fields.get("instance.id").is_none());
+}
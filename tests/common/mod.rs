@@ -0,0 +1,12 @@
+// the mock HEC the tests point the layer at, it's the crate's own behind the `testing` feature
+pub use tracing_splunk_layer::{MockHec, MockResponse};
+
+use tracing_splunk_layer::{BuildError, Error};
+
+// the config error a build failed with, for comparing against. anything else fails the test
+pub fn config_error(error: Error) -> BuildError {
+    match error {
+        Error::Config(error) => error,
+        error => panic!("not a config error: {}", error),
+    }
+}
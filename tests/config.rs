@@ -0,0 +1,92 @@
+#![cfg(feature = "toml")]
+
+use crate::common::{config_error, MockHec};
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::{debug_span, info_span};
+use tracing_splunk_layer::{BuildError, SplunkHecLayerBuilder};
+use tracing_subscriber::prelude::*;
+
+// a config file in the temp dir, removed when it's dropped
+struct ConfigFile(PathBuf);
+
+impl ConfigFile {
+    fn new(name: &str, contents: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "tracing-splunk-layer-{}-{}.toml",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        ConfigFile(path)
+    }
+}
+
+impl Drop for ConfigFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn the_builder_can_be_loaded_from_a_toml_file() {
+    let hec = MockHec::start();
+    let file = ConfigFile::new(
+        "full",
+        &format!(
+            r#"
+            [endpoint]
+            url = "{}"
+
+            [auth]
+            token = "abc"
+
+            [metadata]
+            index = "app_logs"
+
+            [batching]
+            max_events = 10
+            flush_interval_ms = 50
+
+            [retry]
+            max_attempts = 1
+
+            [filter]
+            max_level = "info"
+            "#,
+            hec.url()
+        ),
+    );
+    let (layer, guard) = SplunkHecLayerBuilder::from_toml(&file.0)
+        .unwrap()
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    debug_span!("too_quiet").in_scope(|| {});
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    assert_eq!(requests[0].header("authorization"), Some("Splunk abc"));
+    let events = requests[0].events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["index"], "app_logs");
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    let file = ConfigFile::new(
+        "unknown",
+        r#"
+        [batching]
+        max_event = 10
+        "#,
+    );
+    let err = config_error(SplunkHecLayerBuilder::from_toml(&file.0).err().unwrap());
+    let BuildError::Config { path, message } = &err else {
+        panic!("{}", err);
+    };
+    assert_eq!(path, &file.0);
+    assert!(message.contains("unknown field `max_event`"), "{}", message);
+}
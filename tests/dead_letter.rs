@@ -0,0 +1,123 @@
+use crate::common::{MockHec, MockResponse};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+use tracing_splunk_layer::{
+    DeadLetterSink, ErrorPolicy, FallbackSink, RetryPolicy, SplunkHecLayer,
+};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn rejected_batches_go_to_the_dead_letter_sink() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(
+        400,
+        r#"{"text":"Invalid data format","code":6}"#,
+    ));
+    let letters = Arc::new(Mutex::new(Vec::new()));
+    let seen = letters.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .error_policy(ErrorPolicy::Ignore)
+        .dead_letter_sink(DeadLetterSink::callback(move |letter| {
+            seen.lock()
+                .unwrap()
+                .push((letter.payload.to_string(), letter.reason.to_string()));
+        }))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("not wanted");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let letters = letters.lock().unwrap();
+    assert_eq!(letters.len(), 1);
+    let (payload, reason) = &letters[0];
+    assert_eq!(*payload, hec.requests()[0].body);
+    assert_eq!(
+        reason,
+        "failed to ship 1 events to splunk after 1 attempts: \
+         HEC returned 400 (code 6): Invalid data format"
+    );
+}
+
+#[test]
+fn dead_letters_can_be_written_to_a_file() {
+    let path = std::env::temp_dir().join(format!(
+        "tracing-splunk-layer-dead-letters-{}.jsonl",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(503, "unavailable"));
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .retry_policy(RetryPolicy::none())
+        .error_policy(ErrorPolicy::Ignore)
+        .dead_letter_sink(DeadLetterSink::File(path.clone()))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("for later");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let letter: serde_json::Value = serde_json::from_str(contents.trim_end()).unwrap();
+    let event: serde_json::Value =
+        serde_json::from_str(letter["payload"].as_str().unwrap()).unwrap();
+    assert_eq!(event["event"]["message"], "for later");
+    assert!(letter["reason"]
+        .as_str()
+        .unwrap()
+        .contains("HEC returned 503"));
+}
+
+#[test]
+fn events_go_to_the_fallback_sink_while_splunk_is_unavailable() {
+    let path = std::env::temp_dir().join(format!(
+        "tracing-splunk-layer-fallback-{}.jsonl",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(503, "unavailable"));
+    hec.respond_with(MockResponse::status(400, "bad"));
+    let letters = Arc::new(Mutex::new(0));
+    let seen = letters.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .retry_policy(RetryPolicy::none())
+        .error_policy(ErrorPolicy::Ignore)
+        .fallback_sink(FallbackSink::File(path.clone()))
+        .dead_letter_sink(DeadLetterSink::callback(move |_| {
+            *seen.lock().unwrap() += 1;
+        }))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("during the outage");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    // HEC is up but rejected it, which is a dead letter rather than something to fall back on
+    info!("rejected");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let events: Vec<serde_json::Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"]["message"], "during the outage");
+    assert_eq!(*letters.lock().unwrap(), 1);
+    let snapshot = guard.metrics().snapshot();
+    assert_eq!(snapshot.fallback_events, 1);
+    assert_eq!(snapshot.dropped_total(), 1);
+}
@@ -0,0 +1,56 @@
+use crate::common::{config_error, MockHec};
+use std::env;
+use std::time::Duration;
+use tracing::{debug_span, info_span};
+use tracing_splunk_layer::{BuildError, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+// the environment is shared by every test, so all of this has to happen in the one test
+#[test]
+fn the_layer_can_be_configured_from_the_environment() {
+    let hec = MockHec::start();
+    env::remove_var("SPLUNK_HEC_URL");
+    env::set_var("SPLUNK_HEC_TOKEN", "abc");
+    assert_eq!(
+        SplunkHecLayer::from_env().err().map(config_error),
+        Some(BuildError::MissingEnv("SPLUNK_HEC_URL"))
+    );
+
+    env::set_var("SPLUNK_HEC_URL", hec.url());
+    env::set_var("SPLUNK_HEC_MAX_BATCH_EVENTS", "lots");
+    let err = config_error(SplunkHecLayer::from_env().err().unwrap());
+    let BuildError::InvalidEnv { name, value, .. } = &err else {
+        panic!("{}", err);
+    };
+    assert_eq!(*name, "SPLUNK_HEC_MAX_BATCH_EVENTS");
+    assert_eq!(value, "lots");
+
+    // empty is the same as unset
+    env::set_var("SPLUNK_HEC_MAX_BATCH_EVENTS", "");
+    env::set_var("SPLUNK_INDEX", "app_logs");
+    env::set_var("SPLUNK_SOURCETYPE", "_json");
+    env::set_var("SPLUNK_HEC_MAX_LEVEL", "info");
+    let (layer, guard) = SplunkHecLayer::from_env().unwrap();
+    for name in [
+        "SPLUNK_HEC_URL",
+        "SPLUNK_HEC_TOKEN",
+        "SPLUNK_HEC_MAX_BATCH_EVENTS",
+        "SPLUNK_INDEX",
+        "SPLUNK_SOURCETYPE",
+        "SPLUNK_HEC_MAX_LEVEL",
+    ] {
+        env::remove_var(name);
+    }
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    debug_span!("too_quiet").in_scope(|| {});
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    assert_eq!(requests[0].header("authorization"), Some("Splunk abc"));
+    let events = requests[0].events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["index"], "app_logs");
+    assert_eq!(events[0]["sourcetype"], "_json");
+}
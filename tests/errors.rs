@@ -0,0 +1,203 @@
+use crate::common::{MockHec, MockResponse};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{
+    BatchSummary, BuildError, Error, ErrorPolicy, LayerError, SplunkHecLayer,
+};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::reload;
+
+#[test]
+fn spans_from_before_the_layer_was_added_are_reported_not_panicked_on() {
+    let hec = MockHec::start();
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let seen = errors.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .error_policy(ErrorPolicy::callback(move |e: &Error| {
+            seen.lock().unwrap().push(e.to_string());
+        }))
+        .build()
+        .unwrap();
+
+    // the layer only shows up after the span has been created, so it never got to set it up
+    let (reloadable, handle) = reload::Layer::new(None);
+    let _default = tracing_subscriber::registry()
+        .with(reloadable)
+        // an empty reload layer would otherwise turn every callsite off
+        .with(LevelFilter::TRACE)
+        .set_default();
+    let span = info_span!("early", status = tracing::field::Empty);
+    handle.modify(|l| *l = Some(layer)).unwrap();
+
+    span.record("status", 200);
+    drop(span);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert!(hec.requests().is_empty());
+    assert_eq!(
+        *errors.lock().unwrap(),
+        vec![
+            "span `early` is missing its splunk hec data",
+            "span `early` is missing its splunk hec data"
+        ]
+    );
+}
+
+#[test]
+fn export_failures_go_to_the_error_policy() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(
+        400,
+        r#"{"text":"Invalid data format","code":6}"#,
+    ));
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let seen = errors.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .error_policy(ErrorPolicy::callback(move |e: &Error| {
+            seen.lock().unwrap().push(matches!(
+                e,
+                Error::Layer(LayerError::Export { events: 1, .. })
+            ));
+        }))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    tracing::info!("rejected");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(*errors.lock().unwrap(), vec![true]);
+}
+
+#[test]
+fn a_wrong_token_is_turned_away_without_retrying() {
+    let hec = MockHec::start().require_token("right");
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let seen = errors.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("wrong")
+        .error_policy(ErrorPolicy::callback(move |e: &Error| {
+            seen.lock().unwrap().push(e.to_string());
+        }))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    tracing::info!("unauthorized");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(hec.requests().len(), 1);
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("HEC returned 403 (code 4): Invalid token"));
+}
+
+#[test]
+fn panics_are_shipped_before_they_carry_on() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    layer.install_panic_hook();
+
+    let panicked = std::thread::Builder::new()
+        .name("doomed".to_string())
+        .spawn(|| panic!("out of cheese"))
+        .unwrap()
+        .join();
+    // back to the default hook, for whatever else panics in this test binary
+    drop(std::panic::take_hook());
+    assert!(panicked.is_err());
+
+    // the hook flushed, so the event is already there without waiting on the flush interval
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let event = events
+        .iter()
+        .map(|e| &e["event"])
+        .find(|e| e["message"] == "out of cheese")
+        .unwrap();
+    assert_eq!(event["level"], "ERROR");
+    assert_eq!(event["panic.thread"], "doomed");
+    assert!(event["panic.location"]
+        .as_str()
+        .unwrap()
+        .starts_with("tests/errors.rs:"));
+}
+
+#[test]
+fn export_hooks_hear_about_every_batch() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(
+        400,
+        r#"{"text":"Invalid data format","code":6}"#,
+    ));
+    let failures = Arc::new(Mutex::new(Vec::new()));
+    let successes = Arc::new(Mutex::new(Vec::new()));
+    let (failed, succeeded) = (failures.clone(), successes.clone());
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .error_policy(ErrorPolicy::Ignore)
+        .on_export_error(move |e: &Error| {
+            let Error::Layer(LayerError::Export {
+                events,
+                attempts,
+                source,
+            }) = e
+            else {
+                panic!("{}", e);
+            };
+            failed
+                .lock()
+                .unwrap()
+                .push((*events, *attempts, source.is_retryable()));
+        })
+        .on_export_success(move |summary: &BatchSummary| {
+            succeeded
+                .lock()
+                .unwrap()
+                .push((summary.events, summary.attempts, summary.bytes));
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("rejected").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+    info_span!("accepted").in_scope(|| {});
+    info_span!("accepted").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(*failures.lock().unwrap(), vec![(1, 1, false)]);
+    let bytes = hec.requests()[1].body.len();
+    assert_eq!(*successes.lock().unwrap(), vec![(2, 1, bytes)]);
+}
+
+#[test]
+fn everything_converts_into_the_crate_error() {
+    fn setup(endpoint: Option<&str>) -> Result<(), Error> {
+        let mut builder = SplunkHecLayer::builder().token("abc");
+        if let Some(endpoint) = endpoint {
+            builder = builder.endpoint(endpoint);
+        }
+        let (_layer, guard) = builder.build()?;
+        guard.flush(Duration::from_secs(5))?;
+        Ok(())
+    }
+
+    let err = setup(None).unwrap_err();
+    assert!(matches!(err, Error::Config(BuildError::MissingEndpoint)));
+    assert_eq!(err.to_string(), BuildError::MissingEndpoint.to_string());
+
+    let hec = MockHec::start();
+    setup(Some(hec.url())).unwrap();
+}
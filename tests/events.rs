@@ -0,0 +1,539 @@
+use crate::common::MockHec;
+use std::ops::ControlFlow;
+use std::time::Duration;
+use tracing::{error, info, info_span, warn};
+use tracing_splunk_layer::{
+    splunk_event, ByteEncoding, Encoded, EventRecord, MessageField, Serialized, SpanEventMode,
+    SplunkHecLayer,
+};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn events_outside_of_spans_are_shipped_on_their_own() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .index("app_logs")
+        .flush_interval(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    warn!(answer = 42, "top level");
+    info_span!("request").in_scope(|| {});
+
+    let requests = hec.wait_for_requests(1);
+    let events = requests[0].events();
+    assert_eq!(events.len(), 2);
+
+    let standalone = &events[0];
+    assert_eq!(standalone["index"], "app_logs");
+    assert!(standalone["time"].as_f64().unwrap() > 0.0);
+    assert_eq!(standalone["event"]["message"], "top level");
+    assert_eq!(standalone["event"]["answer"], 42);
+    assert_eq!(standalone["event"]["level"], "WARN");
+    assert_eq!(standalone["event"]["target"], module_path!());
+}
+
+#[test]
+fn spans_are_wrapped_in_the_hec_envelope() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .sourcetype("_json")
+        .indexed_fields(["request_id"])
+        .flush_interval(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", request_id = "abc-123", status = 200).in_scope(|| {});
+
+    let requests = hec.wait_for_requests(1);
+    let record = &requests[0].events()[0];
+    assert!(record["time"].as_f64().unwrap() > 0.0);
+    assert_eq!(record["sourcetype"], "_json");
+    assert_eq!(record["event"]["status"], 200);
+    assert!(record["event"].get("request_id").is_none());
+    assert_eq!(record["fields"]["request_id"], "abc-123");
+}
+
+#[test]
+fn tracing_metadata_is_recorded() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .with_target(false)
+        .with_module_path(true)
+        .with_file(true)
+        .with_line_number(true)
+        .flush_interval(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+
+    let requests = hec.wait_for_requests(1);
+    let event = &requests[0].events()[0]["event"];
+    assert_eq!(event["level"], "INFO");
+    assert_eq!(event["name"], "request");
+    assert!(event.get("target").is_none());
+    assert_eq!(event["module_path"], module_path!());
+    assert_eq!(event["file"], file!());
+    assert!(event["line"].as_u64().is_some());
+}
+
+#[test]
+fn events_from_inside_the_layer_are_ignored() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        // a callback that logs runs inside on_close, which would otherwise loop straight back in
+        .redact_with(|_, _| warn!("redacting"))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", user = "bob").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events = hec.requests()[0].events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"]["name"], "request");
+}
+
+#[derive(Debug)]
+struct Wrapped(&'static str, Option<Box<Wrapped>>);
+
+impl std::fmt::Display for Wrapped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for Wrapped {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.1.as_deref().map(|e| e as _)
+    }
+}
+
+#[test]
+fn errors_are_recorded_with_their_source_chain() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .with_error_debug(true)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let e = Wrapped(
+        "failed to load config",
+        Some(Box::new(Wrapped(
+            "failed to read config.toml",
+            Some(Box::new(Wrapped("permission denied", None))),
+        ))),
+    );
+    error!(
+        error = &e as &(dyn std::error::Error + 'static),
+        "startup failed"
+    );
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(event["error.message"], "failed to load config");
+    assert_eq!(
+        event["error.chain"],
+        serde_json::json!(["failed to read config.toml", "permission denied"])
+    );
+    assert!(event["error.debug"]
+        .as_str()
+        .unwrap()
+        .starts_with("Wrapped(\"failed to load config\""));
+    assert!(event.get("error").is_none());
+}
+
+#[test]
+fn processors_can_change_and_drop_events() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .processor(|record: &mut EventRecord| {
+            if record.event.contains_key("healthcheck") {
+                return ControlFlow::Break(());
+            }
+            record.event.insert("enriched".into(), true.into());
+            ControlFlow::Continue(())
+        })
+        .processor(|record: &mut EventRecord| {
+            record.metadata.sourcetype = Some("enriched".to_string());
+            ControlFlow::Continue(())
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("ping", healthcheck = true).in_scope(|| {});
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"]["name"], "request");
+    assert_eq!(events[0]["event"]["enriched"], true);
+    assert_eq!(events[0]["sourcetype"], "enriched");
+}
+
+#[test]
+fn global_fields_are_added_to_everything() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .global_field("service", "checkout")
+        .global_fields([("region", "eu-west-1"), ("env", "prod")])
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    warn!("top level");
+    info_span!("request", env = "canary").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    for event in &events {
+        assert_eq!(event["event"]["service"], "checkout");
+        assert_eq!(event["event"]["region"], "eu-west-1");
+    }
+    assert_eq!(events[0]["event"]["env"], "prod");
+    assert_eq!(events[1]["event"]["env"], "canary");
+}
+
+#[test]
+fn process_details_are_attached() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .with_thread_ids(true)
+        .with_thread_names(true)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let exported = &hec.requests()[0].events()[0];
+    let event = &exported["event"];
+    assert!(!exported["host"].as_str().unwrap().is_empty());
+    assert_eq!(event["pid"], std::process::id());
+    assert!(event["executable"].is_string());
+    assert!(event["thread.id"].is_u64());
+    assert_eq!(event["thread.name"], std::thread::current().name().unwrap());
+
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .with_hostname(false)
+        .with_pid(false)
+        .with_executable(false)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let exported = &hec.requests()[0].events()[0];
+    assert!(exported.get("host").is_none());
+    for field in ["pid", "executable", "thread.id", "thread.name"] {
+        assert!(exported["event"].get(field).is_none(), "{}", field);
+    }
+}
+
+#[test]
+fn message_can_be_the_event_text() {
+    let exported = |mode| {
+        let hec = MockHec::start();
+        let (layer, guard) = SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .with_pid(false)
+            .with_executable(false)
+            .message_field(mode)
+            .build()
+            .unwrap();
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+
+        info!(user = "bob", "logged in");
+        info_span!("request").in_scope(|| {});
+        guard.flush(Duration::from_secs(5)).unwrap();
+        hec.requests()[0].events()
+    };
+
+    let events = exported(MessageField::Field);
+    assert_eq!(events[0]["event"]["message"], "logged in");
+    assert_eq!(events[0]["event"]["user"], "bob");
+
+    let events = exported(MessageField::Event);
+    assert_eq!(events[0]["event"], "logged in");
+    assert_eq!(events[0]["fields"]["user"], "bob");
+    assert!(events[0]["fields"].get("message").is_none());
+    // nothing to turn into text
+    assert_eq!(events[1]["event"]["name"], "request");
+
+    let events = exported(MessageField::Both);
+    assert_eq!(events[0]["event"], "logged in");
+    assert_eq!(events[0]["fields"]["message"], "logged in");
+}
+
+#[test]
+fn only_flat_values_are_sent_as_indexed_fields() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .with_pid(false)
+        .with_executable(false)
+        .span_event_mode(SpanEventMode::List)
+        .message_field(MessageField::Event)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", message = "handled", ok = true).in_scope(|| info!(rows = 3, "queried"));
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let span = &hec.requests()[0].events()[0];
+    assert_eq!(span["event"], "handled");
+    let fields = span["fields"].as_object().unwrap();
+    for (name, value) in fields {
+        let flat = |value: &serde_json::Value| value.is_string() || value.is_number();
+        let array = value
+            .as_array()
+            .is_some_and(|values| values.iter().all(flat));
+        assert!(flat(value) || array, "{} is {}", name, value);
+    }
+    assert_eq!(fields["ok"], "true");
+    let events: serde_json::Value =
+        serde_json::from_str(fields["events"].as_str().unwrap()).unwrap();
+    assert_eq!(events[0]["message"], "queried");
+    assert_eq!(events[0]["rows"], 3);
+}
+
+#[test]
+fn long_strings_are_truncated() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Dump {
+        rows: Vec<u32>,
+    }
+
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_field_length(10)
+        .max_field_length_for("accent", 3)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let dump = Dump {
+        rows: (0..1000).collect(),
+    };
+    info!(
+        long = "a".repeat(50).as_str(),
+        short = "fits",
+        accent = "ééééé",
+        ?dump,
+        "done"
+    );
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(event["long"], "aaaaaaaaaa…");
+    assert_eq!(event["long.original_length"], 50);
+    assert_eq!(event["short"], "fits");
+    assert!(event.get("short.original_length").is_none());
+    assert_eq!(event["accent"], "é…");
+    assert_eq!(event["accent.original_length"], 10);
+    assert_eq!(event["dump"], "Dump { row…");
+    assert!(event["dump.original_length"].as_u64().unwrap() > 1000);
+}
+
+#[test]
+fn byte_slices_are_encoded_instead_of_debug_dumped() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let (hex_layer, hex_guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .byte_encoding(ByteEncoding::Hex)
+        .build()
+        .unwrap();
+
+    let body: &[u8] = b"hi\xff\x00";
+    {
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+        info!(body, one = &b"M"[..], two = &b"Ma"[..], "sent");
+    }
+    guard.flush(Duration::from_secs(5)).unwrap();
+    {
+        let _default = tracing_subscriber::registry().with(hex_layer).set_default();
+        info!(body, digest = %Encoded::base64(b"Man"), "sent");
+    }
+    hex_guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    let event = &requests[0].events()[0]["event"];
+    assert_eq!(event["body"], "aGn/AA==");
+    assert_eq!(event["one"], "TQ==");
+    assert_eq!(event["two"], "TWE=");
+    let event = &requests[1].events()[0]["event"];
+    assert_eq!(event["body"], "6869ff00");
+    assert_eq!(event["digest"], "TWFu");
+}
+
+#[test]
+fn wide_integers_are_numbers_when_they_fit_and_strings_when_they_dont() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!(
+        nanos = Duration::from_secs(3).as_nanos(),
+        trace = 0x4bf92f3577b34da6a3ce929d0e0e4736u128,
+        small = -5i128,
+        big = i128::MIN,
+        "done"
+    );
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(event["nanos"], 3_000_000_000u64);
+    assert_eq!(
+        event["trace"],
+        0x4bf92f3577b34da6a3ce929d0e0e4736u128.to_string()
+    );
+    assert_eq!(event["small"], -5);
+    assert_eq!(event["big"], i128::MIN.to_string());
+}
+
+#[test]
+fn debug_values_that_look_like_numbers_can_be_parsed_back() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Count(u32);
+
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .parse_debug_field("status")
+        .build()
+        .unwrap();
+    let (all_layer, all_guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .with_debug_value_parsing(true)
+        .build()
+        .unwrap();
+
+    let count = Count(3);
+    {
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+        info!(status = %404, ratio = ?0.5, "served");
+    }
+    guard.flush(Duration::from_secs(5)).unwrap();
+    {
+        let _default = tracing_subscriber::registry().with(all_layer).set_default();
+        info!(
+            ratio = ?0.5,
+            ok = ?true,
+            huge = %u128::MAX,
+            nan = ?f64::NAN,
+            ?count,
+            "42"
+        );
+    }
+    all_guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    let event = &requests[0].events()[0]["event"];
+    assert_eq!(event["status"], 404);
+    assert_eq!(event["ratio"], "0.5");
+    let event = &requests[1].events()[0]["event"];
+    assert_eq!(event["ratio"], 0.5);
+    assert_eq!(event["ok"], true);
+    assert_eq!(event["huge"], u128::MAX.to_string());
+    assert_eq!(event["nan"], "NaN");
+    assert_eq!(event["count"], "Count(3)");
+    assert_eq!(event["message"], "42");
+}
+
+#[test]
+fn serialize_values_are_spread_out_into_fields() {
+    #[derive(serde::Serialize)]
+    struct Order {
+        id: u32,
+        customer: &'static str,
+        items: Vec<&'static str>,
+    }
+
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let order = Order {
+        id: 7,
+        customer: "ana",
+        items: vec!["tea", "cake"],
+    };
+    splunk_event!(
+        tracing::Level::WARN,
+        &order,
+        late = true,
+        "order {} is late",
+        order.id
+    );
+    let span = info_span!("checkout", splunk.fields = %Serialized(&order));
+    span.in_scope(|| splunk_event!(&42));
+    drop(span);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events = hec.requests()[0].events();
+    let late = &events[0]["event"];
+    assert_eq!(late["id"], 7);
+    assert_eq!(late["customer"], "ana");
+    assert_eq!(late["items"], serde_json::json!(["tea", "cake"]));
+    assert_eq!(late["late"], true);
+    assert_eq!(late["message"], "order 7 is late");
+    assert!(late.get("splunk.fields").is_none());
+    assert_eq!(late["level"], "WARN");
+
+    let in_span = &events[1]["event"];
+    assert_eq!(in_span["splunk.fields"], 42);
+    assert_eq!(in_span["customer"], "ana");
+}
@@ -0,0 +1,37 @@
+use serde_json::json;
+use tracing_splunk_layer::FieldMap;
+
+#[test]
+fn field_maps_act_the_same_before_and_after_they_grow() {
+    let mut small = FieldMap::new();
+    let mut large = FieldMap::new();
+    for i in 0..40 {
+        large.insert(format!("field{}", i).into(), i.into());
+    }
+    for i in 0..3 {
+        small.insert(format!("field{}", i).into(), i.into());
+    }
+
+    for map in [&mut small, &mut large] {
+        assert_eq!(map.insert("field1".into(), json!("again")), Some(json!(1)));
+        assert_eq!(map["field1"], "again");
+        assert_eq!(*map.entry("field2".into()).or_insert(json!(0)), 2);
+        *map.entry("new".into()).or_insert(json!(0)) = json!(7);
+        assert_eq!(map.get("new"), Some(&json!(7)));
+        assert_eq!(map.remove("field0"), Some(json!(0)));
+        assert!(!map.contains_key("field0"));
+        map.retain(|name, _| name != "field2");
+        assert!(map.get("field2").is_none());
+    }
+    assert_eq!(small.len(), 2);
+    assert_eq!(large.len(), 39);
+
+    // equal whichever way they're kept
+    let grown: FieldMap = large
+        .clone()
+        .into_iter()
+        .filter(|(name, _)| name == "field1" || name == "new")
+        .collect();
+    assert_eq!(grown, small);
+    assert_eq!(serde_json::to_value(&large).unwrap()["field39"], json!(39));
+}
@@ -0,0 +1,220 @@
+use crate::common::MockHec;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, debug_span, info, info_span};
+use tracing_splunk_layer::{RateLimit, RateLimitConfig, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn max_level_skips_spans_and_events_below_it() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_level(tracing::Level::INFO)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("outer", a = 1).in_scope(|| {
+        debug_span!("inner", b = 2).in_scope(|| {
+            info!(c = 3, "kept");
+            debug!(d = 4, "dropped");
+        });
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    // the debug span is skipped over, so the info event ends up on `outer`
+    let events = hec.requests()[0].events();
+    assert_eq!(events.len(), 1);
+    let event = &events[0]["event"];
+    assert_eq!(event["name"], "outer");
+    assert_eq!(event["a"], 1);
+    assert_eq!(event["c"], 3);
+    assert!(event.get("b").is_none());
+    assert!(event.get("d").is_none());
+}
+
+#[test]
+fn targets_can_be_allowed_and_denied() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .allow_targets(["app"])
+        .deny_targets(["app::noisy"])
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!(target: "app", "kept");
+    info!(target: "app::db", "kept");
+    info!(target: "app::noisy", "denied");
+    info!(target: "app_extras", "not allowed");
+    info!(target: "other", "not allowed");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let targets: Vec<String> = hec.requests()[0]
+        .events()
+        .iter()
+        .map(|e| e["event"]["target"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(targets, vec!["app", "app::db"]);
+}
+
+#[test]
+fn rate_limited_events_are_summarized() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .rate_limit(RateLimitConfig {
+            per_target: Some(RateLimit {
+                per_second: 0.001,
+                burst: 2,
+            }),
+            ..RateLimitConfig::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..5 {
+        info!(target: "noisy", i, "looping");
+    }
+    info!(target: "quiet", "once");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    assert_eq!(guard.metrics().snapshot().dropped_rate_limited, 3);
+    // the summary goes out on shutdown at the latest
+    drop(guard);
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 4);
+    assert_eq!(events[0]["event"]["i"], 0);
+    assert_eq!(events[1]["event"]["i"], 1);
+    assert_eq!(events[2]["event"]["message"], "once");
+    let summary = &events[3]["event"];
+    assert_eq!(summary["rate_limited.count"], 3);
+    assert_eq!(summary["rate_limited.target"], "noisy");
+    assert_eq!(
+        summary["message"],
+        "3 events from noisy were suppressed by the rate limit"
+    );
+}
+
+#[test]
+fn a_rate_that_isnt_a_number_or_is_negative_only_lets_the_burst_through() {
+    for per_second in [f64::NAN, -1.0] {
+        let hec = MockHec::start();
+        let (layer, guard) = SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .rate_limit(RateLimitConfig {
+                global: Some(RateLimit {
+                    per_second,
+                    burst: 2,
+                }),
+                ..RateLimitConfig::default()
+            })
+            .build()
+            .unwrap();
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+
+        for i in 0..5 {
+            info!(i, "looping");
+        }
+        guard.flush(Duration::from_secs(5)).unwrap();
+        assert_eq!(guard.metrics().snapshot().dropped_rate_limited, 3);
+    }
+}
+
+#[test]
+fn filtered_callsites_are_turned_off_only_for_this_layer() {
+    // counts every event it's shown, standing in for fmt
+    #[derive(Clone, Default)]
+    struct Seen(Arc<AtomicUsize>);
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Seen {
+        fn on_event(&self, _: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let hec = MockHec::start();
+    let build = || {
+        SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .max_level(tracing::Level::INFO)
+            .deny_targets(["hyper"])
+            .build()
+            .unwrap()
+    };
+
+    let (layer, guard) = build();
+    let seen = Seen::default();
+    {
+        let _default = tracing_subscriber::registry()
+            .with(layer.filtered())
+            .with(seen.clone())
+            .set_default();
+        info_span!("request").in_scope(|| {
+            debug!("chatty");
+            info!(target: "hyper", "chatty");
+            info!("kept");
+        });
+    }
+    guard.flush(Duration::from_secs(5)).unwrap();
+    assert_eq!(seen.0.load(Ordering::SeqCst), 3);
+    let events = hec.requests()[0].events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"]["message"], "kept");
+
+    // on its own, nobody wants those callsites at all
+    let (layer, _guard) = build();
+    let _default = tracing_subscriber::registry()
+        .with(layer.filtered())
+        .set_default();
+    assert!(!tracing::enabled!(tracing::Level::DEBUG));
+    assert!(!tracing::enabled!(target: "hyper", tracing::Level::INFO));
+    assert!(tracing::enabled!(tracing::Level::INFO));
+}
+
+#[test]
+fn the_filter_can_be_changed_while_running() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_level(tracing::Level::INFO)
+        .build()
+        .unwrap();
+    let handle = layer.filter_handle();
+    let _default = tracing_subscriber::registry()
+        .with(layer.filtered())
+        .set_default();
+
+    let emit = || {
+        debug!(target: "app::payments", "payments");
+        debug!(target: "app::payments::card", "card");
+        debug!(target: "app::search", "search");
+        info!(target: "app::search", "search info");
+    };
+    emit();
+    handle
+        .set_filter("warn, app::payments=debug, app::payments::card=off")
+        .unwrap();
+    emit();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let messages: Vec<String> = hec
+        .requests()
+        .iter()
+        .flat_map(|r| r.events())
+        .map(|e| e["event"]["message"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(messages, ["search info", "payments"]);
+
+    let error = handle.set_filter("info,app=loud").unwrap_err();
+    assert_eq!(error.directive, "app=loud");
+}
@@ -0,0 +1,117 @@
+use crate::common::{MockHec, MockResponse};
+use std::time::{Duration, Instant};
+use tracing::{info, info_span};
+use tracing_splunk_layer::{ErrorPolicy, RetryPolicy, ShutdownReport, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+fn builder(hec: &MockHec) -> tracing_splunk_layer::SplunkHecLayerBuilder {
+    // a flush interval long enough that only the guard could have shipped anything
+    SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .flush_interval(Duration::from_secs(60))
+}
+
+#[test]
+fn flush_ships_buffered_events() {
+    let hec = MockHec::start();
+    let (layer, guard) = builder(&hec).build().unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(hec.requests().len(), 1);
+}
+
+#[test]
+fn dropping_the_guard_ships_buffered_events() {
+    let hec = MockHec::start();
+    let (layer, guard) = builder(&hec).build().unwrap();
+    let default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    drop(default);
+    drop(guard);
+
+    assert_eq!(hec.requests().len(), 1);
+}
+
+#[test]
+fn shutdown_ships_what_was_queued_and_turns_away_the_rest() {
+    let hec = MockHec::start();
+    let (layer, guard) = builder(&hec).build().unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("before");
+    let metrics = guard.metrics();
+    let report = guard.shutdown(Duration::from_secs(5));
+    info!("after");
+
+    assert_eq!(report, ShutdownReport::default());
+    let events = hec.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"]["message"], "before");
+    assert_eq!(metrics.snapshot().dropped_closed, 1);
+}
+
+#[test]
+fn shutdown_gives_up_on_retries_at_the_deadline() {
+    let hec = MockHec::start();
+    for _ in 0..100 {
+        hec.respond_with(MockResponse::busy());
+    }
+    let (layer, guard) = builder(&hec)
+        .retry_policy(RetryPolicy {
+            max_attempts: 100,
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(20),
+        })
+        .error_policy(ErrorPolicy::Ignore)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..3 {
+        info!("{}", i);
+    }
+    let started = Instant::now();
+    let metrics = guard.metrics();
+    let report = guard.shutdown(Duration::from_millis(200));
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert_eq!(report.abandoned, 3);
+    assert!(!report.timed_out);
+    assert_eq!(metrics.snapshot().dropped_export_failed, 3);
+}
+
+#[test]
+fn the_deadline_covers_batches_still_queued_ahead_of_the_shutdown() {
+    let hec = MockHec::start();
+    for _ in 0..1000 {
+        hec.respond_with(MockResponse::busy());
+    }
+    let (layer, guard) = builder(&hec)
+        .max_batch_events(2)
+        .retry_policy(RetryPolicy {
+            max_attempts: 100,
+            initial_backoff: Duration::from_millis(20),
+            max_backoff: Duration::from_millis(20),
+        })
+        .error_policy(ErrorPolicy::Ignore)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    // five full batches, the worker is still retrying the first when shutdown is called
+    for i in 0..10 {
+        info!("{}", i);
+    }
+    let started = Instant::now();
+    let report = guard.shutdown(Duration::from_millis(300));
+
+    // each batch would take two seconds with every retry it's allowed
+    assert!(started.elapsed() < Duration::from_millis(300) + Duration::from_millis(500));
+    assert_eq!(report.abandoned, 10);
+    assert!(!report.timed_out);
+}
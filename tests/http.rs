@@ -0,0 +1,75 @@
+#![cfg(feature = "http")]
+
+use crate::common::MockHec;
+use std::convert::Infallible;
+use std::future::{ready, Future, Ready};
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing_splunk_layer::{HttpSpanLayer, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+// answers every request with a 404 and a log line
+struct NotFound;
+
+impl Service<http::Request<()>> for NotFound {
+    type Response = http::Response<()>;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _: http::Request<()>) -> Self::Future {
+        tracing::info!("no such page");
+        let mut response = http::Response::new(());
+        *response.status_mut() = http::StatusCode::NOT_FOUND;
+        ready(Ok(response))
+    }
+}
+
+// the service's futures are always ready, so there's no need for a runtime
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn requests_get_a_span_with_http_fields() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let mut service = HttpSpanLayer::new().layer(NotFound);
+    let request = http::Request::post("/orders/42")
+        .header(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .body(())
+        .unwrap();
+    let response = block_on(service.call(request)).unwrap();
+    assert_eq!(response.status(), 404);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(event["name"], "http_request");
+    assert_eq!(event["http.method"], "POST");
+    assert_eq!(event["http.route"], "/orders/42");
+    assert_eq!(event["http.status_code"], 404);
+    assert_eq!(event["message"], "no such page");
+    assert_eq!(event["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert!(event["elapsed_time"].is_number());
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "log")]
+
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing_log::log;
+use tracing_splunk_layer::SplunkHecLayer;
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn log_records_are_exported_on_their_own() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    // there's only one logger per process, and set_default installs tracing-subscriber's own
+    // bridge (it's the same one) if it can, so this has most likely been done already
+    let _ = tracing_splunk_layer::init_log_bridge();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    tracing::info_span!("request").in_scope(|| {
+        log::warn!(target: "hyper::proto", "connection reset");
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 2);
+    let record = &events[0]["event"];
+    assert_eq!(record["message"], "connection reset");
+    assert_eq!(record["level"], "WARN");
+    assert_eq!(record["target"], "hyper::proto");
+    assert!(record.get("log.target").is_none());
+    // and it didn't end up in the span either
+    assert!(events[1]["event"].get("message").is_none());
+}
+
+#[test]
+fn log_records_are_filtered_by_their_own_target() {
+    let run = |filtered: bool| {
+        let hec = MockHec::start();
+        let (layer, guard) = SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .allow_targets(["hyper", "main"])
+            .deny_targets(["hyper::client"])
+            .build()
+            .unwrap();
+        let _ = tracing_splunk_layer::init_log_bridge();
+        // as a per-layer filter, tracing caches what it says about the bridge's callsites
+        let _default = if filtered {
+            tracing_subscriber::registry()
+                .with(layer.filtered())
+                .set_default()
+        } else {
+            tracing_subscriber::registry().with(layer).set_default()
+        };
+
+        log::warn!(target: "hyper::client", "denied");
+        log::warn!(target: "hyper::proto", "connection reset");
+        log::warn!(target: "other", "not allowed");
+        log::warn!(target: "hyper::proto", "again");
+        guard.flush(Duration::from_secs(5)).unwrap();
+
+        hec.requests()
+            .iter()
+            .flat_map(|r| r.events())
+            .map(|e| e["event"]["message"].clone())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(run(false), vec!["connection reset", "again"]);
+    assert_eq!(run(true), vec!["connection reset", "again"]);
+}
@@ -1 +1,36 @@
+mod ack;
+mod batching;
+mod builder;
+mod capture;
+mod circuit;
+mod cloud;
+mod common;
+mod config;
+mod dead_letter;
+mod env;
+mod errors;
+mod events;
+mod field_map;
+mod filter;
+mod guard;
+mod http;
+mod log;
+mod metrics;
+mod opentelemetry;
+mod probe;
+mod proxy;
+mod queue;
+mod redact;
+mod reload;
+mod rename;
+mod retry;
+mod routing;
+mod runtime;
+mod sampling;
+mod signal;
 mod spans;
+mod spool;
+mod timestamps;
+mod tls;
+mod transport;
+mod valuable;
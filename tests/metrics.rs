@@ -0,0 +1,55 @@
+use crate::common::{MockHec, MockResponse};
+use std::time::Duration;
+use tracing::info;
+use tracing_splunk_layer::{DropReason, ErrorPolicy, RetryPolicy, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn sent_events_and_retries_are_counted() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(503, "busy"));
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .retry_policy(RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..RetryPolicy::default()
+        })
+        .build()
+        .unwrap();
+    let metrics = layer.metrics();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("one");
+    info!("two");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.events_sent, 2);
+    assert_eq!(snapshot.batches_sent, 1);
+    assert_eq!(snapshot.bytes_sent, hec.requests()[1].body.len() as u64);
+    assert_eq!(snapshot.retries, 1);
+    assert_eq!(snapshot.queue_depth, 0);
+    assert_eq!(snapshot.dropped_total(), 0);
+}
+
+#[test]
+fn dropped_events_are_counted_by_reason() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(400, "bad"));
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .error_policy(ErrorPolicy::Ignore)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("rejected");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let snapshot = guard.metrics().snapshot();
+    assert_eq!(snapshot.dropped(DropReason::ExportFailed), 1);
+    assert_eq!(snapshot.dropped(DropReason::QueueFull), 0);
+    assert_eq!(snapshot.events_sent, 0);
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "opentelemetry")]
+
+use crate::common::MockHec;
+use opentelemetry::trace::{TraceContextExt, TracerProvider};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::time::Duration;
+use tracing::info_span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_splunk_layer::SplunkHecLayer;
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn opentelemetry_ids_are_reused() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let provider = SdkTracerProvider::builder().build();
+    let otel = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+    let _default = tracing_subscriber::registry()
+        .with(otel)
+        .with(layer)
+        .set_default();
+
+    let (trace_id, request_id, query_id) = info_span!("request").in_scope(|| {
+        let request = tracing::Span::current().context();
+        info_span!("query").in_scope(|| {
+            let query = tracing::Span::current().context();
+            (
+                request.span().span_context().trace_id().to_string(),
+                request.span().span_context().span_id().to_string(),
+                query.span().span_context().span_id().to_string(),
+            )
+        })
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let (query, request) = (&events[0]["event"], &events[1]["event"]);
+    assert_eq!(request["trace_id"], trace_id);
+    assert_eq!(request["span_id"], request_id);
+    assert_eq!(query["trace_id"], trace_id);
+    assert_eq!(query["span_id"], query_id);
+    assert_eq!(query["parent_id"], request_id);
+}
@@ -0,0 +1,71 @@
+use crate::common::{config_error, MockHec, MockResponse};
+use tracing_splunk_layer::{BuildError, ProbeError, SplunkHecLayer};
+
+#[test]
+fn the_startup_probe_checks_health_and_the_token() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::success());
+    hec.respond_with(MockResponse::status(400, r#"{"text":"No data","code":5}"#));
+    let built = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .startup_probe()
+        .build();
+    assert!(built.is_ok());
+
+    let requests = hec.requests();
+    assert_eq!(requests[0].path, "/services/collector/health");
+    assert_eq!(requests[1].path, "/services/collector/event");
+    assert_eq!(requests[1].header("authorization"), Some("Splunk abc"));
+    assert_eq!(requests[1].body, "");
+}
+
+#[test]
+fn a_bad_token_fails_the_build() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::success());
+    hec.respond_with(MockResponse::status(
+        403,
+        r#"{"text":"Invalid token","code":4}"#,
+    ));
+    let err = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("wrong")
+        .startup_probe()
+        .build()
+        .err()
+        .map(config_error);
+    assert_eq!(
+        err,
+        Some(BuildError::Probe(ProbeError::InvalidToken {
+            status: 403,
+            code: Some(4),
+            text: "Invalid token".to_owned(),
+        }))
+    );
+}
+
+#[test]
+fn an_unhealthy_hec_fails_the_build() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(
+        503,
+        r#"{"text":"Server is busy","code":9}"#,
+    ));
+    let err = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .startup_probe()
+        .build()
+        .err()
+        .map(config_error);
+    assert_eq!(
+        err,
+        Some(BuildError::Probe(ProbeError::Unhealthy {
+            status: 503,
+            text: "Server is busy".to_owned(),
+        }))
+    );
+    // there's no point checking the token against a HEC that's down
+    assert_eq!(hec.requests().len(), 1);
+}
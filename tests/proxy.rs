@@ -0,0 +1,69 @@
+use crate::common::MockHec;
+use std::io::{BufRead, BufReader};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{ProxyConfig, RetryPolicy, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+// accepts one connection and hands back the head of the request the client sent it
+fn fake_proxy() -> (String, mpsc::Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut head = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                break;
+            }
+            head.push(line.trim().to_owned());
+        }
+        let _ = tx.send(head);
+    });
+    (url, rx)
+}
+
+#[test]
+fn requests_go_through_the_configured_proxy() {
+    let (proxy, head) = fake_proxy();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint("http://splunk.invalid:8088")
+        .token("abc")
+        .proxy(ProxyConfig::new(proxy).basic_auth("user", "pass"))
+        .retry_policy(RetryPolicy::none())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    let _ = guard.flush(Duration::from_secs(1));
+
+    let head = head.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(head[0].contains("splunk.invalid:8088"), "{:?}", head);
+    // base64 of user:pass
+    assert!(head.contains(&"Proxy-Authorization: Basic dXNlcjpwYXNz".to_owned()));
+}
+
+#[test]
+fn no_proxy_hosts_are_connected_to_directly() {
+    let hec = MockHec::start();
+    // nothing listens on port 1, so going through the proxy would fail
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .proxy(ProxyConfig::new("http://127.0.0.1:1").no_proxy("localhost,127.0.0.1"))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(hec.requests().len(), 1);
+}
@@ -0,0 +1,243 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn, Level};
+use tracing_splunk_layer::{
+    Batch, Error, ErrorPolicy, FlushError, HecResponse, LoadShedding, QueueFullPolicy,
+    SplunkHecLayer, SplunkHecLayerBuilder, Transport, TransportFuture, WorkerGuard,
+};
+use tracing_subscriber::prelude::*;
+
+// a transport that holds the worker up in its first send until it's opened, so the queue behind
+// it can be filled
+#[derive(Clone, Default)]
+struct Gate {
+    state: Arc<(Mutex<GateState>, Condvar)>,
+}
+
+#[derive(Default)]
+struct GateState {
+    open: bool,
+    messages: Vec<String>,
+}
+
+impl Gate {
+    fn wait_until_sending(&self) {
+        let (state, changed) = &*self.state;
+        let mut state = state.lock().unwrap();
+        while state.messages.is_empty() {
+            state = changed.wait(state).unwrap();
+        }
+    }
+
+    fn open(&self) {
+        let (state, changed) = &*self.state;
+        state.lock().unwrap().open = true;
+        changed.notify_all();
+    }
+
+    fn messages(&self) -> Vec<String> {
+        self.state.0.lock().unwrap().messages.clone()
+    }
+}
+
+impl Transport for Gate {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        let (state, changed) = &*self.state;
+        let mut state = state.lock().unwrap();
+        for line in batch.as_str().lines() {
+            let event: serde_json::Value = serde_json::from_str(line).unwrap();
+            let message = event["event"]["message"].as_str().unwrap_or_default();
+            state.messages.push(message.to_owned());
+        }
+        changed.notify_all();
+        while !state.open {
+            state = changed.wait(state).unwrap();
+        }
+        Box::pin(async { Ok(HecResponse::success()) })
+    }
+}
+
+// a layer whose worker is stuck sending "first"
+fn stuck(
+    configure: impl FnOnce(SplunkHecLayerBuilder) -> SplunkHecLayerBuilder,
+) -> (Gate, tracing::Dispatch, WorkerGuard) {
+    let gate = Gate::default();
+    let builder = SplunkHecLayer::builder()
+        .transport(gate.clone())
+        .max_batch_events(1);
+    let (layer, guard) = configure(builder).build().unwrap();
+    let dispatch = tracing_subscriber::registry().with(layer).into();
+    tracing::dispatcher::with_default(&dispatch, || info!("first"));
+    gate.wait_until_sending();
+    (gate, dispatch, guard)
+}
+
+#[test]
+fn drop_oldest_makes_room_for_new_events() {
+    let (gate, dispatch, guard) = stuck(|b| {
+        b.channel_capacity(2)
+            .queue_full_policy(QueueFullPolicy::DropOldest)
+    });
+    tracing::dispatcher::with_default(&dispatch, || {
+        for i in 1..=5 {
+            info!("{}", i);
+        }
+    });
+    gate.open();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(gate.messages(), ["first", "4", "5"]);
+    assert_eq!(guard.metrics().snapshot().dropped_queue_full, 3);
+}
+
+#[test]
+fn drop_below_only_drops_the_less_severe() {
+    let (gate, dispatch, guard) = stuck(|b| {
+        b.channel_capacity(1)
+            .queue_full_policy(QueueFullPolicy::DropBelow(Level::WARN))
+    });
+    let opener = {
+        let gate = gate.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            gate.open();
+        })
+    };
+    tracing::dispatcher::with_default(&dispatch, || {
+        info!("queued");
+        info!("dropped");
+        debug!("dropped too");
+        // waits for the gate to open and the queue to have room
+        warn!("kept");
+    });
+    opener.join().unwrap();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(gate.messages(), ["first", "queued", "kept"]);
+    assert_eq!(guard.metrics().snapshot().dropped_queue_full, 2);
+}
+
+#[test]
+fn block_for_gives_up_after_the_deadline() {
+    let (gate, dispatch, guard) = stuck(|b| {
+        b.channel_capacity(1)
+            .queue_full_policy(QueueFullPolicy::BlockFor(Duration::from_millis(50)))
+    });
+    let start = Instant::now();
+    tracing::dispatcher::with_default(&dispatch, || {
+        info!("queued");
+        info!("waited, then dropped");
+    });
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    gate.open();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(gate.messages(), ["first", "queued"]);
+    assert_eq!(guard.metrics().snapshot().dropped_queue_full, 1);
+}
+
+#[test]
+fn a_full_queue_is_reported_once_until_it_has_room_again() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let (gate, dispatch, guard) = stuck(|b| {
+        b.channel_capacity(1)
+            .error_policy(ErrorPolicy::callback(move |e: &Error| {
+                reported.lock().unwrap().push(e.to_string());
+            }))
+    });
+    tracing::dispatcher::with_default(&dispatch, || {
+        info!("queued");
+        for _ in 0..3 {
+            info!("dropped");
+        }
+    });
+    assert_eq!(
+        *errors.lock().unwrap(),
+        ["the splunk hec queue is full, dropping events"]
+    );
+
+    gate.open();
+    guard.flush(Duration::from_secs(5)).unwrap();
+    tracing::dispatcher::with_default(&dispatch, || info!("room again"));
+    guard.flush(Duration::from_secs(5)).unwrap();
+    assert_eq!(errors.lock().unwrap().len(), 1);
+    assert_eq!(guard.metrics().snapshot().dropped_queue_full, 3);
+}
+
+#[test]
+fn past_the_high_water_mark_only_the_severe_get_through() {
+    let (gate, dispatch, guard) = stuck(|b| {
+        b.load_shedding(LoadShedding {
+            high_water_mark: Some(2),
+            keep: Level::INFO,
+        })
+    });
+    tracing::dispatcher::with_default(&dispatch, || {
+        debug!("under the mark");
+        info!("at the mark");
+        debug!("shed");
+        trace!("shed too");
+        info!("kept");
+        warn!("warned");
+        error!("failed");
+    });
+    gate.open();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(
+        gate.messages(),
+        [
+            "first",
+            "under the mark",
+            "at the mark",
+            "kept",
+            "warned",
+            "failed"
+        ]
+    );
+    let snapshot = guard.metrics().snapshot();
+    assert_eq!(snapshot.dropped_shed, 2);
+    assert_eq!(snapshot.dropped_queue_full, 0);
+}
+
+#[test]
+fn the_default_high_water_mark_follows_the_channel_capacity() {
+    let (gate, dispatch, guard) =
+        stuck(|b| b.channel_capacity(4).load_shedding(LoadShedding::default()));
+    tracing::dispatcher::with_default(&dispatch, || {
+        for i in 1..=3 {
+            debug!("{}", i);
+        }
+        debug!("shed");
+    });
+    gate.open();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(gate.messages(), ["first", "1", "2", "3"]);
+    assert_eq!(guard.metrics().snapshot().dropped_shed, 1);
+}
+
+#[test]
+fn a_tiny_queue_only_sheds_once_something_is_waiting() {
+    let (gate, dispatch, guard) =
+        stuck(|b| b.channel_capacity(1).load_shedding(LoadShedding::default()));
+    tracing::dispatcher::with_default(&dispatch, || {
+        debug!("kept");
+        debug!("shed");
+    });
+    gate.open();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(gate.messages(), ["first", "kept"]);
+    assert_eq!(guard.metrics().snapshot().dropped_shed, 1);
+}
+
+#[test]
+fn a_flush_that_runs_out_of_time_says_so() {
+    let (gate, _dispatch, guard) = stuck(|b| b);
+    let err = guard.flush(Duration::from_millis(50)).unwrap_err();
+    assert!(matches!(err, Error::Flush(FlushError::Timeout)), "{}", err);
+    gate.open();
+    guard.flush(Duration::from_secs(5)).unwrap();
+}
@@ -0,0 +1,63 @@
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing::{info, info_span};
+use tracing_splunk_layer::{SpanEventMode, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn denied_fields_never_leave_the_process() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_event_mode(SpanEventMode::List)
+        .redact_fields(["password", "SSN"])
+        .redact_with(|name, value| {
+            if name == "email" {
+                *value = "***@***".into();
+            }
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("login", user = "alice", password = "hunter2").in_scope(|| {
+        info!(
+            ssn = "123-45-6789",
+            email = "alice@example.com",
+            "looked up"
+        );
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let request = &hec.requests()[0];
+    assert!(!request.body.contains("hunter2"));
+    assert!(!request.body.contains("123-45-6789"));
+    assert!(!request.body.contains("alice@example.com"));
+
+    let span = &request.events()[0]["event"];
+    assert_eq!(span["user"], "alice");
+    assert_eq!(span["password"], "[REDACTED]");
+    assert_eq!(span["events"][0]["ssn"], "[REDACTED]");
+    assert_eq!(span["events"][0]["email"], "***@***");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn patterns_are_masked_inside_values() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .redact_pattern(regex::Regex::new(r"\d{4}-\d{4}-\d{4}-\d{4}").unwrap())
+        .redaction_mask("XXXX")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("charged card 4111-1111-1111-1111 for $5");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(event["message"], "charged card XXXX for $5");
+}
@@ -0,0 +1,79 @@
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{BatchConfig, ReloadError, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn batching_and_the_token_can_be_changed_while_running() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("old")
+        .max_batch_events(100)
+        .flush_interval(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let config = layer.config_handle();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 0..3 {
+        info_span!("request", i).in_scope(|| {});
+    }
+    // the three already batched are over the new limit, so they go out under the old token
+    let batching = BatchConfig {
+        max_events: 2,
+        flush_interval: Duration::from_secs(60),
+        ..BatchConfig::default()
+    };
+    config
+        .set_batching(batching, Duration::from_secs(5))
+        .unwrap();
+    config.set_token("new", Duration::from_secs(5)).unwrap();
+    for i in 0..2 {
+        info_span!("request", i).in_scope(|| {});
+    }
+
+    let requests = hec.wait_for_requests(2);
+    let sizes: Vec<usize> = requests.iter().map(|r| r.events().len()).collect();
+    assert_eq!(sizes, vec![3, 2]);
+    assert_eq!(requests[0].header("authorization"), Some("Splunk old"));
+    assert_eq!(requests[1].header("authorization"), Some("Splunk new"));
+}
+
+#[test]
+fn the_head_sample_ratio_can_be_changed_while_running() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .head_sample_ratio(0.0)
+        .build()
+        .unwrap();
+    let config = layer.config_handle();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("dropped").in_scope(|| {});
+    config.set_head_sample_ratio(1.0);
+    assert_eq!(config.head_sample_ratio(), 1.0);
+    info_span!("kept").in_scope(|| {});
+
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let requests = hec.requests();
+    let events = requests[0].events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"]["name"], "kept");
+}
+
+#[test]
+fn a_transport_without_a_token_says_so() {
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .writer(std::io::sink)
+        .build()
+        .unwrap();
+    let config = layer.config_handle();
+    assert_eq!(
+        config.set_token("new", Duration::from_secs(5)),
+        Err(ReloadError::Unsupported)
+    );
+}
@@ -0,0 +1,78 @@
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{CimModel, KeyCase, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn key_cases_convert_each_segment() {
+    assert_eq!(KeyCase::Snake.apply("http.statusCode"), "http.status_code");
+    assert_eq!(KeyCase::Snake.apply("HTTPStatus"), "http_status");
+    assert_eq!(KeyCase::Snake.apply("user-agent"), "user_agent");
+    assert_eq!(KeyCase::Camel.apply("http.status_code"), "http.statusCode");
+    assert_eq!(KeyCase::Camel.apply("elapsed_time"), "elapsedTime");
+    assert_eq!(KeyCase::Camel.apply("already"), "already");
+}
+
+#[test]
+fn fields_are_renamed_on_export() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .rename_field("msg", "message")
+        .rename_field("http.status", "status_code")
+        .key_case(KeyCase::Snake)
+        .indexed_fields(["status_code"])
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!(
+        "request",
+        msg = "hello",
+        http.status = 200,
+        userAgent = "curl"
+    )
+    .in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let exported = &hec.requests()[0].events()[0];
+    let event = &exported["event"];
+    assert_eq!(event["message"], "hello");
+    assert_eq!(event["user_agent"], "curl");
+    assert!(event.get("msg").is_none());
+    assert!(event.get("userAgent").is_none());
+    assert_eq!(exported["fields"]["status_code"], 200);
+}
+
+#[test]
+fn cim_models_rename_to_cim_fields() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .rename_field("http.url", "full_url")
+        .cim_model(CimModel::Web)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!(
+        "request",
+        http.method = "GET",
+        http.status_code = 404,
+        http.url = "https://example.com/missing",
+        net.peer.ip = "10.0.0.1",
+    )
+    .in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(event["http_method"], "GET");
+    assert_eq!(event["status"], 404);
+    assert_eq!(event["src"], "10.0.0.1");
+    assert_eq!(event["full_url"], "https://example.com/missing");
+    assert!(event["duration"].is_u64());
+    assert!(event.get("elapsed_time").is_none());
+}
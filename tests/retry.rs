@@ -0,0 +1,88 @@
+use crate::common::{MockHec, MockResponse};
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{ErrorPolicy, RetryPolicy, SplunkHecLayer, WorkerGuard};
+use tracing_subscriber::prelude::*;
+
+fn layer(hec: &MockHec) -> (SplunkHecLayer, WorkerGuard) {
+    SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .flush_interval(Duration::from_millis(10))
+        .retry_policy(RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+        })
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn server_errors_are_retried() {
+    let hec = MockHec::start();
+    hec.respond_with(
+        MockResponse::status(503, r#"{"text":"Server is busy","code":9}"#)
+            .header("Retry-After", "0"),
+    );
+    hec.respond_with(MockResponse::status(500, "oops"));
+    let (layer, _guard) = layer(&hec);
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+
+    // failed twice, then made it through on the last allowed attempt
+    let requests = hec.wait_for_requests(3);
+    assert_eq!(requests.len(), 3);
+    assert!(requests.iter().all(|r| r.body == requests[0].body));
+}
+
+#[test]
+fn client_errors_are_not_retried() {
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(
+        403,
+        r#"{"text":"Invalid token","code":4}"#,
+    ));
+    let (layer, _guard) = layer(&hec);
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+
+    hec.wait_for_requests(1);
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(hec.requests().len(), 1);
+}
+
+#[test]
+fn requests_to_an_endpoint_that_never_answers_time_out_and_are_retried() {
+    // takes connections and then sits on them
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let held: Vec<_> = listener.incoming().collect();
+        drop(held);
+    });
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(url)
+        .token("abc")
+        .request_timeout(Duration::from_millis(200))
+        .retry_policy(RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(10),
+        })
+        .error_policy(ErrorPolicy::Ignore)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    let started = std::time::Instant::now();
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(2));
+    let snapshot = guard.metrics().snapshot();
+    assert_eq!(snapshot.retries, 1);
+    assert_eq!(snapshot.dropped_export_failed, 1);
+}
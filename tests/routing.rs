@@ -0,0 +1,190 @@
+use crate::common::{MockHec, MockResponse};
+use std::time::Duration;
+use tracing::{error, info, info_span, warn, Level};
+use tracing_splunk_layer::{Route, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn splunk_fields_route_a_span_to_another_index() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .index("app_logs")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!(
+        "login",
+        splunk.index = "audit",
+        splunk.sourcetype = "security",
+        user = "bob"
+    )
+    .in_scope(|| {
+        info_span!("check_password").in_scope(|| {});
+    });
+    info_span!("request").in_scope(|| {});
+    info!(splunk.index = 5, "not a string");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let by_name = |name: &str| {
+        events
+            .iter()
+            .find(|e| e["event"]["name"] == name || e["event"]["message"] == name)
+            .cloned()
+            .unwrap()
+    };
+
+    let login = by_name("login");
+    assert_eq!(login["index"], "audit");
+    assert_eq!(login["sourcetype"], "security");
+    assert_eq!(login["event"]["user"], "bob");
+    assert!(login["event"].get("splunk.index").is_none());
+    // children inherit the parent's fields, routing included
+    assert_eq!(by_name("check_password")["index"], "audit");
+
+    assert_eq!(by_name("request")["index"], "app_logs");
+    let ignored = by_name("not a string");
+    assert_eq!(ignored["index"], "app_logs");
+    assert_eq!(ignored["event"]["splunk.index"], 5);
+}
+
+#[test]
+fn host_and_source_can_be_set_per_record() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .host("collector-1")
+        .source("collector")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!(
+        splunk.host = "edge-7",
+        splunk.source = "edge-agent",
+        "forwarded"
+    );
+    info!("from the collector itself");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events = hec.requests()[0].events();
+    assert_eq!(events[0]["host"], "edge-7");
+    assert_eq!(events[0]["source"], "edge-agent");
+    assert!(events[0]["event"].get("splunk.host").is_none());
+    assert!(events[0]["event"].get("splunk.source").is_none());
+    // the override was only for that one record
+    assert_eq!(events[1]["host"], "collector-1");
+    assert_eq!(events[1]["source"], "collector");
+}
+
+#[test]
+fn levels_can_be_routed_to_their_own_index() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .index("app_logs")
+        .route_levels([Level::ERROR, Level::WARN], "alerts")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    error!("disk full");
+    warn!("disk nearly full");
+    info!("disk fine");
+    warn!(splunk.index = "audit", "disk audited");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let index = |message: &str| {
+        events
+            .iter()
+            .find(|e| e["event"]["message"] == message)
+            .map(|e| e["index"].clone())
+            .unwrap()
+    };
+    assert_eq!(index("disk full"), "alerts");
+    assert_eq!(index("disk nearly full"), "alerts");
+    assert_eq!(index("disk fine"), "app_logs");
+    assert_eq!(index("disk audited"), "audit");
+}
+
+#[test]
+fn tenants_get_their_own_endpoint_token_and_index() {
+    let hec = MockHec::start();
+    let acme = MockHec::start();
+    let routes = [(
+        "acme".to_string(),
+        Route {
+            index: Some("acme_logs".to_string()),
+            ..Route::new(acme.url(), "acme-token")
+        },
+    )];
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .tenant_routes(
+            |record| {
+                let tenant = record.event.get("tenant")?.as_str()?;
+                Some(tenant.to_owned())
+            },
+            routes,
+        )
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", tenant = "acme").in_scope(|| {});
+    info_span!("request", tenant = "acme").in_scope(|| {});
+    info_span!("request", tenant = "globex").in_scope(|| {});
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = acme.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].header("authorization"),
+        Some("Splunk acme-token")
+    );
+    let events = requests[0].events();
+    assert_eq!(events.len(), 2);
+    assert!(events.iter().all(|e| e["index"] == "acme_logs"));
+    // no route for globex, so it stays with everything else
+    assert_eq!(hec.requests()[0].events().len(), 2);
+    let acme_metrics = guard.route_metrics("acme").unwrap().snapshot();
+    assert_eq!(acme_metrics.events_sent, 2);
+}
+
+#[test]
+fn routes_catching_up_share_the_in_flight_limit() {
+    let hec = MockHec::start();
+    for _ in 0..3 {
+        hec.respond_with(MockResponse::success().delay(Duration::from_millis(200)));
+    }
+    let routes = ["a", "b", "c"].map(|tenant| (tenant.to_string(), Route::new(hec.url(), "abc")));
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_batch_events(1)
+        .max_in_flight(1)
+        .tenant_routes(
+            |record| Some(record.event.get("tenant")?.as_str()?.to_owned()),
+            routes,
+        )
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    // every route's worker sends as soon as its event is in, all at the same time
+    for tenant in ["a", "b", "c"] {
+        info!(tenant, "catching up");
+    }
+    assert_eq!(hec.wait_for_requests(3).len(), 3);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(hec.most_in_flight(), 1);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "tokio")]
+
+use crate::common::{config_error, MockHec};
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{BuildError, SplunkHecLayer};
+use tracing_subscriber::prelude::*;
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn the_worker_can_run_on_a_given_tokio_runtime() {
+    let runtime = runtime();
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .tokio_handle(runtime.handle().clone())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", answer = 42).in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events = hec.requests()[0].events();
+    assert_eq!(events[0]["event"]["answer"], 42);
+}
+
+#[test]
+fn the_ambient_tokio_runtime_is_picked_up() {
+    let hec = MockHec::start();
+    let (layer, guard) = runtime().block_on(async {
+        SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .tokio_runtime()
+            .build()
+            .unwrap()
+    });
+    drop((layer, guard));
+
+    let outside = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .tokio_runtime()
+        .build();
+    assert_eq!(
+        outside.err().map(config_error),
+        Some(BuildError::NoTokioRuntime)
+    );
+}
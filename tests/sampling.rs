@@ -0,0 +1,116 @@
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing::{error, info, info_span};
+use tracing_splunk_layer::SplunkHecLayer;
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn tail_sampling_keeps_only_spans_with_errors() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .tail_sample_errors()
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("healthy").in_scope(|| info!("all good"));
+    info_span!("outer").in_scope(|| {
+        info_span!("failing").in_scope(|| error!("oh no"));
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    // the error in `failing` keeps `outer` around too
+    let names: Vec<String> = hec.requests()[0]
+        .events()
+        .iter()
+        .map(|e| e["event"]["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(names, vec!["failing", "outer"]);
+    assert_eq!(guard.suppressed_spans(), 1);
+}
+
+#[test]
+fn tail_sampling_with_a_predicate() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .tail_sample_with(|sample| {
+            sample.fields.get("status").and_then(|s| s.as_u64()) >= Some(500)
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", status = 200).in_scope(|| {});
+    info_span!("request", status = 503).in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events = hec.requests()[0].events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"]["status"], 503);
+    assert_eq!(guard.suppressed_spans(), 1);
+}
+
+#[test]
+fn head_sampling_keeps_whole_traces() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .head_sample_ratio(0.5)
+        .max_batch_events(1000)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for trace in 0..200 {
+        info_span!("root", trace).in_scope(|| {
+            info_span!("child", trace).in_scope(|| info!("hello"));
+        });
+    }
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let roots: Vec<_> = events
+        .iter()
+        .filter(|e| e["event"]["name"] == "root")
+        .collect();
+    let children: Vec<_> = events
+        .iter()
+        .filter(|e| e["event"]["name"] == "child")
+        .collect();
+
+    // roughly half made it, and every trace that made it brought its child along
+    assert!(roots.len() > 50 && roots.len() < 150);
+    assert_eq!(roots.len(), children.len());
+    for (root, child) in roots.iter().zip(&children) {
+        assert_eq!(root["event"]["trace"], child["event"]["trace"]);
+    }
+    assert_eq!(guard.sampled_out_spans() as usize, 400 - events.len());
+}
+
+#[test]
+fn a_ratio_that_isnt_a_number_keeps_everything() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .head_sample_ratio(f64::NAN)
+        .build()
+        .unwrap();
+    let config = layer.config_handle();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("built").in_scope(|| {});
+    config.set_head_sample_ratio(f64::NAN);
+    assert_eq!(config.head_sample_ratio(), 1.0);
+    info_span!("reloaded").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(guard.sampled_out_spans(), 0);
+}
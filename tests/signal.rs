@@ -0,0 +1,20 @@
+#![cfg(feature = "signal")]
+
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing_splunk_layer::{flush_on_signal, SignalError, SplunkHecLayer};
+
+// the handler exits the process, so all there is to check from in here is that it's installed
+#[test]
+fn only_one_signal_handler_can_be_installed() {
+    let hec = MockHec::start();
+    let (_layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+
+    flush_on_signal(&guard, Duration::from_secs(1)).unwrap();
+    let again = flush_on_signal(&guard, Duration::from_secs(1));
+    assert!(matches!(again, Err(SignalError::MultipleHandlers)));
+}
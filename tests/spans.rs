@@ -1,18 +1,784 @@
-use tracing::{debug_span, info, info_span};
-use tracing_splunk_layer::SplunkHecLayer;
+use crate::common::MockHec;
+use std::time::Duration;
+use tracing::{debug_span, error, info, info_span, warn};
+use tracing_splunk_layer::{
+    ElapsedTime, ElapsedUnit, FieldCollision, FieldInheritance, ManualClock, RateLimit,
+    RateLimitConfig, SpanAggregation, SpanEventLimit, SpanEventMode, SpanEventOverflow,
+    SpanExportMode, SpanMetrics, SplunkHecLayer, TraceParent,
+};
 use tracing_subscriber::prelude::*;
 
 #[test]
 fn span_test() {
-    tracing_subscriber::registry().with(SplunkHecLayer).init();
+    let hec = MockHec::start();
+    let (layer, _guard) =
+        SplunkHecLayer::new(hec.url(), "00000000-0000-0000-0000-000000000000").unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
 
-    let outer_span = info_span!("outer", level = 0, other_field = tracing::field::Empty);
-    let _outer_entered = outer_span.enter();
+    {
+        let outer_span = info_span!("outer", level = 0, other_field = tracing::field::Empty);
+        let _outer_entered = outer_span.enter();
 
-    std::thread::sleep(std::time::Duration::from_millis(50));
-    let inner_span = debug_span!("inner", level = 1);
-    let _inner_entered = inner_span.enter();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let inner_span = debug_span!("inner", level = 1);
+        let _inner_entered = inner_span.enter();
 
-    outer_span.record("other_field", &7);
-    info!(a_bool = true, answer = 42, message = "first example");
+        outer_span.record("other_field", 7);
+        info!(a_bool = true, answer = 42, message = "first example");
+    }
+
+    // both spans have closed, so both should have been shipped together in one batch
+    let requests = hec.wait_for_requests(1);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/services/collector/event");
+    assert_eq!(
+        requests[0].header("authorization"),
+        Some("Splunk 00000000-0000-0000-0000-000000000000")
+    );
+
+    let events = requests[0].events();
+    assert_eq!(events.len(), 2);
+    let inner = &events[0];
+    assert_eq!(inner["event"]["level"], 1);
+    assert_eq!(inner["event"]["answer"], 42);
+    let outer = &events[1];
+    assert_eq!(outer["event"]["other_field"], 7);
+    assert!(outer["event"]["elapsed_time"].as_u64().unwrap() >= 50);
+}
+
+#[test]
+fn span_events_can_be_kept_as_a_list() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_event_mode(SpanEventMode::List)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {
+        info!(attempt = 1, "trying");
+        warn!(attempt = 2, "trying again");
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let span = &hec.requests()[0].events()[0]["event"];
+    // nothing leaked onto the span itself
+    assert!(span.get("attempt").is_none());
+
+    let events = span["events"].as_array().unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["attempt"], 1);
+    assert_eq!(events[0]["level"], "INFO");
+    assert_eq!(events[0]["message"], "trying");
+    assert_eq!(events[1]["attempt"], 2);
+    assert_eq!(events[1]["level"], "WARN");
+    assert!(events[1]["time"].as_f64().unwrap() >= events[0]["time"].as_f64().unwrap());
+}
+
+#[test]
+fn events_can_be_exported_on_their_own() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_event_mode(SpanEventMode::Export)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", user = "alice", splunk.index = "web").in_scope(|| {
+        info_span!("query", table = "orders").in_scope(|| {
+            warn!(rows = 0, "nothing found");
+        });
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events[0]["index"], "web");
+    let (logged, query) = (&events[0]["event"], &events[1]["event"]);
+    assert_eq!(logged["message"], "nothing found");
+    assert_eq!(logged["level"], "WARN");
+    assert_eq!(logged["rows"], 0);
+    assert_eq!(logged["table"], "orders");
+    assert_eq!(logged["user"], "alice");
+    assert_eq!(logged["span_id"], query["span_id"]);
+    assert_eq!(logged["trace_id"], query["trace_id"]);
+    assert!(query.get("rows").is_none());
+}
+
+#[test]
+fn long_lived_spans_send_checkpoints() {
+    let hec = MockHec::start();
+    let clock = ManualClock::new();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .clock(clock.clone())
+        .span_event_mode(SpanEventMode::List)
+        .checkpoint_interval(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let connection = info_span!("connection", peer = "10.0.0.1");
+    connection.in_scope(|| info!("opened"));
+    clock.advance(Duration::from_millis(150));
+    connection.in_scope(|| info!("request"));
+    // too soon for another
+    connection.in_scope(|| info!("request"));
+    drop(connection);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 2);
+    let (checkpoint, closed) = (&events[0]["event"], &events[1]["event"]);
+    assert_eq!(checkpoint["checkpoint"], 1);
+    assert_eq!(checkpoint["peer"], "10.0.0.1");
+    assert_eq!(checkpoint["elapsed_time"], 150);
+    assert_eq!(checkpoint["span_id"], closed["span_id"]);
+    assert_eq!(checkpoint["events"].as_array().unwrap().len(), 2);
+    assert!(closed.get("checkpoint").is_none());
+    assert_eq!(closed["events"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn a_rate_limited_checkpoint_leaves_its_events_on_the_span() {
+    let hec = MockHec::start();
+    let clock = ManualClock::new();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .clock(clock.clone())
+        .span_event_mode(SpanEventMode::List)
+        .checkpoint_interval(Duration::from_millis(100))
+        .rate_limit(RateLimitConfig {
+            per_target: Some(RateLimit {
+                per_second: 10.0,
+                burst: 1,
+            }),
+            ..RateLimitConfig::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let connection = info_span!("connection");
+    connection.in_scope(|| info!("opened"));
+    clock.advance(Duration::from_millis(150));
+    connection.in_scope(|| info!("first"));
+    clock.advance(Duration::from_millis(150));
+    // the bucket's empty for this checkpoint, but has room again by the time the span closes
+    connection.in_scope(|| info!("second"));
+    std::thread::sleep(Duration::from_millis(200));
+    drop(connection);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let messages = |event: &serde_json::Value| -> Vec<String> {
+        let events = event["event"]["events"].as_array().unwrap();
+        events
+            .iter()
+            .map(|e| e["message"].as_str().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(events.len(), 2);
+    assert_eq!(messages(&events[0]), ["opened", "first"]);
+    assert_eq!(messages(&events[1]), ["second"]);
+    assert_eq!(guard.metrics().snapshot().dropped_rate_limited, 1);
+}
+
+#[test]
+fn span_event_limits_bound_the_events_list() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_event_mode(SpanEventMode::List)
+        .span_event_limit(SpanEventLimit {
+            max_events: 3,
+            ..SpanEventLimit::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("busy_loop").in_scope(|| {
+        for i in 0..5 {
+            info!(i, "tick");
+        }
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let span = &hec.requests()[0].events()[0]["event"];
+    let kept: Vec<_> = span["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["i"].clone())
+        .collect();
+    assert_eq!(kept, vec![2, 3, 4]);
+    assert_eq!(span["events_dropped"], 2);
+}
+
+#[test]
+fn span_event_limits_can_summarize_or_flush() {
+    let run = |overflow| {
+        let hec = MockHec::start();
+        let (layer, guard) = SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .span_event_mode(SpanEventMode::List)
+            .span_event_limit(SpanEventLimit {
+                max_events: 2,
+                overflow,
+                ..SpanEventLimit::default()
+            })
+            .build()
+            .unwrap();
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+        info_span!("busy_loop").in_scope(|| {
+            for i in 0..5 {
+                info!(i, "tick");
+            }
+        });
+        guard.flush(Duration::from_secs(5)).unwrap();
+        let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+        events
+            .iter()
+            .map(|e| {
+                let kept = e["event"]["events"].as_array().unwrap();
+                let kept: Vec<_> = kept.iter().map(|e| e["i"].as_u64().unwrap()).collect();
+                (kept, e["event"].get("events_dropped").cloned())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        run(SpanEventOverflow::Summarize),
+        vec![(vec![0, 1], Some(3.into()))]
+    );
+    // two checkpoints on the way and the rest when it closes, nothing is lost
+    assert_eq!(
+        run(SpanEventOverflow::Flush),
+        vec![(vec![0, 1], None), (vec![2, 3], None), (vec![4], None)]
+    );
+}
+
+#[test]
+fn an_event_that_cant_be_flushed_for_the_rate_limit_is_counted_as_dropped() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_event_mode(SpanEventMode::List)
+        .span_event_limit(SpanEventLimit {
+            max_events: 2,
+            overflow: SpanEventOverflow::Flush,
+            ..SpanEventLimit::default()
+        })
+        .rate_limit(RateLimitConfig {
+            per_target: Some(RateLimit {
+                per_second: 10.0,
+                burst: 1,
+            }),
+            ..RateLimitConfig::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let span = info_span!("busy_loop");
+    // the first flush takes the only token, so the second one is turned away
+    span.in_scope(|| {
+        for i in 0..5 {
+            info!(i, "tick");
+        }
+    });
+    // and the bucket has room again for the span itself
+    std::thread::sleep(Duration::from_millis(200));
+    drop(span);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 2);
+    let closed = &events[1]["event"];
+    let kept: Vec<_> = closed["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["i"].as_u64().unwrap())
+        .collect();
+    assert_eq!(kept, vec![2, 3]);
+    assert_eq!(closed["events_dropped"], 1);
+    assert_eq!(guard.metrics().snapshot().dropped_rate_limited, 1);
+}
+
+#[test]
+fn span_references_export_only_a_span_s_own_fields() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_export_mode(SpanExportMode::References)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", user = "alice", splunk.index = "web").in_scope(|| {
+        info_span!("query", table = "orders").in_scope(|| {});
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["index"], "web");
+    let (query, request) = (&events[0]["event"], &events[1]["event"]);
+    assert!(query.get("user").is_none());
+    assert_eq!(query["table"], "orders");
+    assert_eq!(query["parent_span_id"], request["span_id"]);
+    assert_eq!(query["trace_id"], request["trace_id"]);
+    assert!(query.get("parent_id").is_none());
+    assert!(request.get("parent_span_id").is_none());
+}
+
+#[test]
+fn span_hierarchy_is_recorded() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .with_span_hierarchy(true)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let outer = info_span!("outer", user = "alice", route = tracing::field::Empty);
+    outer.in_scope(|| {
+        info_span!("inner", attempt = 1).in_scope(|| {});
+    });
+    outer.record("route", "/login");
+    drop(outer);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events = hec.requests()[0].events();
+    let inner = &events[0]["event"];
+    assert_eq!(inner["span"]["name"], "inner");
+    assert_eq!(inner["span"]["attempt"], 1);
+    assert!(inner["span"].get("user").is_none());
+    assert_eq!(inner["parent_span"]["name"], "outer");
+    assert_eq!(inner["parent_span"]["user"], "alice");
+    let names: Vec<&str> = inner["spans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["outer", "inner"]);
+
+    let outer = &events[1]["event"];
+    assert_eq!(outer["span"]["route"], "/login");
+    assert!(outer.get("parent_span").is_none());
+    assert_eq!(outer["spans"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn span_tree_exports_one_event_per_root() {
+    let hec = MockHec::start();
+    let clock = ManualClock::new();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .clock(clock.clone())
+        .span_export_mode(SpanExportMode::Tree)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", user = "alice", splunk.index = "web").in_scope(|| {
+        info_span!("auth", method = "token").in_scope(|| clock.advance(Duration::from_millis(5)));
+        info_span!("query", table = "orders", splunk.index = "db").in_scope(|| {
+            info_span!("fetch", rows = 3).in_scope(|| clock.advance(Duration::from_millis(20)));
+            info!(cached = false, "queried");
+        });
+    });
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["index"], "web");
+    let root = &events[0]["event"];
+    assert_eq!(root["name"], "request");
+    assert_eq!(root["elapsed_time"], 25);
+
+    let children = root["children"].as_array().unwrap();
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0]["name"], "auth");
+    assert_eq!(children[0]["method"], "token");
+    assert_eq!(children[0]["elapsed_time"], 5);
+    assert!(children[0].get("user").is_none());
+    assert!(children[0]["time"].is_number());
+
+    let query = &children[1];
+    assert_eq!(query["name"], "query");
+    assert_eq!(query["cached"], false);
+    assert!(query.get("splunk.index").is_none());
+    assert_eq!(query["children"][0]["name"], "fetch");
+    assert_eq!(query["children"][0]["rows"], 3);
+    assert_eq!(query["children"][0]["elapsed_time"], 20);
+}
+
+#[test]
+fn spans_carry_trace_and_parent_ids() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| info_span!("query").in_scope(|| {}));
+    info_span!("another_request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let (query, request, another) = (
+        &events[0]["event"],
+        &events[1]["event"],
+        &events[2]["event"],
+    );
+
+    let trace_id = request["trace_id"].as_str().unwrap();
+    assert_eq!(trace_id.len(), 32);
+    assert_eq!(query["trace_id"], trace_id);
+    assert_ne!(another["trace_id"], trace_id);
+
+    assert_eq!(request["span_id"].as_str().unwrap().len(), 16);
+    assert_eq!(query["parent_id"], request["span_id"]);
+    assert_ne!(query["span_id"], request["span_id"]);
+    assert!(request.get("parent_id").is_none());
+}
+
+#[test]
+fn spans_link_to_the_spans_they_follow_from() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    // a job queued by one request and picked up later by a consumer with a trace of its own
+    let producer = info_span!("enqueue");
+    let consumer = info_span!("process");
+    consumer.follows_from(&producer);
+    drop(producer);
+    drop(consumer);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let (producer, consumer) = (&events[0]["event"], &events[1]["event"]);
+    assert!(producer.get("links").is_none());
+    assert_ne!(consumer["trace_id"], producer["trace_id"]);
+    assert_eq!(
+        consumer["links"],
+        serde_json::json!([{
+            "trace_id": producer["trace_id"],
+            "span_id": producer["span_id"],
+        }])
+    );
+}
+
+#[test]
+fn traceparent_headers_are_parsed() {
+    let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let parent = TraceParent::parse(header).unwrap();
+    assert_eq!(parent.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+    assert_eq!(parent.parent_id, 0x00f067aa0ba902b7);
+    assert!(parent.sampled());
+    assert_eq!(parent.to_string(), header);
+
+    let headers = [("Content-Type", "text/plain"), ("TraceParent", header)];
+    assert_eq!(TraceParent::from_headers(headers), Some(parent));
+
+    for invalid in [
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+    ] {
+        assert_eq!(TraceParent::parse(invalid), None, "{}", invalid);
+    }
+    // later versions can have more on the end
+    assert!(
+        TraceParent::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra")
+            .is_some()
+    );
+}
+
+#[test]
+fn spans_with_a_traceparent_join_the_upstream_trace() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    info_span!("request", traceparent = %header).in_scope(|| info_span!("query").in_scope(|| {}));
+    let late = info_span!("late", traceparent = tracing::field::Empty);
+    late.record("traceparent", header);
+    drop(late);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let (query, request, late) = (
+        &events[0]["event"],
+        &events[1]["event"],
+        &events[2]["event"],
+    );
+    for span in [query, request, late] {
+        assert_eq!(span["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    }
+    assert_eq!(request["parent_span_id"], "00f067aa0ba902b7");
+    assert_eq!(late["parent_span_id"], "00f067aa0ba902b7");
+    assert!(query.get("parent_span_id").is_none());
+    assert_eq!(query["parent_id"], request["span_id"]);
+}
+
+#[test]
+fn field_inheritance_can_be_limited() {
+    let exported = |inheritance| {
+        let hec = MockHec::start();
+        let (layer, guard) = SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .field_inheritance(inheritance)
+            .build()
+            .unwrap();
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+
+        info_span!("root", tenant = "acme", splunk.index = "audit").in_scope(|| {
+            info_span!("request", user = "ferris", shared = "request").in_scope(|| {
+                info_span!("query", shared = "query").in_scope(|| {});
+            });
+        });
+        guard.flush(Duration::from_secs(5)).unwrap();
+        let requests = hec.requests();
+        requests[0].events()[0].clone()
+    };
+
+    let full = exported(FieldInheritance::Full);
+    assert_eq!(full["event"]["tenant"], "acme");
+    assert_eq!(full["event"]["user"], "ferris");
+    assert_eq!(full["event"]["shared"], "query");
+
+    let parent = exported(FieldInheritance::Parent);
+    assert!(parent["event"].get("tenant").is_none());
+    assert_eq!(parent["event"]["user"], "ferris");
+    assert_eq!(parent["event"]["shared"], "query");
+
+    let none = exported(FieldInheritance::None);
+    assert!(none["event"].get("tenant").is_none());
+    assert!(none["event"].get("user").is_none());
+    // where the tree goes is still up to the root
+    assert_eq!(none["index"], "audit");
+}
+
+#[test]
+fn field_collisions_follow_the_policy() {
+    let exported = |collision| {
+        let hec = MockHec::start();
+        let (layer, guard) = SplunkHecLayer::builder()
+            .endpoint(hec.url())
+            .token("abc")
+            .field_collision(collision)
+            .build()
+            .unwrap();
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+
+        info_span!("root", shared = "root").in_scope(|| {
+            info_span!("request", shared = "request").in_scope(|| {
+                info_span!("query", shared = "query").in_scope(|| info!(shared = "event"));
+            });
+        });
+        guard.flush(Duration::from_secs(5)).unwrap();
+        let requests = hec.requests();
+        requests[0].events()[0]["event"].clone()
+    };
+
+    assert_eq!(exported(FieldCollision::Overwrite)["shared"], "event");
+    assert_eq!(exported(FieldCollision::KeepFirst)["shared"], "root");
+
+    let namespaced = exported(FieldCollision::Namespace);
+    assert_eq!(namespaced["shared"], "event");
+    assert_eq!(namespaced["query.shared"], "query");
+    assert_eq!(namespaced["request.shared"], "request");
+    assert_eq!(namespaced["root.shared"], "root");
+
+    assert_eq!(
+        exported(FieldCollision::Collect)["shared"],
+        serde_json::json!(["root", "request", "query", "event"])
+    );
+}
+
+#[test]
+fn spans_are_sent_as_metrics() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .global_field("service", "checkout")
+        .span_metrics(SpanMetrics {
+            index: Some("metrics".to_string()),
+            dimensions: vec!["route".to_string()],
+            ..SpanMetrics::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", route = "/cart", rows = 12, user = "bob").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 2);
+    let metric = &events[0];
+    assert_eq!(metric["event"], "metric");
+    assert_eq!(metric["index"], "metrics");
+    let fields = &metric["fields"];
+    assert!(fields["metric_name:span.duration"].is_u64());
+    assert!(fields["metric_name:span.busy_time"].is_u64());
+    assert_eq!(fields["metric_name:span.count"], 1);
+    assert_eq!(fields["metric_name:span.rows"], 12);
+    assert_eq!(fields["span.name"], "request");
+    assert_eq!(fields["route"], "/cart");
+    assert_eq!(fields["service"], "checkout");
+    assert!(fields.get("user").is_none());
+    assert_eq!(events[1]["event"]["name"], "request");
+
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .span_metrics(SpanMetrics {
+            span_events: false,
+            ..SpanMetrics::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["event"], "metric");
+}
+
+#[test]
+fn hot_spans_are_summarized() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .aggregate_spans(SpanAggregation {
+            spans: vec!["hot".to_string()],
+            exclude_from_export: true,
+            ..SpanAggregation::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for _ in 0..3 {
+        info_span!("hot").in_scope(|| {});
+    }
+    info_span!("hot").in_scope(|| error!("failed"));
+    info_span!("cold").in_scope(|| {});
+    // whatever's been summarized so far goes out on shutdown
+    drop(guard);
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["event"]["name"], "cold");
+    let summary = &events[1]["event"];
+    assert_eq!(summary["name"], "hot");
+    assert_eq!(summary["span_summary"], true);
+    assert_eq!(summary["count"], 4);
+    assert_eq!(summary["errors"], 1);
+    assert!(summary["elapsed_time.max"].as_u64() >= summary["elapsed_time.min"].as_u64());
+    let buckets = summary["elapsed_time.buckets"].as_object().unwrap();
+    assert_eq!(buckets.len(), 14);
+    assert_eq!(
+        buckets.values().map(|n| n.as_u64().unwrap()).sum::<u64>(),
+        4
+    );
+}
+
+#[test]
+fn summary_buckets_keep_their_bounds_apart_in_a_coarse_unit() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .elapsed_time(ElapsedTime {
+            unit: ElapsedUnit::Seconds,
+            ..ElapsedTime::default()
+        })
+        .aggregate_spans(SpanAggregation {
+            exclude_from_export: true,
+            ..SpanAggregation::default()
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for _ in 0..3 {
+        info_span!("hot").in_scope(|| {});
+    }
+    drop(guard);
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events.len(), 1);
+    let buckets = events[0]["event"]["elapsed_time.buckets"]
+        .as_object()
+        .unwrap();
+    assert_eq!(buckets.len(), 14);
+    assert_eq!(buckets["0.001"], 3);
+    assert_eq!(buckets["0.25"], 0);
+    assert_eq!(buckets["10"], 0);
+}
+
+#[test]
+fn fields_a_parent_records_after_its_child_is_made_are_still_inherited() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let request = info_span!("request", user = tracing::field::Empty);
+    request.in_scope(|| {
+        let query = info_span!("query");
+        request.record("user", "ferris");
+        query.in_scope(|| {});
+    });
+    drop(request);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    let query = &requests[0].events()[0]["event"];
+    assert_eq!(query["name"], "query");
+    assert_eq!(query["user"], "ferris");
 }
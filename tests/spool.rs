@@ -0,0 +1,145 @@
+use crate::common::{MockHec, MockResponse};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::info;
+use tracing_splunk_layer::{AckConfig, ErrorPolicy, RetryPolicy, SplunkHecLayer, SpoolConfig};
+use tracing_subscriber::prelude::*;
+
+// a fresh directory for each test, cleaned up when it's dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "tracing-splunk-layer-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        TempDir(dir)
+    }
+
+    fn files(&self) -> usize {
+        std::fs::read_dir(&self.0).map(|d| d.count()).unwrap_or(0)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn messages(hec: &MockHec) -> Vec<String> {
+    hec.requests()
+        .iter()
+        .flat_map(|r| r.events())
+        .map(|e| e["event"]["message"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn undeliverable_batches_are_replayed_once_splunk_recovers() {
+    let dir = TempDir::new("replay");
+    let hec = MockHec::start();
+    hec.respond_with(MockResponse::status(
+        503,
+        r#"{"text":"Server is busy","code":9}"#,
+    ));
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .retry_policy(RetryPolicy::none())
+        .spool(SpoolConfig::new(&dir.0))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("while splunk was down");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    assert_eq!(dir.files(), 1);
+
+    info!("once it was back");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(
+        messages(&hec),
+        vec![
+            "while splunk was down",
+            "once it was back",
+            "while splunk was down"
+        ]
+    );
+    assert_eq!(dir.files(), 0);
+}
+
+#[test]
+fn the_spool_survives_a_restart() {
+    let dir = TempDir::new("restart");
+    let down = MockHec::start();
+    down.respond_with(MockResponse::status(503, "unavailable"));
+    {
+        let (layer, _guard) = SplunkHecLayer::builder()
+            .endpoint(down.url())
+            .token("abc")
+            .retry_policy(RetryPolicy::none())
+            .spool(SpoolConfig::new(&dir.0))
+            .build()
+            .unwrap();
+        let _default = tracing_subscriber::registry().with(layer).set_default();
+        info!("from the last run");
+    }
+    assert_eq!(dir.files(), 1);
+
+    // the next run picks the spool up and replays it without waiting for anything new
+    let hec = MockHec::start();
+    let (_layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .spool(SpoolConfig::new(&dir.0))
+        .build()
+        .unwrap();
+    hec.wait_for_requests(1);
+
+    assert_eq!(messages(&hec), vec!["from the last run"]);
+}
+
+#[test]
+fn unacknowledged_batches_spooled_at_shutdown_report_a_full_spool() {
+    let dir = TempDir::new("unacked-full");
+    let hec = MockHec::start().enable_acks();
+    hec.hold_acks(true);
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let seen = errors.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .max_batch_events(1)
+        .indexer_ack(AckConfig::new())
+        // room for only the newest batch
+        .spool(SpoolConfig {
+            max_bytes: 1,
+            segment_bytes: 1,
+            ..SpoolConfig::new(&dir.0)
+        })
+        .error_policy(ErrorPolicy::callback(move |e| {
+            seen.lock().unwrap().push(e.to_string())
+        }))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("first");
+    info!("second");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    drop(guard);
+
+    assert_eq!(dir.files(), 1);
+    let errors = errors.lock().unwrap();
+    assert!(
+        errors.iter().any(|e| e.contains("spool is full")),
+        "{:?}",
+        errors
+    );
+}
@@ -0,0 +1,181 @@
+use crate::common::MockHec;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info_span;
+use tracing_splunk_layer::{
+    ElapsedTime, ElapsedUnit, HecTime, ManualClock, SplunkHecLayer, TimestampPrecision,
+};
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn hec_time_keeps_the_requested_digits() {
+    let time = UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789);
+    let render = |precision| serde_json::to_string(&HecTime::new(time, precision)).unwrap();
+
+    assert_eq!(render(TimestampPrecision::Seconds), "1700000000");
+    assert_eq!(render(TimestampPrecision::Milliseconds), "1700000000.123");
+    assert_eq!(
+        render(TimestampPrecision::Microseconds),
+        "1700000000.123456"
+    );
+    assert_eq!(
+        render(TimestampPrecision::Nanoseconds),
+        "1700000000.123456789"
+    );
+}
+
+#[test]
+fn spans_are_timestamped_at_creation() {
+    let hec = MockHec::start();
+    let (layer, _guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .timestamp_precision(TimestampPrecision::Microseconds)
+        .flush_interval(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    info_span!("request").in_scope(|| std::thread::sleep(Duration::from_millis(200)));
+
+    let requests = hec.wait_for_requests(1);
+    let time = requests[0].events()[0]["time"].as_f64().unwrap();
+    assert!(time >= before.as_secs_f64() - 0.001);
+    assert!(time < before.as_secs_f64() + 0.1);
+}
+
+#[test]
+fn elapsed_time_can_be_reported_in_other_units() {
+    let elapsed = Duration::new(2, 345_678_901);
+    let value = |unit, fractional| {
+        ElapsedTime {
+            unit,
+            fractional,
+            ..ElapsedTime::default()
+        }
+        .value(elapsed)
+    };
+
+    assert_eq!(value(ElapsedUnit::Milliseconds, false), 2345);
+    assert_eq!(value(ElapsedUnit::Seconds, false), 2);
+    assert_eq!(value(ElapsedUnit::Nanoseconds, false), 2_345_678_901u64);
+    assert_eq!(value(ElapsedUnit::Seconds, true), 2.345678901);
+    assert_eq!(value(ElapsedUnit::Microseconds, true), 2345678.901);
+
+    // too many nanoseconds for a u64 still come out whole
+    let forever = ElapsedTime {
+        unit: ElapsedUnit::Nanoseconds,
+        ..ElapsedTime::default()
+    };
+    assert_eq!(
+        forever.value(Duration::MAX),
+        (u128::from(u64::MAX) * 1_000_000_000 + 999_999_999).to_string()
+    );
+}
+
+#[test]
+fn elapsed_time_can_be_renamed() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .elapsed_time(ElapsedTime {
+            field: "duration_us".to_string(),
+            unit: ElapsedUnit::Microseconds,
+            fractional: false,
+        })
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| std::thread::sleep(Duration::from_millis(20)));
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert!(event.get("elapsed_time").is_none());
+    assert!(event["duration_us"].as_u64().unwrap() >= 20_000);
+}
+
+#[test]
+fn spans_entered_more_than_once_report_busy_and_idle_time() {
+    let hec = MockHec::start();
+    let clock = ManualClock::new();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    // what an async span looks like, entered while it's polled and idle while it waits
+    let span = info_span!("request");
+    for _ in 0..2 {
+        span.in_scope(|| clock.advance(Duration::from_millis(20)));
+        clock.advance(Duration::from_millis(50));
+    }
+    drop(span);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(event["busy_time"], 40);
+    assert_eq!(event["idle_time"], 100);
+    assert_eq!(event["elapsed_time"], 140);
+    assert_eq!(event["time_to_first_enter"], 0);
+}
+
+#[test]
+fn timestamps_come_from_the_clock() {
+    let hec = MockHec::start();
+    let clock = ManualClock::starting_at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let span = info_span!("request");
+    clock.advance(Duration::from_millis(1500));
+    tracing::info!("top level");
+    drop(span);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    assert_eq!(events[0]["time"], 1_700_000_001.5);
+    assert_eq!(events[1]["time"], 1_700_000_000.0);
+    assert_eq!(events[1]["event"]["elapsed_time"], 1500);
+}
+
+#[test]
+fn span_timings_start_when_the_span_is_created() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let queued = info_span!("queued");
+    std::thread::sleep(Duration::from_millis(50));
+    queued.in_scope(|| std::thread::sleep(Duration::from_millis(10)));
+    drop(queued);
+
+    let never_entered = info_span!("never_entered");
+    std::thread::sleep(Duration::from_millis(30));
+    drop(never_entered);
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let events: Vec<_> = hec.requests().iter().flat_map(|r| r.events()).collect();
+    let queued = &events[0]["event"];
+    assert!(queued["time_to_first_enter"].as_u64().unwrap() >= 50);
+    assert!(queued["elapsed_time"].as_u64().unwrap() >= 60);
+    assert!(queued["busy_time"].as_u64().unwrap() >= 10);
+
+    let never_entered = &events[1]["event"];
+    assert!(never_entered.get("time_to_first_enter").is_none());
+    assert!(never_entered["elapsed_time"].as_u64().unwrap() >= 30);
+    assert_eq!(never_entered["busy_time"], 0);
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "rustls")]
+
+use crate::common::{config_error, MockHec};
+use std::time::Duration;
+use tracing::info_span;
+use tracing_splunk_layer::{BuildError, SplunkHecLayer, TlsConfig, TlsError};
+use tracing_subscriber::prelude::*;
+
+const CA: &str = include_str!("tls/ca.pem");
+const CLIENT_CERT: &str = include_str!("tls/client.pem");
+const CLIENT_KEY: &str = include_str!("tls/client.key");
+
+#[test]
+fn a_custom_ca_and_client_certificate_are_accepted() {
+    let hec = MockHec::start();
+    let tls = TlsConfig::new()
+        .root_certificate(CA)
+        .client_identity(CLIENT_CERT, CLIENT_KEY);
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .tls(tls)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", status = 200).in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(hec.requests()[0].events()[0]["event"]["status"], 200);
+}
+
+#[test]
+fn bad_certificates_fail_the_build() {
+    let err = SplunkHecLayer::builder()
+        .endpoint("https://localhost:8088")
+        .token("abc")
+        .tls(TlsConfig::new().root_certificate("not a certificate"))
+        .build()
+        .err()
+        .map(config_error);
+    assert!(matches!(
+        err,
+        Some(BuildError::Tls(TlsError::InvalidRootCertificate(_)))
+    ));
+
+    // a key isn't a certificate chain
+    let err = SplunkHecLayer::builder()
+        .endpoint("https://localhost:8088")
+        .token("abc")
+        .tls(TlsConfig::new().client_identity(CLIENT_KEY, CLIENT_KEY))
+        .build()
+        .err()
+        .map(config_error);
+    assert!(matches!(
+        err,
+        Some(BuildError::Tls(TlsError::InvalidClientIdentity(_)))
+    ));
+}
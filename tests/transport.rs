@@ -0,0 +1,409 @@
+use crate::common::MockHec;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{TcpListener, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error_span, info, info_span, Level};
+use tracing_splunk_layer::{
+    Batch, Destination, ErrorPolicy, EventRecord, FileConfig, HecResponse, Logfmt, RetryPolicy,
+    SplunkHecLayer, TcpTransport, Transport, TransportFuture,
+};
+use tracing_subscriber::prelude::*;
+
+// a test double that just remembers every batch it was handed
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Transport for Recorder {
+    fn send<'a>(&'a self, batch: &'a Batch) -> TransportFuture<'a> {
+        self.0.lock().unwrap().push(batch.as_str().to_owned());
+        Box::pin(async { Ok(HecResponse::success()) })
+    }
+}
+
+#[test]
+fn custom_transports_receive_batches() {
+    let recorder = Recorder::default();
+    // no endpoint or token, the transport is on its own there
+    let (layer, guard) = SplunkHecLayer::builder()
+        .transport(recorder.clone())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request", answer = 42).in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let batches = recorder.0.lock().unwrap();
+    assert_eq!(batches.len(), 1);
+    let record: serde_json::Value = serde_json::from_str(&batches[0]).unwrap();
+    assert_eq!(record["event"]["answer"], 42);
+}
+
+#[cfg(feature = "reqwest")]
+#[test]
+fn reqwest_transport_ships_to_hec() {
+    use tracing_splunk_layer::ReqwestTransport;
+
+    let hec = MockHec::start();
+    let transport = ReqwestTransport::new(hec.url(), "abc").unwrap();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .transport(transport)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].header("authorization"), Some("Splunk abc"));
+}
+
+// an io::Write into a buffer the test can look at afterwards
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writers_get_newline_delimited_json() {
+    let buffer = Buffer::default();
+    let make_writer = buffer.clone();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .writer(move || make_writer.clone())
+        .index("main")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("first").in_scope(|| {});
+    info_span!("second").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let records: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["index"], "main");
+    assert_eq!(records[0]["event"]["name"], "first");
+    assert_eq!(records[1]["event"]["name"], "second");
+    assert!(output.ends_with('\n'));
+}
+
+#[test]
+fn raw_endpoint_gets_formatted_lines() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .raw_endpoint(Logfmt)
+        .with_hostname(false)
+        .with_pid(false)
+        .with_executable(false)
+        .sourcetype("app logs")
+        .index("main")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!(user = "bob", note = "a \"quoted\" word", "logged in");
+    info_span!("request", attempt = 2).in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let requests = hec.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(
+        request.path,
+        "/services/collector/raw?sourcetype=app%20logs&index=main"
+    );
+    assert!(request.header("x-splunk-request-channel").is_some());
+    assert_eq!(request.header("content-type"), Some("text/plain"));
+
+    let lines: Vec<_> = request.body.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("time="));
+    assert!(lines[0].contains(" message=\"logged in\" "));
+    assert!(lines[0].contains(" note=\"a \\\"quoted\\\" word\""));
+    assert!(lines[0].contains(" user=bob"));
+    assert!(lines[1].contains(" attempt=2"));
+    assert!(lines[1].contains(" name=request"));
+}
+
+#[test]
+fn raw_lines_can_be_formatted_any_way() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .raw_endpoint(|record: &EventRecord| format!("span {}", record.event["name"]))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    assert_eq!(hec.requests()[0].body, "span \"request\"");
+}
+
+#[test]
+fn destinations_get_what_their_filters_let_through() {
+    let hec = MockHec::start();
+    let errors = Recorder::default();
+    let audit = Recorder::default();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .destination(Destination::new(errors.clone()).max_level(Level::ERROR))
+        .destination(
+            Destination::new(audit.clone()).filter(|record| record.event.contains_key("user")),
+        )
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info_span!("request").in_scope(|| {});
+    error_span!("failed").in_scope(|| {});
+    info_span!("login", user = "ada").in_scope(|| {});
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let names = |batches: &[String]| -> Vec<String> {
+        batches
+            .iter()
+            .flat_map(|batch| batch.lines())
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .map(|record| record["event"]["name"].as_str().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(hec.requests()[0].events().len(), 3);
+    assert_eq!(names(&errors.0.lock().unwrap()), vec!["failed"]);
+    assert_eq!(names(&audit.0.lock().unwrap()), vec!["login"]);
+    assert_eq!(guard.destination_metrics()[0].snapshot().events_sent, 1);
+}
+
+#[test]
+fn batches_share_a_kept_alive_connection() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for batch in 0..3 {
+        info!(batch, "shipped");
+        guard.flush(Duration::from_secs(5)).unwrap();
+    }
+    assert_eq!(hec.requests().len(), 3);
+    assert_eq!(hec.connections(), 1);
+}
+
+#[test]
+fn idle_connections_are_closed_after_the_pool_idle_timeout() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .pool_idle_timeout(Duration::from_millis(50))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("before");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    info!("after");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    assert_eq!(hec.connections(), 2);
+}
+
+#[test]
+fn the_file_transport_rotates_and_compresses() {
+    let dir =
+        std::env::temp_dir().join(format!("tracing-splunk-layer-file-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("events.json");
+    let (layer, guard) = SplunkHecLayer::builder()
+        .file(FileConfig {
+            // every batch gets a file of its own
+            max_bytes: Some(1),
+            max_files: Some(2),
+            compress: true,
+            ..FileConfig::new(&path)
+        })
+        .max_batch_events(1)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    for i in 1..=4 {
+        info!("{}", i);
+        guard.flush(Duration::from_secs(5)).unwrap();
+    }
+
+    let message = |text: &str| {
+        let line: serde_json::Value = serde_json::from_str(text.trim_end()).unwrap();
+        line["event"]["message"].as_str().unwrap().to_owned()
+    };
+    let rotated = |n: u32| {
+        let file = std::fs::File::open(dir.join(format!("events.json.{}.gz", n))).unwrap();
+        let mut text = String::new();
+        flate2::read::GzDecoder::new(file)
+            .read_to_string(&mut text)
+            .unwrap();
+        message(&text)
+    };
+    assert_eq!(message(&std::fs::read_to_string(&path).unwrap()), "4");
+    assert_eq!(rotated(3), "3");
+    assert_eq!(rotated(2), "2");
+    // the oldest is gone, there's only room to keep two
+    assert!(!dir.join("events.json.1.gz").exists());
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn the_tcp_transport_writes_a_line_per_event() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .tcp(listener.local_addr().unwrap().to_string())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("first");
+    info!("second");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    for expected in ["first", "second"] {
+        let line: serde_json::Value =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(line["event"]["message"], expected);
+    }
+}
+
+#[test]
+fn the_tcp_transport_connects_again_once_the_input_is_back() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .transport(TcpTransport::new(addr.to_string()))
+        .retry_policy(RetryPolicy::none())
+        .error_policy(ErrorPolicy::Ignore)
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+    let message = |stream| {
+        let line = BufReader::new(stream).lines().next().unwrap().unwrap();
+        let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+        event["event"]["message"].as_str().unwrap().to_owned()
+    };
+
+    info!("before");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    assert_eq!(message(stream), "before");
+    drop(listener);
+
+    // the first write after the input went away can look like it worked, the next can't, and
+    // connecting again fails while there's nothing listening
+    for _ in 0..3 {
+        info!("while it was down");
+        guard.flush(Duration::from_secs(5)).unwrap();
+    }
+
+    let listener = TcpListener::bind(addr).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    // keep sending until the backoff has passed and it's connected again
+    let stream = loop {
+        info!("after");
+        guard.flush(Duration::from_secs(5)).unwrap();
+        match listener.accept() {
+            Ok((stream, _)) => break stream,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock && Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            Err(e) => panic!("never connected again: {}", e),
+        }
+    };
+    stream.set_nonblocking(false).unwrap();
+    assert_eq!(message(stream), "after");
+}
+
+#[test]
+fn the_udp_transport_sends_a_datagram_per_event() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .udp(socket.local_addr().unwrap().to_string())
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("first");
+    info!("second");
+    guard.flush(Duration::from_secs(5)).unwrap();
+    let mut buf = [0; 65536];
+    for expected in ["first", "second"] {
+        let len = socket.recv(&mut buf).unwrap();
+        let event: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+        assert_eq!(event["event"]["message"], expected);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn the_unix_transports_write_to_a_local_socket() {
+    use std::os::unix::net::{UnixDatagram, UnixListener};
+    use tracing_splunk_layer::UnixDatagramTransport;
+
+    let dir =
+        std::env::temp_dir().join(format!("tracing-splunk-layer-unix-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let listener = UnixListener::bind(dir.join("stream.sock")).unwrap();
+    let datagrams = UnixDatagram::bind(dir.join("datagram.sock")).unwrap();
+    datagrams
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .unix_stream(dir.join("stream.sock"))
+        .destination(Destination::new(UnixDatagramTransport::new(
+            dir.join("datagram.sock"),
+        )))
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    info!("hello");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let (stream, _) = listener.accept().unwrap();
+    let line = BufReader::new(stream).lines().next().unwrap().unwrap();
+    let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(event["event"]["message"], "hello");
+    let mut buf = [0; 65536];
+    let len = datagrams.recv(&mut buf).unwrap();
+    let event: serde_json::Value = serde_json::from_slice(&buf[..len]).unwrap();
+    assert_eq!(event["event"]["message"], "hello");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,29 @@
+#![cfg(all(tracing_unstable, feature = "valuable"))]
+
+use crate::common::MockHec;
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::info;
+use tracing_splunk_layer::SplunkHecLayer;
+use tracing_subscriber::prelude::*;
+
+#[test]
+fn valuable_fields_are_nested_json() {
+    let hec = MockHec::start();
+    let (layer, guard) = SplunkHecLayer::builder()
+        .endpoint(hec.url())
+        .token("abc")
+        .build()
+        .unwrap();
+    let _default = tracing_subscriber::registry().with(layer).set_default();
+
+    let cart = HashMap::from([("apples", vec![1u32, 2]), ("pears", vec![])]);
+    info!(cart = tracing::field::valuable(&cart), "checked out");
+    guard.flush(Duration::from_secs(5)).unwrap();
+
+    let event = &hec.requests()[0].events()[0]["event"];
+    assert_eq!(
+        event["cart"],
+        serde_json::json!({"apples": [1, 2], "pears": []})
+    );
+}